@@ -0,0 +1,153 @@
+use crate::database::DbState;
+use crate::music::{Song, SONG_COLUMNS};
+use crate::sort_key::{build_index, IndexedList};
+use rusqlite::{Connection, ToSql};
+use serde::Serialize;
+use tauri::State;
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct AudioQuality {
+    pub bitrate: u32,
+    pub sample_rate: u32,
+    pub bit_depth: Option<u8>,
+    pub format: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct AlbumSummary {
+    pub id: i64,
+    pub title: String,
+    pub album_artist: String,
+    pub sort_key: String,
+    pub track_count: u32,
+    pub disc_count: u32,
+    pub total_duration: u64,
+    pub year: Option<u32>,
+    // 已缓存的封面 (可能为空)，以及可用于 get_song_cover_thumbnail 的代表曲目
+    pub cover_path: Option<String>,
+    pub cover_song_path: String,
+    // 专辑内音质最好的一轨：位深 > 采样率 > 码率
+    pub best_quality: AudioQuality,
+}
+
+// --- 专辑归组：album_artist (缺省用 artist) + 专辑名 ---
+
+pub fn upsert_album(
+    conn: &Connection,
+    title: &str,
+    album_artist: &str,
+    title_sort: &str,
+    album_artist_sort: &str,
+) -> rusqlite::Result<i64> {
+    conn.execute(
+        "INSERT INTO albums (title, album_artist, title_sort, album_artist_sort) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(title, album_artist) DO UPDATE SET
+            title_sort = excluded.title_sort, album_artist_sort = excluded.album_artist_sort",
+        (title, album_artist, title_sort, album_artist_sort),
+    )?;
+    conn.query_row(
+        "SELECT id FROM albums WHERE title = ?1 AND album_artist = ?2",
+        (title, album_artist),
+        |row| row.get(0),
+    )
+}
+
+pub fn prune_empty_albums(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM albums WHERE id NOT IN (SELECT album_id FROM songs WHERE album_id IS NOT NULL)",
+        [],
+    )?;
+    Ok(())
+}
+
+// 专辑聚合查询，filter 为追加在外层的 WHERE 条件 (针对 albums a)
+pub fn query_albums(
+    conn: &Connection,
+    filter: &str,
+    params: &[&dyn ToSql],
+) -> rusqlite::Result<Vec<AlbumSummary>> {
+    let sql = format!(
+        "WITH ranked AS (
+            SELECT s.*,
+                   ROW_NUMBER() OVER (
+                       PARTITION BY s.album_id
+                       ORDER BY COALESCE(s.bit_depth, 0) DESC, COALESCE(s.sample_rate, 0) DESC, COALESCE(s.bitrate, 0) DESC
+                   ) AS quality_rank,
+                   ROW_NUMBER() OVER (
+                       PARTITION BY s.album_id
+                       ORDER BY COALESCE(s.disc_number, 1), COALESCE(s.track_number, 2147483647), s.path
+                   ) AS track_rank
+            FROM songs s
+            WHERE s.album_id IS NOT NULL
+        )
+        SELECT a.id, a.title, a.album_artist, COALESCE(a.title_sort, ''),
+               COUNT(*), COUNT(DISTINCT COALESCE(r.disc_number, 1)), COALESCE(SUM(r.duration), 0), MAX(r.year),
+               MAX(r.cover_path),
+               MAX(CASE WHEN r.track_rank = 1 THEN r.path END),
+               MAX(CASE WHEN r.quality_rank = 1 THEN r.bitrate END),
+               MAX(CASE WHEN r.quality_rank = 1 THEN r.sample_rate END),
+               MAX(CASE WHEN r.quality_rank = 1 THEN r.bit_depth END),
+               MAX(CASE WHEN r.quality_rank = 1 THEN r.format END)
+        FROM albums a
+        JOIN ranked r ON r.album_id = a.id
+        {}
+        GROUP BY a.id
+        ORDER BY a.title_sort, a.album_artist_sort, a.title COLLATE NOCASE",
+        if filter.is_empty() { String::new() } else { format!("WHERE {}", filter) }
+    );
+
+    let mut stmt = conn.prepare(&sql)?;
+    let albums = stmt
+        .query_map(params, |row| {
+            Ok(AlbumSummary {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                album_artist: row.get(2)?,
+                sort_key: row.get(3)?,
+                track_count: row.get(4)?,
+                disc_count: row.get(5)?,
+                total_duration: row.get::<_, i64>(6)? as u64,
+                year: row.get(7)?,
+                cover_path: row.get(8)?,
+                cover_song_path: row.get::<_, Option<String>>(9)?.unwrap_or_default(),
+                best_quality: AudioQuality {
+                    bitrate: row.get::<_, Option<u32>>(10)?.unwrap_or(0),
+                    sample_rate: row.get::<_, Option<u32>>(11)?.unwrap_or(0),
+                    bit_depth: row.get(12)?,
+                    format: row.get::<_, Option<String>>(13)?.unwrap_or_default(),
+                },
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(albums)
+}
+
+pub fn query_album_tracks(conn: &Connection, album_id: i64) -> rusqlite::Result<Vec<Song>> {
+    let sql = format!(
+        "SELECT {} FROM songs WHERE album_id = ?1
+         ORDER BY COALESCE(disc_number, 1), COALESCE(track_number, 2147483647), title COLLATE NOCASE, path",
+        SONG_COLUMNS
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let songs = stmt
+        .query_map([album_id], Song::from_row)?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(songs)
+}
+
+// --- Commands ---
+
+#[tauri::command]
+pub async fn get_albums(db_state: State<'_, DbState>) -> Result<IndexedList<AlbumSummary>, String> {
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    let albums = query_albums(&conn, "", &[]).map_err(|e| e.to_string())?;
+    Ok(build_index(albums, |a| &a.sort_key))
+}
+
+#[tauri::command]
+pub async fn get_album_tracks(album_id: i64, db_state: State<'_, DbState>) -> Result<Vec<Song>, String> {
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    query_album_tracks(&conn, album_id).map_err(|e| e.to_string())
+}
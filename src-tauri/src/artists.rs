@@ -0,0 +1,517 @@
+use crate::albums::{query_albums, AlbumSummary};
+use crate::database::DbState;
+use crate::metadata::UNKNOWN_ARTIST;
+use crate::music::{Song, SONG_COLUMNS};
+use crate::settings::{load_setting, save_setting};
+use crate::sort_key::{build_index, sort_key, IndexedList};
+use rusqlite::{Connection, ToSql};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+const SPLIT_CONFIG_KEY: &str = "artist_split";
+
+// 多值标签 (如多个 TPE1 / ARTIST 字段) 在 songs 表中以此拼接展示，
+// 重建索引时无论配置如何都会按它拆开
+pub const MULTI_VALUE_JOINER: &str = "; ";
+
+// --- 艺人 / 流派拆分配置 ---
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArtistSplitConfig {
+    // 分隔符，大小写不敏感，如 "/"、" feat. "；" & " 误拆组合名的情况太多，默认不启用
+    pub separators: Vec<String>,
+    // 不参与拆分的完整名字，如 "AC/DC"
+    pub exceptions: Vec<String>,
+}
+
+impl Default for ArtistSplitConfig {
+    fn default() -> Self {
+        Self {
+            separators: [";", "；", "/", "、", " feat. ", " (feat. ", " feat ", " ft. ", " (ft. ", " featuring "]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            exceptions: ["AC/DC", "Simon & Garfunkel", "Earth, Wind & Fire", "Hall & Oates", "Mumford & Sons"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl ArtistSplitConfig {
+    pub fn load(conn: &Connection) -> Self {
+        load_setting(conn, SPLIT_CONFIG_KEY)
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ArtistSummary {
+    pub id: i64,
+    pub name: String,
+    pub sort_key: String,
+    pub track_count: u32,
+    pub album_count: u32,
+    pub total_duration: u64,
+    pub cover_path: Option<String>,
+    pub cover_song_path: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ArtistDetail {
+    pub artist: ArtistSummary,
+    // 该艺人以任意身份 (演唱 / 专辑艺人) 参与的专辑
+    pub albums: Vec<AlbumSummary>,
+    pub tracks: Vec<Song>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct GenreSummary {
+    pub id: i64,
+    pub name: String,
+    pub track_count: u32,
+}
+
+// 去掉拆分后残留的半边括号："A (feat. B)" -> "A" + "B"
+fn trim_dangling_brackets(part: &str) -> &str {
+    let mut s = part.trim();
+    loop {
+        let opens = s.matches(['(', '[', '（']).count();
+        let closes = s.matches([')', ']', '）']).count();
+        if opens > closes && s.ends_with(['(', '[', '（']) {
+            s = s[..s.len() - s.chars().last().map(|c| c.len_utf8()).unwrap_or(0)].trim_end();
+        } else if closes > opens && s.ends_with([')', ']', '）']) {
+            s = s[..s.len() - s.chars().last().map(|c| c.len_utf8()).unwrap_or(0)].trim_end();
+        } else if closes > opens && s.starts_with([')', ']', '）']) {
+            s = s[s.chars().next().map(|c| c.len_utf8()).unwrap_or(0)..].trim_start();
+        } else {
+            return s;
+        }
+    }
+}
+
+// 按配置把一个字段拆成多个名字，exceptions 中的名字整体保留
+pub fn split_names(value: &str, config: &ArtistSplitConfig) -> Vec<String> {
+    // 只做 ASCII 小写，保证字节偏移与原串一致
+    let lower = value.to_ascii_lowercase();
+
+    let mut protected: Vec<(usize, usize)> = Vec::new();
+    for exception in &config.exceptions {
+        let needle = exception.to_ascii_lowercase();
+        if needle.is_empty() {
+            continue;
+        }
+        let mut from = 0;
+        while let Some(pos) = lower[from..].find(&needle) {
+            let start = from + pos;
+            protected.push((start, start + needle.len()));
+            from = start + needle.len();
+        }
+    }
+
+    let mut separators: Vec<String> = config
+        .separators
+        .iter()
+        .chain(std::iter::once(&MULTI_VALUE_JOINER.trim().to_string()))
+        .map(|s| s.to_ascii_lowercase())
+        .filter(|s| !s.is_empty())
+        .collect();
+    // 长的优先，避免 " feat. " 被 " feat " 抢先匹配
+    separators.sort_by_key(|s| std::cmp::Reverse(s.len()));
+
+    let mut pieces = Vec::new();
+    let mut last = 0;
+    let mut i = 0;
+    while i < value.len() {
+        if let Some(&(_, end)) = protected.iter().find(|(s, e)| *s <= i && i < *e) {
+            i = end;
+            continue;
+        }
+        if let Some(sep) = separators.iter().find(|sep| lower[i..].starts_with(sep.as_str())) {
+            pieces.push(&value[last..i]);
+            i += sep.len();
+            last = i;
+            continue;
+        }
+        i += value[i..].chars().next().map(|c| c.len_utf8()).unwrap_or(1);
+    }
+    pieces.push(&value[last..]);
+
+    let was_split = pieces.len() > 1;
+    let mut names: Vec<String> = Vec::new();
+    for piece in pieces {
+        let name = if was_split { trim_dangling_brackets(piece) } else { piece.trim() };
+        if !name.is_empty() && !names.iter().any(|n| n.eq_ignore_ascii_case(name)) {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
+
+// 对一组原始标签值逐个拆分并去重
+pub fn split_values(values: &[String], config: &ArtistSplitConfig) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for value in values {
+        for name in split_names(value, config) {
+            if !names.iter().any(|n| n.eq_ignore_ascii_case(&name)) {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
+
+// --- 写入关联表 ---
+
+fn upsert_name(conn: &Connection, table: &str, name: &str) -> rusqlite::Result<i64> {
+    conn.execute(
+        &format!("INSERT OR IGNORE INTO {} (name, sort_name) VALUES (?1, ?2)", table),
+        (name, sort_key(name)),
+    )?;
+    conn.query_row(&format!("SELECT id FROM {} WHERE name = ?1", table), [name], |row| row.get(0))
+}
+
+pub fn link_song(
+    conn: &Connection,
+    song_id: i64,
+    artists: &[String],
+    album_artists: &[String],
+    genres: &[String],
+    config: &ArtistSplitConfig,
+) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM song_artists WHERE song_id = ?1", [song_id])?;
+    conn.execute("DELETE FROM song_genres WHERE song_id = ?1", [song_id])?;
+
+    for (role, values) in [("artist", artists), ("album_artist", album_artists)] {
+        for (position, name) in split_values(values, config).iter().enumerate() {
+            let artist_id = upsert_name(conn, "artists", name)?;
+            conn.execute(
+                "INSERT OR IGNORE INTO song_artists (song_id, artist_id, role, position) VALUES (?1, ?2, ?3, ?4)",
+                (song_id, artist_id, role, position as i64),
+            )?;
+        }
+    }
+
+    for (position, name) in split_values(genres, config).iter().enumerate() {
+        let genre_id = upsert_name(conn, "genres", name)?;
+        conn.execute(
+            "INSERT OR IGNORE INTO song_genres (song_id, genre_id, position) VALUES (?1, ?2, ?3)",
+            (song_id, genre_id, position as i64),
+        )?;
+    }
+    Ok(())
+}
+
+// 单艺人歌曲的 TSOP 排序名覆盖该艺人的 sort_name；多艺人时无法对应，保持转写结果
+fn apply_artist_sort_tag(conn: &Connection, song_id: i64, sort_tag: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE artists SET sort_name = ?1
+         WHERE id = (SELECT artist_id FROM song_artists WHERE song_id = ?2 AND role = 'artist')
+           AND (SELECT COUNT(*) FROM song_artists WHERE song_id = ?2 AND role = 'artist') = 1",
+        (sort_key(sort_tag), song_id),
+    )?;
+    Ok(())
+}
+
+// 建立关联所用的原始标签值，扫描时以 JSON 存入 songs.link_tags，拆分配置变更后据此重建
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LinkTags {
+    pub artists: Vec<String>,
+    pub album_artists: Vec<String>,
+    pub genres: Vec<String>,
+    // 标签中原样的艺人排序名 (TSOP / ARTISTSORT)
+    pub artist_sort: Option<String>,
+}
+
+// 扫描与重建共用：写入关联表，再应用艺人排序名
+pub fn link_song_tags(conn: &Connection, song_id: i64, tags: &LinkTags, config: &ArtistSplitConfig) -> rusqlite::Result<()> {
+    link_song(conn, song_id, &tags.artists, &tags.album_artists, &tags.genres, config)?;
+    if let Some(sort) = tags.artist_sort.as_deref() {
+        apply_artist_sort_tag(conn, song_id, sort)?;
+    }
+    Ok(())
+}
+
+// 清理已没有任何歌曲引用的艺人 / 流派
+pub fn prune_orphans(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "DELETE FROM artists WHERE id NOT IN (SELECT artist_id FROM song_artists);
+         DELETE FROM genres WHERE id NOT IN (SELECT genre_id FROM song_genres);",
+    )
+}
+
+// id, artist, album_artist, genre, link_tags
+type LinkFields = (i64, Option<String>, Option<String>, Option<String>, Option<String>);
+
+// 配置变更后重建全部关联 (无需重新读文件)。优先用扫描时存下的原始标签值；
+// 旧版本扫描、还没有 link_tags 的行退回到 songs 表中拼接后的字段
+fn rebuild_links(conn: &mut Connection, config: &ArtistSplitConfig) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    {
+        let mut stmt = tx.prepare(
+            "SELECT id, artist, album_artist, genre, link_tags FROM songs WHERE meta_version IS NOT NULL",
+        )?;
+        let rows: Vec<LinkFields> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        for (id, artist, album_artist, genre, link_tags) in rows {
+            let tags = link_tags
+                .and_then(|json| serde_json::from_str(&json).ok())
+                .unwrap_or_else(|| LinkTags {
+                    artists: artist.into_iter().filter(|a| a != UNKNOWN_ARTIST).collect(),
+                    album_artists: album_artist.into_iter().collect(),
+                    genres: genre.into_iter().collect(),
+                    artist_sort: None,
+                });
+            link_song_tags(&tx, id, &tags, config)?;
+        }
+        prune_orphans(&tx)?;
+    }
+    tx.commit()
+}
+
+// --- Commands ---
+
+// 曲目按任意身份统计：只作为专辑艺人出现的艺人也能显示其专辑里的曲目数
+fn query_artists(conn: &Connection, filter: &str, params: &[&dyn ToSql]) -> rusqlite::Result<Vec<ArtistSummary>> {
+    let sql = format!(
+        "SELECT a.id, a.name, COALESCE(a.sort_name, ''),
+                COUNT(sa.song_id) AS track_count,
+                COUNT(DISTINCT s.album_id) AS album_count,
+                COALESCE(SUM(s.duration), 0) AS total_duration,
+                MAX(s.cover_path),
+                MIN(s.path)
+         FROM artists a
+         JOIN (SELECT DISTINCT artist_id, song_id FROM song_artists) sa ON sa.artist_id = a.id
+         JOIN songs s ON s.id = sa.song_id
+         {}
+         GROUP BY a.id
+         ORDER BY a.sort_name, a.name COLLATE NOCASE",
+        if filter.is_empty() { String::new() } else { format!("WHERE {}", filter) }
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let artists = stmt
+        .query_map(params, |row| {
+            Ok(ArtistSummary {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                sort_key: row.get(2)?,
+                track_count: row.get(3)?,
+                album_count: row.get(4)?,
+                total_duration: row.get::<_, i64>(5)? as u64,
+                cover_path: row.get(6)?,
+                cover_song_path: row.get::<_, Option<String>>(7)?.unwrap_or_default(),
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(artists)
+}
+
+#[tauri::command]
+pub async fn get_artists(db_state: State<'_, DbState>) -> Result<IndexedList<ArtistSummary>, String> {
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    let artists = query_artists(&conn, "", &[]).map_err(|e| e.to_string())?;
+    Ok(build_index(artists, |a| &a.sort_key))
+}
+
+#[tauri::command]
+pub async fn get_artist_detail(artist_id: i64, db_state: State<'_, DbState>) -> Result<ArtistDetail, String> {
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+
+    let artist = query_artists(&conn, "a.id = ?1", &[&artist_id])
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .next()
+        .ok_or_else(|| "艺人不存在".to_string())?;
+
+    let albums = query_albums(
+        &conn,
+        "a.id IN (SELECT s.album_id FROM songs s JOIN song_artists sa ON sa.song_id = s.id WHERE sa.artist_id = ?1)",
+        &[&artist_id],
+    )
+    .map_err(|e| e.to_string())?;
+
+    let sql = format!(
+        "SELECT {} FROM songs
+         WHERE id IN (SELECT song_id FROM song_artists WHERE artist_id = ?1)
+         ORDER BY year DESC, album COLLATE NOCASE, COALESCE(disc_number, 1), COALESCE(track_number, 2147483647), path",
+        SONG_COLUMNS
+    );
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let tracks = stmt
+        .query_map([artist_id], Song::from_row)
+        .map_err(|e| e.to_string())?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(ArtistDetail { artist, albums, tracks })
+}
+
+#[tauri::command]
+pub async fn get_genres(db_state: State<'_, DbState>) -> Result<Vec<GenreSummary>, String> {
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT g.id, g.name, COUNT(DISTINCT sg.song_id)
+             FROM genres g
+             JOIN song_genres sg ON sg.genre_id = g.id
+             GROUP BY g.id
+             ORDER BY COUNT(DISTINCT sg.song_id) DESC, g.sort_name",
+        )
+        .map_err(|e| e.to_string())?;
+    let genres = stmt
+        .query_map([], |row| {
+            Ok(GenreSummary {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                track_count: row.get(2)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(genres)
+}
+
+#[tauri::command]
+pub fn get_artist_split_config(db_state: State<'_, DbState>) -> Result<ArtistSplitConfig, String> {
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    Ok(ArtistSplitConfig::load(&conn))
+}
+
+#[tauri::command]
+pub async fn set_artist_split_config(
+    config: ArtistSplitConfig,
+    db_state: State<'_, DbState>,
+) -> Result<(), String> {
+    let db_conn = db_state.conn.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut conn = db_conn.lock().map_err(|e| e.to_string())?;
+        save_setting(&conn, SPLIT_CONFIG_KEY, &config)?;
+        rebuild_links(&mut conn, &config).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> ArtistSplitConfig {
+        ArtistSplitConfig::default()
+    }
+
+    #[test]
+    fn splits_on_default_separators() {
+        assert_eq!(split_names("周杰伦/费玉清", &config()), ["周杰伦", "费玉清"]);
+        assert_eq!(split_names("A; B；C、D", &config()), ["A", "B", "C", "D"]);
+        assert_eq!(split_names("Artist feat. Guest", &config()), ["Artist", "Guest"]);
+    }
+
+    #[test]
+    fn trims_brackets_left_by_feat() {
+        assert_eq!(split_names("Artist (feat. Guest)", &config()), ["Artist", "Guest"]);
+        assert_eq!(split_names("Artist (FT. Guest)", &config()), ["Artist", "Guest"]);
+    }
+
+    #[test]
+    fn keeps_ampersand_by_default() {
+        assert_eq!(split_names("Tom & Jerry", &config()), ["Tom & Jerry"]);
+    }
+
+    #[test]
+    fn exceptions_survive_opt_in_separators() {
+        let mut config = config();
+        config.separators.push(" & ".to_string());
+        assert_eq!(split_names("AC/DC", &config), ["AC/DC"]);
+        assert_eq!(split_names("Simon & Garfunkel & Friend", &config), ["Simon & Garfunkel", "Friend"]);
+        assert_eq!(split_names("Tom & Jerry", &config), ["Tom", "Jerry"]);
+    }
+
+    #[test]
+    fn dedups_case_insensitively() {
+        assert_eq!(split_names("Band / band / Other", &config()), ["Band", "Other"]);
+        let values = vec!["A/B".to_string(), "b; C".to_string()];
+        assert_eq!(split_values(&values, &config()), ["A", "B", "C"]);
+    }
+
+    #[test]
+    fn unsplit_value_is_only_trimmed() {
+        assert_eq!(split_names("  Solo (Live)  ", &config()), ["Solo (Live)"]);
+        assert!(split_names("   ", &config()).is_empty());
+    }
+
+    fn links_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE songs (
+                id INTEGER PRIMARY KEY, artist TEXT, album_artist TEXT, genre TEXT, link_tags TEXT, meta_version INTEGER
+            );
+            CREATE TABLE artists (id INTEGER PRIMARY KEY, name TEXT NOT NULL UNIQUE COLLATE NOCASE, sort_name TEXT);
+            CREATE TABLE song_artists (
+                song_id INTEGER NOT NULL, artist_id INTEGER NOT NULL, role TEXT NOT NULL DEFAULT 'artist',
+                position INTEGER NOT NULL DEFAULT 0, PRIMARY KEY (song_id, artist_id, role)
+            );
+            CREATE TABLE genres (id INTEGER PRIMARY KEY, name TEXT NOT NULL UNIQUE COLLATE NOCASE, sort_name TEXT);
+            CREATE TABLE song_genres (
+                song_id INTEGER NOT NULL, genre_id INTEGER NOT NULL, position INTEGER NOT NULL DEFAULT 0,
+                PRIMARY KEY (song_id, genre_id)
+            );",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn linked_artists(conn: &Connection, song_id: i64) -> Vec<(String, String)> {
+        let mut stmt = conn
+            .prepare(
+                "SELECT a.name, a.sort_name FROM song_artists sa JOIN artists a ON a.id = sa.artist_id
+                 WHERE sa.song_id = ?1 AND sa.role = 'artist' ORDER BY sa.position",
+            )
+            .unwrap();
+        stmt.query_map([song_id], |row| Ok((row.get(0)?, row.get(1)?))).unwrap().map(|r| r.unwrap()).collect()
+    }
+
+    #[test]
+    fn rebuild_keeps_tag_values_and_sort_names() {
+        let mut conn = links_db();
+        let listed = LinkTags {
+            artists: vec!["Tom".to_string(), "Jerry".to_string()],
+            genres: vec!["Pop".to_string()],
+            ..Default::default()
+        };
+        let sorted = LinkTags {
+            artists: vec!["The Band".to_string()],
+            artist_sort: Some("Band, The".to_string()),
+            ..Default::default()
+        };
+        for (id, artist, tags) in [(1, "Tom & Jerry", &listed), (2, "The Band", &sorted)] {
+            conn.execute(
+                "INSERT INTO songs (id, artist, genre, link_tags, meta_version) VALUES (?1, ?2, 'Pop', ?3, 8)",
+                (id, artist, serde_json::to_string(tags).unwrap()),
+            )
+            .unwrap();
+            link_song_tags(&conn, id, tags, &config()).unwrap();
+        }
+        // 旧版本扫描的行没有 link_tags，按拼接后的字段拆分
+        conn.execute("INSERT INTO songs (id, artist, meta_version) VALUES (3, 'A/B', 7)", []).unwrap();
+
+        // 重建时艺人行重新创建，排序名要从 link_tags 再应用一次
+        conn.execute_batch("DELETE FROM song_artists; DELETE FROM artists;").unwrap();
+
+        let mut changed = config();
+        changed.separators.push(" & ".to_string());
+        rebuild_links(&mut conn, &changed).unwrap();
+
+        let names = |id| linked_artists(&conn, id).into_iter().map(|(name, _)| name).collect::<Vec<_>>();
+        assert_eq!(names(1), ["Tom", "Jerry"]);
+        assert_eq!(linked_artists(&conn, 2), [("The Band".to_string(), sort_key("Band, The"))]);
+        assert_eq!(names(3), ["A", "B"]);
+    }
+}
+
@@ -0,0 +1,361 @@
+use crate::database::DbState;
+use crate::music::{embedded_cover, ScannedCover};
+use crate::settings::{load_setting, save_setting};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::imageops::FilterType;
+use image::metadata::Orientation;
+use image::{DynamicImage, ExtendedColorType, ImageDecoder, ImageFormat, ImageReader};
+use lofty::config::WriteOptions;
+use lofty::picture::{MimeType, Picture, PictureType};
+use lofty::prelude::*;
+use lofty::probe::Probe;
+use lofty::file::TaggedFile;
+use lofty::tag::{Tag, TagType};
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, State};
+
+// --- 封面来源与转码 ---
+// 文件夹封面：专辑目录下的 cover.jpg / folder.jpg / Front.png 等，
+// 文件名按列表顺序匹配，不区分大小写；不带扩展名的条目匹配任意图片格式。
+// 缓存的封面统一解码后按 EXIF 方向摆正、缩放并重新编码，避免 CMYK JPEG、超大 PNG 等 webview 显示不了的图
+
+const COVER_CONFIG_KEY: &str = "covers";
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp", "bmp", "gif"];
+// get_song_cover_sized 的标准尺寸 (最长边像素)，请求的尺寸向上取到最近的一档
+pub const COVER_SIZES: &[u32] = &[64, 128, 256, 512, 1024];
+const JPEG_QUALITY: u8 = 88;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CoverFormat {
+    #[default]
+    Jpeg,
+    // image 库只支持无损 WebP，体积通常比 JPEG 大，但保留透明通道
+    Webp,
+}
+
+impl CoverFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            CoverFormat::Jpeg => "jpg",
+            CoverFormat::Webp => "webp",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CoverConfig {
+    // 优先级从高到低，如 "cover"、"folder.jpg"、"Front.png"
+    pub sidecar_names: Vec<String>,
+    // true: 文件夹图片优先于内嵌封面；默认内嵌优先，文件夹图片兜底
+    pub prefer_sidecar: bool,
+    // 缓存封面的编码格式
+    pub format: CoverFormat,
+    // 完整封面的最长边上限，超过则缩小
+    pub max_full_size: u32,
+}
+
+impl Default for CoverConfig {
+    fn default() -> Self {
+        Self {
+            sidecar_names: ["cover", "folder", "front", "album", "albumart"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            prefer_sidecar: false,
+            format: CoverFormat::Jpeg,
+            max_full_size: 2048,
+        }
+    }
+}
+
+impl CoverConfig {
+    pub fn load(conn: &Connection) -> Self {
+        load_setting(conn, COVER_CONFIG_KEY)
+    }
+}
+
+// 图片处理在后台线程里进行，拿不到数据库时使用默认设置
+pub fn load_cover_config(app: &AppHandle) -> CoverConfig {
+    app.try_state::<DbState>()
+        .and_then(|db| db.conn.lock().ok().map(|conn| CoverConfig::load(&conn)))
+        .unwrap_or_default()
+}
+
+fn is_image(path: &Path) -> bool {
+    path.extension()
+        .map(|e| IMAGE_EXTENSIONS.contains(&e.to_string_lossy().to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+// 歌曲所在目录中第一个命中优先级列表的图片
+pub fn find_sidecar_cover(song_path: &Path, names: &[String]) -> Option<PathBuf> {
+    let images: Vec<(String, PathBuf)> = fs::read_dir(song_path.parent()?)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && is_image(path))
+        .map(|path| (path.file_name().unwrap_or_default().to_string_lossy().to_lowercase(), path))
+        .collect();
+    if images.is_empty() {
+        return None;
+    }
+
+    for name in names.iter().map(|n| n.trim().to_lowercase()).filter(|n| !n.is_empty()) {
+        if is_image(Path::new(&name)) {
+            if let Some((_, path)) = images.iter().find(|(file, _)| *file == name) {
+                return Some(path.clone());
+            }
+            continue;
+        }
+        // 同名多种格式时按 IMAGE_EXTENSIONS 的顺序取
+        for ext in IMAGE_EXTENSIONS {
+            let file_name = format!("{}.{}", name, ext);
+            if let Some((_, path)) = images.iter().find(|(file, _)| *file == file_name) {
+                return Some(path.clone());
+            }
+        }
+    }
+    None
+}
+
+pub fn standard_size(requested: u32) -> u32 {
+    COVER_SIZES
+        .iter()
+        .copied()
+        .find(|size| *size >= requested)
+        .unwrap_or(COVER_SIZES[COVER_SIZES.len() - 1])
+}
+
+// 解码 -> 按 EXIF 方向摆正 -> 最长边不超过 max_dim -> 重新编码
+pub fn render_cover(data: &[u8], max_dim: u32, format: CoverFormat) -> Result<Vec<u8>, String> {
+    let mut decoder = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|e| e.to_string())?
+        .into_decoder()
+        .map_err(|e| e.to_string())?;
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let mut img = DynamicImage::from_decoder(decoder).map_err(|e| e.to_string())?;
+    img.apply_orientation(orientation);
+    if img.width() > max_dim || img.height() > max_dim {
+        img = img.resize(max_dim, max_dim, FilterType::Triangle);
+    }
+
+    let mut out = Vec::new();
+    match format {
+        CoverFormat::Jpeg => JpegEncoder::new_with_quality(&mut out, JPEG_QUALITY)
+            .encode_image(&img.to_rgb8())
+            .map_err(|e| e.to_string())?,
+        CoverFormat::Webp => {
+            let rgba = img.to_rgba8();
+            WebPEncoder::new_lossless(&mut out)
+                .encode(&rgba, rgba.width(), rgba.height(), ExtendedColorType::Rgba8)
+                .map_err(|e| e.to_string())?
+        }
+    }
+    Ok(out)
+}
+
+// --- 写入文件的封面：嵌入、移除、导出 ---
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct EmbedCoverOptions {
+    // 最长边上限，超过则缩小；None 保持原尺寸
+    pub max_size: Option<u32>,
+    // 重新压缩为 JPEG (指定 max_size 时总会重新编码)
+    pub recompress: bool,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct CoverWriteReport {
+    pub processed: u32,
+    pub failed: Vec<String>,
+}
+
+// 待嵌入的图片；播放器普遍支持的格式原样写入，其余 (如 WebP) 转成 JPEG
+fn prepare_picture(data: Vec<u8>, options: &EmbedCoverOptions) -> Result<Picture, String> {
+    let format = image::guess_format(&data).map_err(|e| e.to_string())?;
+    let mime = match format {
+        _ if options.max_size.is_some() || options.recompress => None,
+        ImageFormat::Jpeg => Some(MimeType::Jpeg),
+        ImageFormat::Png => Some(MimeType::Png),
+        ImageFormat::Gif => Some(MimeType::Gif),
+        ImageFormat::Bmp => Some(MimeType::Bmp),
+        _ => None,
+    };
+    let (mime, data) = match mime {
+        Some(mime) => (mime, data),
+        None => (MimeType::Jpeg, render_cover(&data, options.max_size.unwrap_or(u32::MAX), CoverFormat::Jpeg)?),
+    };
+    Ok(Picture::new_unchecked(PictureType::CoverFront, Some(mime), None, data))
+}
+
+// 能存放图片的标签：RIFF INFO / AIFF 文本块放不下图片，WAV / AIFF 写到 ID3v2
+fn picture_tag_type(tagged_file: &TaggedFile) -> Option<TagType> {
+    [tagged_file.primary_tag_type(), TagType::Id3v2]
+        .into_iter()
+        .find(|t| !matches!(t, TagType::RiffInfo | TagType::AiffText) && tagged_file.supports_tag_type(*t))
+}
+
+// picture 为 None 时移除全部内嵌图片，否则替换封面 (CoverFront)
+fn write_embedded_cover(path: &Path, picture: Option<&Picture>) -> Result<(), String> {
+    let mut tagged_file = Probe::open(path)
+        .and_then(|p| p.read())
+        .map_err(|e| e.to_string())?;
+    let tag_type = picture_tag_type(&tagged_file)
+        .ok_or_else(|| format!("该格式不支持内嵌封面: {:?}", tagged_file.file_type()))?;
+    if tagged_file.tag(tag_type).is_none() {
+        if picture.is_none() {
+            return Ok(());
+        }
+        tagged_file.insert_tag(Tag::new(tag_type));
+    }
+    let tag = tagged_file.tag_mut(tag_type).ok_or("无法创建标签")?;
+
+    match picture {
+        Some(picture) => {
+            tag.remove_picture_type(PictureType::CoverFront);
+            tag.push_picture(picture.clone());
+        }
+        None => {
+            while !tag.pictures().is_empty() {
+                tag.remove_picture(0);
+            }
+        }
+    }
+    tag.save_to_path(path, WriteOptions::default()).map_err(|e| e.to_string())
+}
+
+// 写入后文件大小 / 修改时间可能不变 (同一秒内换了同样大小的图)，不能指望指纹失效，
+// 直接按新封面重建映射与缩略图
+fn refresh_song_cover(db: &Mutex<Connection>, path: &Path, embedded: Option<Vec<u8>>) -> Result<(), String> {
+    let config = CoverConfig::load(&*db.lock().map_err(|e| e.to_string())?);
+    let cover = ScannedCover::prepare(path, embedded, &config);
+
+    let conn = db.lock().map_err(|e| e.to_string())?;
+    let song_id: Option<i64> = conn
+        .query_row("SELECT id FROM songs WHERE path = ?1", [&*path.to_string_lossy()], |row| row.get(0))
+        .optional()
+        .map_err(|e| e.to_string())?;
+    let Some(song_id) = song_id else { return Ok(()) };
+    conn.execute("UPDATE songs SET cover_path = ?1 WHERE id = ?2", (&cover.thumbnail, song_id))
+        .map_err(|e| e.to_string())?;
+    cover.store(&conn, song_id).map_err(|e| e.to_string())
+}
+
+fn write_covers(db: &Mutex<Connection>, paths: &[String], picture: Option<&Picture>) -> CoverWriteReport {
+    let mut report = CoverWriteReport::default();
+    for path in paths {
+        let file = Path::new(path);
+        let result = write_embedded_cover(file, picture)
+            .and_then(|_| refresh_song_cover(db, file, picture.map(|p| p.data().to_vec())));
+        match result {
+            Ok(()) => report.processed += 1,
+            Err(e) => report.failed.push(format!("{}: {}", path, e)),
+        }
+    }
+    report
+}
+
+// --- Commands ---
+
+#[tauri::command]
+pub fn get_cover_config(db_state: State<'_, DbState>) -> Result<CoverConfig, String> {
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    Ok(load_setting(&conn, COVER_CONFIG_KEY))
+}
+
+#[tauri::command]
+pub fn set_cover_config(config: CoverConfig, db_state: State<'_, DbState>) -> Result<(), String> {
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    save_setting(&conn, COVER_CONFIG_KEY, &config)
+}
+
+// 把图片作为封面写入一首或多首歌 (如整张专辑)，已有封面会被替换
+#[tauri::command]
+pub async fn embed_cover(
+    paths: Vec<String>,
+    image_path: String,
+    options: Option<EmbedCoverOptions>,
+    db_state: State<'_, DbState>,
+) -> Result<CoverWriteReport, String> {
+    let data = fs::read(&image_path).map_err(|e| e.to_string())?;
+    let picture = prepare_picture(data, &options.unwrap_or_default())?;
+    let db = db_state.conn.clone();
+    tauri::async_runtime::spawn_blocking(move || write_covers(&db, &paths, Some(&picture)))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn remove_embedded_cover(paths: Vec<String>, db_state: State<'_, DbState>) -> Result<CoverWriteReport, String> {
+    let db = db_state.conn.clone();
+    tauri::async_runtime::spawn_blocking(move || write_covers(&db, &paths, None))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// 导出内嵌封面为同目录下的 cover.jpg，返回写入的路径；非 JPEG 的封面会转码
+#[tauri::command]
+pub async fn export_embedded_cover(path: String, overwrite: Option<bool>) -> Result<String, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let song = Path::new(&path);
+        let target = song.parent().ok_or("无效的文件路径")?.join("cover.jpg");
+        if target.exists() && !overwrite.unwrap_or(false) {
+            return Err(format!("{} 已存在", target.display()));
+        }
+        let data = embedded_cover(song).ok_or("该文件没有内嵌封面")?;
+        let bytes = match image::guess_format(&data) {
+            Ok(ImageFormat::Jpeg) => data,
+            _ => render_cover(&data, u32::MAX, CoverFormat::Jpeg)?,
+        };
+        fs::write(&target, bytes).map_err(|e| e.to_string())?;
+        Ok(target.to_string_lossy().into_owned())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    // 44.1kHz / 双声道 / 16bit，0.1 秒静音
+    fn wav_bytes() -> Vec<u8> {
+        let data = vec![0u8; 17640];
+        let mut bytes = b"RIFF".to_vec();
+        bytes.extend((36 + data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt \x10\0\0\0\x01\0\x02\0");
+        bytes.extend(44100u32.to_le_bytes());
+        bytes.extend((44100u32 * 4).to_le_bytes());
+        bytes.extend_from_slice(b"\x04\0\x10\0data");
+        bytes.extend((data.len() as u32).to_le_bytes());
+        bytes.extend(data);
+        bytes
+    }
+
+    #[test]
+    fn wav_cover_goes_to_id3v2() {
+        let dir = TempDir::new("artwork_test");
+        let path = dir.write("song.wav", &wav_bytes());
+        let cover = b"\x89PNG\r\n\x1a\n not really a png".to_vec();
+        let picture = Picture::new_unchecked(PictureType::CoverFront, Some(MimeType::Png), None, cover.clone());
+
+        write_embedded_cover(&path, Some(&picture)).unwrap();
+        let tagged_file = Probe::open(&path).unwrap().read().unwrap();
+        assert!(tagged_file.tag(TagType::Id3v2).is_some());
+        assert_eq!(embedded_cover(&path), Some(cover));
+
+        write_embedded_cover(&path, None).unwrap();
+        assert_eq!(embedded_cover(&path), None);
+    }
+}
@@ -0,0 +1,231 @@
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+// --- 音频流哈希：只哈希音频数据，跳过标签块，改标签 / 换封面后哈希不变 ---
+// MP3: 去掉开头的 ID3v2、结尾的 ID3v1 与 APEv2
+// FLAC: 跳过所有 METADATA_BLOCK，只哈希音频帧
+// WAV: 只哈希 data 块
+// 其他格式标签与音频交织，退化为整文件哈希
+
+fn read_at(file: &mut File, offset: u64, buf: &mut [u8]) -> std::io::Result<bool> {
+    file.seek(SeekFrom::Start(offset))?;
+    match file.read_exact(buf) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+// 开头的 ID3v2 标签长度 (含 10 字节头与可选的页脚)
+fn id3v2_size(file: &mut File, offset: u64) -> std::io::Result<u64> {
+    let mut header = [0u8; 10];
+    if !read_at(file, offset, &mut header)? || &header[0..3] != b"ID3" {
+        return Ok(0);
+    }
+    let size = header[6..10].iter().fold(0u64, |acc, b| (acc << 7) | (*b as u64 & 0x7f));
+    let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+    Ok(10 + size + footer)
+}
+
+// 结尾的 ID3v1 与 APEv2 标签，返回去掉它们后的结束位置
+fn trailing_tags_start(file: &mut File, start: u64, mut end: u64) -> std::io::Result<u64> {
+    let mut tag = [0u8; 3];
+    if end >= start + 128 && read_at(file, end - 128, &mut tag)? && &tag == b"TAG" {
+        end -= 128;
+    }
+    let mut footer = [0u8; 32];
+    if end >= start + 32 && read_at(file, end - 32, &mut footer)? && &footer[0..8] == b"APETAGEX" {
+        let size = u32::from_le_bytes([footer[12], footer[13], footer[14], footer[15]]) as u64;
+        let flags = u32::from_le_bytes([footer[20], footer[21], footer[22], footer[23]]);
+        let total = size + if flags & 0x8000_0000 != 0 { 32 } else { 0 };
+        if total <= end - start {
+            end -= total;
+        }
+    }
+    Ok(end)
+}
+
+fn flac_audio_start(file: &mut File, offset: u64) -> std::io::Result<Option<u64>> {
+    let mut magic = [0u8; 4];
+    if !read_at(file, offset, &mut magic)? || &magic != b"fLaC" {
+        return Ok(None);
+    }
+    let mut pos = offset + 4;
+    let mut header = [0u8; 4];
+    while read_at(file, pos, &mut header)? {
+        let length = u32::from_be_bytes([0, header[1], header[2], header[3]]) as u64;
+        pos += 4 + length;
+        if header[0] & 0x80 != 0 {
+            return Ok(Some(pos));
+        }
+    }
+    Ok(None)
+}
+
+fn wav_data_range(file: &mut File, len: u64) -> std::io::Result<Option<(u64, u64)>> {
+    let mut riff = [0u8; 12];
+    if !read_at(file, 0, &mut riff)? || &riff[0..4] != b"RIFF" || &riff[8..12] != b"WAVE" {
+        return Ok(None);
+    }
+    let mut pos = 12;
+    let mut header = [0u8; 8];
+    while read_at(file, pos, &mut header)? {
+        let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as u64;
+        if &header[0..4] == b"data" {
+            return Ok(Some((pos + 8, (pos + 8 + size).min(len))));
+        }
+        // 块按偶数字节对齐
+        pos += 8 + size + (size & 1);
+    }
+    Ok(None)
+}
+
+fn audio_range(file: &mut File, format: &str) -> std::io::Result<(u64, u64)> {
+    let len = file.metadata()?.len();
+    match format {
+        "wav" => Ok(wav_data_range(file, len)?.unwrap_or((0, len))),
+        "flac" => {
+            let skip = id3v2_size(file, 0)?;
+            match flac_audio_start(file, skip)? {
+                Some(start) => Ok((start, trailing_tags_start(file, start, len)?)),
+                None => Ok((0, len)),
+            }
+        }
+        "mp3" => {
+            let start = id3v2_size(file, 0)?.min(len);
+            Ok((start, trailing_tags_start(file, start, len)?))
+        }
+        _ => Ok((0, len)),
+    }
+}
+
+pub fn audio_stream_hash(path: &Path) -> Result<String, String> {
+    let format = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let mut file = File::open(path).map_err(|e| e.to_string())?;
+    let (start, end) = audio_range(&mut file, &format).map_err(|e| e.to_string())?;
+
+    file.seek(SeekFrom::Start(start)).map_err(|e| e.to_string())?;
+    let mut hasher = Sha256::new();
+    let mut remaining = end.saturating_sub(start);
+    let mut buffer = vec![0u8; 256 * 1024];
+    while remaining > 0 {
+        let want = remaining.min(buffer.len() as u64) as usize;
+        let read = file.read(&mut buffer[..want]).map_err(|e| e.to_string())?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        remaining -= read as u64;
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    const AUDIO: &[u8] = b"\xFF\xFB\x90\x64 pretend audio frames \x00\x01\x02";
+
+    fn range(path: &Path) -> (u64, u64) {
+        let format = path.extension().unwrap().to_string_lossy().to_string();
+        audio_range(&mut File::open(path).unwrap(), &format).unwrap()
+    }
+
+    fn id3v2(body_len: usize) -> Vec<u8> {
+        // 同步安全整数：每字节 7 位
+        let size = body_len as u32;
+        let mut tag = b"ID3\x04\x00\x00".to_vec();
+        tag.extend([(size >> 21) as u8 & 0x7f, (size >> 14) as u8 & 0x7f, (size >> 7) as u8 & 0x7f, size as u8 & 0x7f]);
+        tag.extend(std::iter::repeat_n(b'T', body_len));
+        tag
+    }
+
+    fn ape(items_len: u32) -> Vec<u8> {
+        let mut tag = vec![b'I'; items_len as usize];
+        tag.extend_from_slice(b"APETAGEX");
+        tag.extend(2000u32.to_le_bytes());
+        tag.extend((items_len + 32).to_le_bytes());
+        tag.extend(0u32.to_le_bytes());
+        tag.extend(0u32.to_le_bytes());
+        tag.extend([0u8; 8]);
+        tag
+    }
+
+    #[test]
+    fn mp3_skips_leading_and_trailing_tags() {
+        let dir = TempDir::new("audio_hash_test");
+        let mut bytes = id3v2(300);
+        bytes.extend_from_slice(AUDIO);
+        bytes.extend(ape(40));
+        let mut v1 = b"TAG".to_vec();
+        v1.resize(128, b' ');
+        bytes.extend(v1);
+
+        let tagged = dir.write("tagged.mp3", &bytes);
+        assert_eq!(range(&tagged), (310, 310 + AUDIO.len() as u64));
+        let bare = dir.write("bare.mp3", AUDIO);
+        assert_eq!(audio_stream_hash(&tagged).unwrap(), audio_stream_hash(&bare).unwrap());
+    }
+
+    #[test]
+    fn flac_skips_metadata_blocks() {
+        let dir = TempDir::new("audio_hash_test");
+        let flac = |blocks: &[(u8, usize)]| {
+            let mut bytes = b"fLaC".to_vec();
+            for (i, (kind, len)) in blocks.iter().enumerate() {
+                let last = if i + 1 == blocks.len() { 0x80 } else { 0 };
+                bytes.push(kind | last);
+                bytes.extend(&(*len as u32).to_be_bytes()[1..]);
+                bytes.extend(std::iter::repeat_n(*kind, *len));
+            }
+            bytes.extend_from_slice(AUDIO);
+            bytes
+        };
+        let small = dir.write("small.flac", &flac(&[(0, 34)]));
+        let large = dir.write("large.flac", &flac(&[(0, 34), (4, 200), (6, 1000), (1, 64)]));
+        assert_eq!(range(&small), (42, 42 + AUDIO.len() as u64));
+        assert_eq!(range(&large), (4 + 38 + 204 + 1004 + 68, 4 + 38 + 204 + 1004 + 68 + AUDIO.len() as u64));
+        assert_eq!(audio_stream_hash(&small).unwrap(), audio_stream_hash(&large).unwrap());
+    }
+
+    #[test]
+    fn wav_hashes_only_the_data_chunk() {
+        let dir = TempDir::new("audio_hash_test");
+        let wav = |extra: &[u8]| {
+            let mut bytes = b"RIFF\0\0\0\0WAVE".to_vec();
+            bytes.extend_from_slice(b"fmt \x10\0\0\0");
+            bytes.extend([0u8; 16]);
+            // LIST 块长度为奇数，需补一个字节
+            bytes.extend_from_slice(b"LIST");
+            bytes.extend((extra.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(extra);
+            if extra.len() % 2 == 1 {
+                bytes.push(0);
+            }
+            bytes.extend_from_slice(b"data");
+            bytes.extend((AUDIO.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(AUDIO);
+            bytes.extend_from_slice(b"id3 \x04\0\0\0ID3!");
+            bytes
+        };
+        let a = dir.write("a.wav", &wav(b"INFOabc"));
+        let b = dir.write("b.wav", &wav(b"INFO longer comment"));
+        assert_eq!(range(&a), (12 + 24 + 8 + 8 + 8, 12 + 24 + 8 + 8 + 8 + AUDIO.len() as u64));
+        assert_eq!(audio_stream_hash(&a).unwrap(), audio_stream_hash(&b).unwrap());
+    }
+
+    #[test]
+    fn other_formats_hash_the_whole_file() {
+        let dir = TempDir::new("audio_hash_test");
+        let path = dir.write("song.ogg", b"OggS whatever");
+        assert_eq!(range(&path), (0, 13));
+        let mp3 = dir.write("untagged.mp3", AUDIO);
+        assert_eq!(range(&mp3), (0, AUDIO.len() as u64));
+    }
+}
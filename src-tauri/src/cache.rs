@@ -0,0 +1,457 @@
+use crate::settings::{load_setting, save_setting};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager};
+
+// --- 磁盘缓存管理：封面 / 波形 / 歌词缓存共用一个管理器，各自有目录与容量上限 ---
+// 文件系统的 atime 经常被关闭 (noatime、Windows 默认不更新)，访问时间由这里自己记录：
+// 先记在内存里，定期与清理前批量写入 cache_access 表。
+// 超出上限时按最近访问时间从旧到新删除，没有访问记录的文件按修改时间算。
+
+const CACHE_CONFIG_KEY: &str = "cache";
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(30 * 60);
+// 清理到上限的 90%，避免刚清完又超出
+const CLEANUP_TARGET: f64 = 0.9;
+const MB: u64 = 1024 * 1024;
+// 单条 UPDATE ... IN (...) 的参数个数
+const CLEAR_BATCH: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheKind {
+    Covers,
+    Waveforms,
+    Lyrics,
+}
+
+const KIND_COUNT: usize = 3;
+
+impl CacheKind {
+    pub const ALL: [CacheKind; KIND_COUNT] = [CacheKind::Covers, CacheKind::Waveforms, CacheKind::Lyrics];
+
+    // 同时是缓存目录名与 cache_access.kind 的值
+    fn name(self) -> &'static str {
+        match self {
+            CacheKind::Covers => "covers",
+            CacheKind::Waveforms => "waveforms",
+            CacheKind::Lyrics => "lyrics",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    // 各类缓存的容量上限 (MB)，0 表示不限
+    pub cover_limit_mb: u64,
+    pub waveform_limit_mb: u64,
+    pub lyrics_limit_mb: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self { cover_limit_mb: 500, waveform_limit_mb: 200, lyrics_limit_mb: 50 }
+    }
+}
+
+impl CacheConfig {
+    pub fn load(conn: &Connection) -> Self {
+        load_setting(conn, CACHE_CONFIG_KEY)
+    }
+
+    fn limit_mb(&self, kind: CacheKind) -> u64 {
+        match kind {
+            CacheKind::Covers => self.cover_limit_mb,
+            CacheKind::Waveforms => self.waveform_limit_mb,
+            CacheKind::Lyrics => self.lyrics_limit_mb,
+        }
+    }
+
+    fn set_limit_mb(&mut self, kind: CacheKind, limit_mb: u64) {
+        match kind {
+            CacheKind::Covers => self.cover_limit_mb = limit_mb,
+            CacheKind::Waveforms => self.waveform_limit_mb = limit_mb,
+            CacheKind::Lyrics => self.lyrics_limit_mb = limit_mb,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CacheStats {
+    pub kind: CacheKind,
+    pub files: u64,
+    // 字节
+    pub size: u64,
+    // 字节，0 表示不限
+    pub limit: u64,
+}
+
+pub struct CacheManager {
+    root: PathBuf,
+    conn: Arc<Mutex<Connection>>,
+    // 尚未写入数据库的访问记录：(类别, 文件名) -> 时间戳 (秒)
+    pending: Mutex<HashMap<(CacheKind, String), i64>>,
+    // 各类缓存的大小估计，写入时累加，清理时按目录实际大小校正
+    sizes: [AtomicU64; KIND_COUNT],
+    limits: [AtomicU64; KIND_COUNT],
+    cleaning: [AtomicBool; KIND_COUNT],
+}
+
+static MANAGER: OnceLock<CacheManager> = OnceLock::new();
+
+fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0)
+}
+
+// 目录中的文件：(文件名, 路径, 大小, 修改时间)
+fn list_files(dir: &Path) -> Vec<(String, PathBuf, u64, i64)> {
+    let Ok(read_dir) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    read_dir
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            if !metadata.is_file() {
+                return None;
+            }
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs() as i64)
+                .unwrap_or(0);
+            Some((entry.file_name().to_string_lossy().into_owned(), entry.path(), metadata.len(), modified))
+        })
+        .collect()
+}
+
+// 启动时调用：创建管理器并开始定期清理 (第一次清理立即进行)
+pub fn init(app: &AppHandle, conn: Arc<Mutex<Connection>>) -> Result<(), String> {
+    let root = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    let config = CacheConfig::load(&*conn.lock().map_err(|e| e.to_string())?);
+    let cache = CacheManager {
+        root,
+        conn,
+        pending: Mutex::new(HashMap::new()),
+        sizes: Default::default(),
+        limits: CacheKind::ALL.map(|kind| AtomicU64::new(config.limit_mb(kind) * MB)),
+        cleaning: Default::default(),
+    };
+    if MANAGER.set(cache).is_err() {
+        return Ok(());
+    }
+
+    std::thread::spawn(|| {
+        let Some(manager) = manager() else { return };
+        loop {
+            manager.flush();
+            for kind in CacheKind::ALL {
+                if let Err(e) = manager.cleanup(kind) {
+                    eprintln!("缓存清理失败 ({}): {}", kind.name(), e);
+                }
+            }
+            std::thread::sleep(CLEANUP_INTERVAL);
+        }
+    });
+    Ok(())
+}
+
+pub fn manager() -> Option<&'static CacheManager> {
+    MANAGER.get()
+}
+
+// 由源文件派生的缓存 (波形、内嵌歌词) 的文件名：路径 + 大小 + 修改时间的哈希，文件改动后自然失效
+pub fn source_key(path: &Path) -> Option<String> {
+    let metadata = fs::metadata(path).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    let mut hasher = Sha256::new();
+    hasher.update(path.to_string_lossy().as_bytes());
+    hasher.update(metadata.len().to_be_bytes());
+    hasher.update(modified.as_nanos().to_be_bytes());
+    Some(hex::encode(hasher.finalize()))
+}
+
+impl CacheManager {
+    pub fn dir(&self, kind: CacheKind) -> PathBuf {
+        let dir = self.root.join(kind.name());
+        if !dir.exists() {
+            let _ = fs::create_dir_all(&dir);
+        }
+        dir
+    }
+
+    // 缓存文件被读取 / 返回给前端时调用
+    pub fn touch(&self, kind: CacheKind, path: &Path) {
+        let Some(name) = path.file_name() else { return };
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.insert((kind, name.to_string_lossy().into_owned()), now());
+        }
+    }
+
+    // 读取一个缓存文件并记一次访问
+    pub fn read(&self, kind: CacheKind, name: &str) -> Option<Vec<u8>> {
+        let path = self.dir(kind).join(name);
+        let bytes = fs::read(&path).ok()?;
+        self.touch(kind, &path);
+        Some(bytes)
+    }
+
+    // 写入一个缓存文件并计入容量
+    pub fn write(&'static self, kind: CacheKind, name: &str, bytes: &[u8]) -> Result<PathBuf, String> {
+        let path = self.dir(kind).join(name);
+        fs::write(&path, bytes).map_err(|e| e.to_string())?;
+        self.record_write(kind, &path, bytes.len() as u64);
+        Ok(path)
+    }
+
+    // 写入新的缓存文件后调用，超出上限时在后台清理
+    pub fn record_write(&'static self, kind: CacheKind, path: &Path, len: u64) {
+        self.touch(kind, path);
+        let size = self.sizes[kind.index()].fetch_add(len, Ordering::Relaxed) + len;
+        let limit = self.limits[kind.index()].load(Ordering::Relaxed);
+        if limit > 0 && size > limit && !self.cleaning[kind.index()].load(Ordering::Relaxed) {
+            std::thread::spawn(move || {
+                if let Err(e) = self.cleanup(kind) {
+                    eprintln!("缓存清理失败 ({}): {}", kind.name(), e);
+                }
+            });
+        }
+    }
+
+    // 把内存中的访问记录写入数据库
+    fn flush(&self) {
+        let pending: Vec<_> = match self.pending.lock() {
+            Ok(mut pending) => pending.drain().collect(),
+            Err(_) => return,
+        };
+        if pending.is_empty() {
+            return;
+        }
+        let Ok(conn) = self.conn.lock() else { return };
+        let result = (|| -> rusqlite::Result<()> {
+            let tx = conn.unchecked_transaction()?;
+            {
+                let mut stmt = tx.prepare(
+                    "INSERT INTO cache_access (kind, name, last_access) VALUES (?1, ?2, ?3)
+                     ON CONFLICT(kind, name) DO UPDATE SET last_access = MAX(last_access, excluded.last_access)",
+                )?;
+                for ((kind, name), time) in &pending {
+                    stmt.execute((kind.name(), name, time))?;
+                }
+            }
+            tx.commit()
+        })();
+        if let Err(e) = result {
+            eprintln!("缓存访问记录写入失败: {}", e);
+        }
+    }
+
+    pub fn stats(&self) -> Vec<CacheStats> {
+        CacheKind::ALL
+            .iter()
+            .map(|&kind| {
+                let files = list_files(&self.dir(kind));
+                let size = files.iter().map(|f| f.2).sum();
+                self.sizes[kind.index()].store(size, Ordering::Relaxed);
+                CacheStats {
+                    kind,
+                    files: files.len() as u64,
+                    size,
+                    limit: self.limits[kind.index()].load(Ordering::Relaxed),
+                }
+            })
+            .collect()
+    }
+
+    pub fn set_limit(&'static self, kind: CacheKind, limit_mb: u64) -> Result<(), String> {
+        {
+            let conn = self.conn.lock().map_err(|e| e.to_string())?;
+            let mut config = CacheConfig::load(&conn);
+            config.set_limit_mb(kind, limit_mb);
+            save_setting(&conn, CACHE_CONFIG_KEY, &config)?;
+        }
+        self.limits[kind.index()].store(limit_mb * MB, Ordering::Relaxed);
+        // 调小上限后立即按新上限清理
+        std::thread::spawn(move || {
+            if let Err(e) = self.cleanup(kind) {
+                eprintln!("缓存清理失败 ({}): {}", kind.name(), e);
+            }
+        });
+        Ok(())
+    }
+
+    // 超出上限时按最近访问时间删除最旧的文件，返回释放的字节数
+    pub fn cleanup(&self, kind: CacheKind) -> Result<u64, String> {
+        let flag = &self.cleaning[kind.index()];
+        if flag.swap(true, Ordering::AcqRel) {
+            return Ok(0);
+        }
+        let result = self.cleanup_locked(kind);
+        flag.store(false, Ordering::Release);
+        result
+    }
+
+    fn cleanup_locked(&self, kind: CacheKind) -> Result<u64, String> {
+        let mut files = list_files(&self.dir(kind));
+        let mut total: u64 = files.iter().map(|f| f.2).sum();
+        self.sizes[kind.index()].store(total, Ordering::Relaxed);
+        let limit = self.limits[kind.index()].load(Ordering::Relaxed);
+        if limit == 0 || total <= limit {
+            return Ok(0);
+        }
+
+        self.flush();
+        let access: HashMap<String, i64> = {
+            let conn = self.conn.lock().map_err(|e| e.to_string())?;
+            let mut stmt = conn
+                .prepare("SELECT name, last_access FROM cache_access WHERE kind = ?1")
+                .map_err(|e| e.to_string())?;
+            let rows = stmt
+                .query_map([kind.name()], |row| Ok((row.get(0)?, row.get(1)?)))
+                .map_err(|e| e.to_string())?;
+            rows.filter_map(|r| r.ok()).collect()
+        };
+        files.sort_by_key(|(name, _, _, modified)| access.get(name).copied().unwrap_or(*modified));
+
+        let target = (limit as f64 * CLEANUP_TARGET) as u64;
+        let mut removed = Vec::new();
+        let mut freed = 0;
+        for (name, path, len, _) in files {
+            if total <= target {
+                break;
+            }
+            if fs::remove_file(&path).is_ok() {
+                total = total.saturating_sub(len);
+                freed += len;
+                removed.push((name, path));
+            }
+        }
+        self.sizes[kind.index()].store(total, Ordering::Relaxed);
+
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+        for (name, _) in &removed {
+            tx.execute("DELETE FROM cache_access WHERE kind = ?1 AND name = ?2", (kind.name(), name))
+                .map_err(|e| e.to_string())?;
+        }
+        // 被删掉的缩略图不能再作为 Song.cover 返回给前端
+        if kind == CacheKind::Covers {
+            let paths: Vec<String> = removed.iter().map(|(_, path)| path.to_string_lossy().into_owned()).collect();
+            clear_cover_paths(&tx, &paths).map_err(|e| e.to_string())?;
+        }
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(freed)
+    }
+
+    // 删除某类缓存的全部文件，返回释放的字节数
+    pub fn clear(&self, kind: CacheKind) -> Result<u64, String> {
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.retain(|(k, _), _| *k != kind);
+        }
+        let mut freed = 0;
+        let mut remaining = 0;
+        for (_, path, len, _) in list_files(&self.dir(kind)) {
+            if fs::remove_file(&path).is_ok() {
+                freed += len;
+            } else {
+                remaining += len;
+            }
+        }
+        self.sizes[kind.index()].store(remaining, Ordering::Relaxed);
+
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM cache_access WHERE kind = ?1", [kind.name()])
+            .map_err(|e| e.to_string())?;
+        if kind == CacheKind::Covers {
+            conn.execute("UPDATE songs SET cover_path = NULL WHERE cover_path IS NOT NULL", [])
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(freed)
+    }
+}
+
+// songs.cover_path 没有索引，按批用 IN 一次扫表清掉，而不是每个文件扫一次
+fn clear_cover_paths(conn: &Connection, paths: &[String]) -> rusqlite::Result<()> {
+    for chunk in paths.chunks(CLEAR_BATCH) {
+        let placeholders = vec!["?"; chunk.len()].join(", ");
+        conn.execute(
+            &format!("UPDATE songs SET cover_path = NULL WHERE cover_path IN ({})", placeholders),
+            rusqlite::params_from_iter(chunk),
+        )?;
+    }
+    Ok(())
+}
+
+fn require_manager() -> Result<&'static CacheManager, String> {
+    manager().ok_or_else(|| "缓存管理器尚未初始化".to_string())
+}
+
+// --- Commands ---
+
+#[tauri::command]
+pub async fn get_cache_stats() -> Result<Vec<CacheStats>, String> {
+    let manager = require_manager()?;
+    tauri::async_runtime::spawn_blocking(move || manager.stats())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn clear_cover_cache() -> Result<u64, String> {
+    let manager = require_manager()?;
+    tauri::async_runtime::spawn_blocking(move || manager.clear(CacheKind::Covers))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+// limit_mb 为 0 表示不限
+#[tauri::command]
+pub fn set_cache_limit(kind: CacheKind, limit_mb: u64) -> Result<(), String> {
+    require_manager()?.set_limit(kind, limit_mb)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn clears_evicted_cover_paths_in_batches() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE songs (id INTEGER PRIMARY KEY, cover_path TEXT)").unwrap();
+        for id in 0..CLEAR_BATCH + 10 {
+            conn.execute("INSERT INTO songs (id, cover_path) VALUES (?1, ?2)", (id as i64, format!("c{}.jpg", id)))
+                .unwrap();
+        }
+        let evicted: Vec<String> = (5..CLEAR_BATCH + 5).map(|id| format!("c{}.jpg", id)).collect();
+        clear_cover_paths(&conn, &evicted).unwrap();
+
+        let kept: i64 = conn
+            .query_row("SELECT COUNT(*) FROM songs WHERE cover_path IS NOT NULL", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(kept, 10);
+    }
+
+    #[test]
+    fn source_key_changes_with_the_file() {
+        let dir = TempDir::new("cache_test");
+        let path = dir.write("song.mp3", b"abc");
+        let before = source_key(&path).unwrap();
+        assert_eq!(source_key(&path), Some(before.clone()));
+        dir.write("song.mp3", b"abcd");
+        assert_ne!(source_key(&path).unwrap(), before);
+        assert_eq!(source_key(&dir.write("other.mp3", b"abc").with_extension("missing")), None);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::search::register_functions;
+use crate::sort_key::backfill_sort_keys;
 use rusqlite::Connection;
 use std::fs;
 use std::sync::{Arc, Mutex};
@@ -7,6 +9,29 @@ pub struct DbState {
     pub conn: Arc<Mutex<Connection>>,
 }
 
+// LIKE 模式中的通配符转义，配合 ESCAPE '\\' 使用
+pub fn escape_like(term: &str) -> String {
+    term.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+// 为已存在的表补齐缺失的列 (ALTER TABLE ... ADD COLUMN)
+fn add_missing_columns(conn: &Connection, table: &str, new_columns: &[(&str, &str)]) -> Result<(), String> {
+    let existing: Vec<String> = conn
+        .prepare(&format!("PRAGMA table_info({})", table))
+        .map_err(|e| e.to_string())?
+        .query_map([], |row| row.get::<_, String>(1))
+        .map_err(|e| e.to_string())?
+        .filter_map(|r| r.ok())
+        .collect();
+    for (name, ty) in new_columns {
+        if !existing.iter().any(|c| c == name) {
+            conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, name, ty), [])
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
 impl DbState {
     pub fn new(app_handle: &AppHandle) -> Result<Self, String> {
         let app_dir = app_handle
@@ -20,6 +45,9 @@ impl DbState {
 
         let db_path = app_dir.join("library.db");
         let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+        conn.execute_batch("PRAGMA foreign_keys = ON;")
+            .map_err(|e| e.to_string())?;
+        register_functions(&conn).map_err(|e| e.to_string())?;
 
         conn.execute(
             "CREATE TABLE IF NOT EXISTS songs (
@@ -61,6 +89,296 @@ impl DbState {
                 .ok();
         }
 
+        // --- Migration: Full tag metadata (v1.2.0) ---
+        let tag_columns = [
+            ("album_artist", "TEXT"),
+            ("genre", "TEXT"),
+            ("year", "INTEGER"),
+            ("track_number", "INTEGER"),
+            ("track_total", "INTEGER"),
+            ("disc_number", "INTEGER"),
+            ("disc_total", "INTEGER"),
+            ("composer", "TEXT"),
+            // 元数据版本号，低于 metadata::METADATA_VERSION 的行扫描时重新解析
+            ("meta_version", "INTEGER"),
+        ];
+        for (name, ty) in tag_columns {
+            if !columns.iter().any(|c| c == name) {
+                conn.execute(&format!("ALTER TABLE songs ADD COLUMN {} {}", name, ty), [])
+                    .ok();
+            }
+        }
+
+        conn.execute_batch(
+            "CREATE INDEX IF NOT EXISTS idx_songs_album_artist ON songs(album_artist);
+             CREATE INDEX IF NOT EXISTS idx_songs_genre ON songs(genre);
+             CREATE INDEX IF NOT EXISTS idx_songs_year ON songs(year);
+             CREATE INDEX IF NOT EXISTS idx_songs_album_disc_track ON songs(album, disc_number, track_number);
+             CREATE INDEX IF NOT EXISTS idx_songs_composer ON songs(composer);",
+        )
+        .map_err(|e| e.to_string())?;
+
+        // --- Migration: Backend settings + normalized artists / genres (v1.2.0) ---
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS app_settings (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS artists (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL UNIQUE COLLATE NOCASE
+            );
+            CREATE TABLE IF NOT EXISTS song_artists (
+                song_id INTEGER NOT NULL REFERENCES songs(id) ON DELETE CASCADE,
+                artist_id INTEGER NOT NULL REFERENCES artists(id) ON DELETE CASCADE,
+                role TEXT NOT NULL DEFAULT 'artist',
+                position INTEGER NOT NULL DEFAULT 0,
+                PRIMARY KEY (song_id, artist_id, role)
+            );
+            CREATE INDEX IF NOT EXISTS idx_song_artists_artist ON song_artists(artist_id, role);
+            CREATE TABLE IF NOT EXISTS genres (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL UNIQUE COLLATE NOCASE
+            );
+            CREATE TABLE IF NOT EXISTS song_genres (
+                song_id INTEGER NOT NULL REFERENCES songs(id) ON DELETE CASCADE,
+                genre_id INTEGER NOT NULL REFERENCES genres(id) ON DELETE CASCADE,
+                position INTEGER NOT NULL DEFAULT 0,
+                PRIMARY KEY (song_id, genre_id)
+            );
+            CREATE INDEX IF NOT EXISTS idx_song_genres_genre ON song_genres(genre_id);",
+        )
+        .map_err(|e| e.to_string())?;
+
+        // --- Migration: Albums grouped by album artist + title (v1.2.0) ---
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS albums (
+                id INTEGER PRIMARY KEY,
+                title TEXT NOT NULL COLLATE NOCASE,
+                album_artist TEXT NOT NULL COLLATE NOCASE,
+                UNIQUE (title, album_artist)
+            );",
+        )
+        .map_err(|e| e.to_string())?;
+        if !columns.iter().any(|c| c == "album_id") {
+            conn.execute("ALTER TABLE songs ADD COLUMN album_id INTEGER REFERENCES albums(id) ON DELETE SET NULL", [])
+                .ok();
+        }
+        // 旧行回填 album_id，专辑艺人缺省时用歌手
+        conn.execute_batch(
+            "CREATE INDEX IF NOT EXISTS idx_songs_album_id ON songs(album_id);
+             INSERT OR IGNORE INTO albums (title, album_artist)
+                 SELECT DISTINCT COALESCE(album, ''), COALESCE(NULLIF(album_artist, ''), artist, '')
+                 FROM songs WHERE album_id IS NULL;
+             UPDATE songs SET album_id = (
+                 SELECT a.id FROM albums a
+                 WHERE a.title = COALESCE(songs.album, '')
+                   AND a.album_artist = COALESCE(NULLIF(songs.album_artist, ''), songs.artist, '')
+             ) WHERE album_id IS NULL;",
+        )
+        .map_err(|e| e.to_string())?;
+
+        // --- Migration: Full-text search index (v1.2.0) ---
+        // rowid 与 songs.id 对应；歌词只存在索引里，扫描时由 search::index_song 写入
+        conn.execute_batch(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS songs_fts USING fts5(
+                title, artist, album, album_artist, genre, file_name, lyrics,
+                tokenize = 'trigram remove_diacritics 1'
+            );
+            CREATE TRIGGER IF NOT EXISTS songs_fts_delete AFTER DELETE ON songs BEGIN
+                DELETE FROM songs_fts WHERE rowid = old.id;
+            END;
+            CREATE TRIGGER IF NOT EXISTS songs_fts_move AFTER UPDATE OF path ON songs BEGIN
+                UPDATE songs_fts
+                SET file_name = replace(replace(new.path, '\\', '/'), rtrim(replace(new.path, '\\', '/'), replace(replace(new.path, '\\', '/'), '/', '')), '')
+                WHERE rowid = new.id;
+            END;
+            INSERT INTO songs_fts (rowid, title, artist, album, album_artist, genre, file_name)
+                SELECT id, title, artist, album, album_artist, genre,
+                       replace(replace(path, '\\', '/'), rtrim(replace(path, '\\', '/'), replace(replace(path, '\\', '/'), '/', '')), '')
+                FROM songs WHERE id NOT IN (SELECT rowid FROM songs_fts);",
+        )
+        .map_err(|e| e.to_string())?;
+
+        // --- Migration: Sort keys for pinyin / kana aware ordering (v1.2.0) ---
+        add_missing_columns(&conn, "songs", &[
+            ("title_sort", "TEXT"),
+            ("artist_sort", "TEXT"),
+            ("album_sort", "TEXT"),
+            ("album_artist_sort", "TEXT"),
+        ])?;
+        add_missing_columns(&conn, "artists", &[("sort_name", "TEXT")])?;
+        add_missing_columns(&conn, "genres", &[("sort_name", "TEXT")])?;
+        add_missing_columns(&conn, "albums", &[("title_sort", "TEXT"), ("album_artist_sort", "TEXT")])?;
+        conn.execute_batch(
+            "CREATE INDEX IF NOT EXISTS idx_songs_title_sort ON songs(title_sort);
+             CREATE INDEX IF NOT EXISTS idx_songs_artist_sort ON songs(artist_sort);
+             CREATE INDEX IF NOT EXISTS idx_songs_album_sort ON songs(album_sort);
+             CREATE INDEX IF NOT EXISTS idx_artists_sort_name ON artists(sort_name);
+             CREATE INDEX IF NOT EXISTS idx_albums_title_sort ON albums(title_sort);",
+        )
+        .map_err(|e| e.to_string())?;
+        backfill_sort_keys(&conn).map_err(|e| e.to_string())?;
+
+        // --- Migration: Playlists stored by song id (v1.2.0) ---
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS playlists (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                position INTEGER NOT NULL DEFAULT 0,
+                created_at INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS playlist_items (
+                id INTEGER PRIMARY KEY,
+                playlist_id INTEGER NOT NULL REFERENCES playlists(id) ON DELETE CASCADE,
+                song_id INTEGER NOT NULL REFERENCES songs(id) ON DELETE CASCADE,
+                position INTEGER NOT NULL,
+                added_at INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_playlist_items_order ON playlist_items(playlist_id, position);
+            CREATE INDEX IF NOT EXISTS idx_playlist_items_song ON playlist_items(song_id);",
+        )
+        .map_err(|e| e.to_string())?;
+
+        // --- Migration: Favorites, play events and legacy playlist ids (v1.2.0) ---
+        add_missing_columns(&conn, "playlists", &[("legacy_id", "TEXT")])?;
+        conn.execute_batch(
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_playlists_legacy_id ON playlists(legacy_id) WHERE legacy_id IS NOT NULL;
+            CREATE TABLE IF NOT EXISTS favorites (
+                song_id INTEGER PRIMARY KEY REFERENCES songs(id) ON DELETE CASCADE,
+                added_at INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS play_events (
+                id INTEGER PRIMARY KEY,
+                song_id INTEGER NOT NULL REFERENCES songs(id) ON DELETE CASCADE,
+                started_at INTEGER NOT NULL,
+                listened_ms INTEGER,
+                completed INTEGER NOT NULL DEFAULT 0,
+                skipped INTEGER NOT NULL DEFAULT 0,
+                source TEXT NOT NULL DEFAULT 'player'
+            );
+            CREATE INDEX IF NOT EXISTS idx_play_events_song ON play_events(song_id, started_at);
+            CREATE INDEX IF NOT EXISTS idx_play_events_started ON play_events(started_at);",
+        )
+        .map_err(|e| e.to_string())?;
+
+        // --- Migration: Play statistics aggregated from play_events (v1.2.0) ---
+        let needs_stats_backfill = conn.prepare("SELECT play_count FROM songs LIMIT 0").is_err();
+        add_missing_columns(&conn, "songs", &[
+            ("play_count", "INTEGER NOT NULL DEFAULT 0"),
+            ("skip_count", "INTEGER NOT NULL DEFAULT 0"),
+            ("last_played", "INTEGER"),
+        ])?;
+        conn.execute_batch(
+            "CREATE TRIGGER IF NOT EXISTS play_events_stats AFTER INSERT ON play_events BEGIN
+                UPDATE songs SET
+                    play_count = play_count + new.completed,
+                    skip_count = skip_count + new.skipped,
+                    last_played = MAX(COALESCE(last_played, 0), new.started_at)
+                WHERE id = new.song_id;
+            END;
+            CREATE INDEX IF NOT EXISTS idx_songs_play_count ON songs(play_count);
+            CREATE INDEX IF NOT EXISTS idx_songs_last_played ON songs(last_played);",
+        )
+        .map_err(|e| e.to_string())?;
+        if needs_stats_backfill {
+            conn.execute_batch(
+                "UPDATE songs SET
+                    play_count = (SELECT COALESCE(SUM(completed), 0) FROM play_events WHERE song_id = songs.id),
+                    skip_count = (SELECT COALESCE(SUM(skipped), 0) FROM play_events WHERE song_id = songs.id),
+                    last_played = (SELECT MAX(started_at) FROM play_events WHERE song_id = songs.id)
+                 WHERE id IN (SELECT song_id FROM play_events);",
+            )
+            .map_err(|e| e.to_string())?;
+        }
+
+        // --- Migration: Star ratings (v1.2.0) ---
+        add_missing_columns(&conn, "songs", &[("rating", "INTEGER")])?;
+
+        // --- Migration: Smart playlists (v1.2.0) ---
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS smart_playlists (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                rules TEXT NOT NULL,
+                position INTEGER NOT NULL DEFAULT 0,
+                created_at INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL
+            )",
+            [],
+        )
+        .map_err(|e| e.to_string())?;
+
+        // --- Migration: File size for library statistics (v1.2.0) ---
+        add_missing_columns(&conn, "songs", &[("file_size", "INTEGER")])?;
+
+        // --- Migration: Duplicate detection (v1.2.0) ---
+        // audio_hash 只覆盖音频数据，audio_hash_size 记录计算时的文件大小，大小变化后重新计算
+        add_missing_columns(&conn, "songs", &[("audio_hash", "TEXT"), ("audio_hash_size", "INTEGER")])?;
+        // 声学指纹：小端 u32 序列 (Chromaprint 原始指纹)
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS fingerprints (
+                song_id INTEGER PRIMARY KEY REFERENCES songs(id) ON DELETE CASCADE,
+                fingerprint BLOB NOT NULL,
+                created_at INTEGER NOT NULL
+            )",
+            [],
+        )
+        .map_err(|e| e.to_string())?;
+
+        // --- Migration: Acoustic fingerprint staleness (v1.2.0) ---
+        // 指纹计算时的文件大小，不一致时重新计算
+        add_missing_columns(&conn, "fingerprints", &[("file_size", "INTEGER")])?;
+
+        // --- Migration: Cover cache keyed by image content (v1.2.0) ---
+        // cover_key 为图片内容的哈希 (NULL = 没有封面)，source_hash 为生成映射时的文件指纹
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS song_covers (
+                song_id INTEGER PRIMARY KEY REFERENCES songs(id) ON DELETE CASCADE,
+                cover_key TEXT,
+                source_hash TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_song_covers_key ON song_covers(cover_key);",
+        )
+        .map_err(|e| e.to_string())?;
+
+        // --- Migration: Cover palettes (v1.2.0) ---
+        // 按封面键缓存取色结果 (palette::CoverPalette 的 JSON)
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS cover_palettes (
+                cover_key TEXT PRIMARY KEY,
+                palette TEXT NOT NULL
+            );",
+        )
+        .map_err(|e| e.to_string())?;
+
+        // --- Migration: Cache access tracking (v1.2.0) ---
+        // 缓存文件的最近访问时间，清理时按它淘汰 (不依赖文件系统 atime)
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS cache_access (
+                kind TEXT NOT NULL,
+                name TEXT NOT NULL,
+                last_access INTEGER NOT NULL,
+                PRIMARY KEY (kind, name)
+            );",
+        )
+        .map_err(|e| e.to_string())?;
+
+        // --- Migration: Lyrics file encoding overrides (v1.2.0) ---
+        // 自动检测猜错时，用户为单个歌词文件指定的编码 (encoding_rs 的名称，如 GBK、Big5)
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS lyrics_encodings (
+                path TEXT PRIMARY KEY,
+                encoding TEXT NOT NULL
+            );",
+        )
+        .map_err(|e| e.to_string())?;
+
+        // --- Migration: Raw tag values for re-linking artists (v1.2.0) ---
+        // artists::LinkTags 的 JSON，拆分配置变更时不必重新读取文件
+        add_missing_columns(&conn, "songs", &[("link_tags", "TEXT")])?;
+
         Ok(DbState {
             conn: Arc::new(Mutex::new(conn)),
         })
@@ -0,0 +1,555 @@
+use crate::audio_hash::audio_stream_hash;
+use crate::database::DbState;
+use crate::fingerprint::blob_to_raw;
+use crate::music::{delete_song_file, move_song_file, Song, SONG_COLUMNS, SONG_COLUMN_COUNT};
+use crate::playlists::compact_positions;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use tauri::State;
+
+// --- 重复曲目检测 ---
+// audio_hash:  音频数据完全相同 (忽略标签与封面)
+// metadata:    规范化后的艺人 + 标题相同，时长相差不超过容差
+// fingerprint: 声学指纹相似度达到阈值 (不同编码 / 码率的同一录音)
+// 每组按位深 > 采样率 > 码率选出最佳版本，其余为可清理的副本
+
+const DEFAULT_METADATA_TOLERANCE: u32 = 3;
+const DEFAULT_FINGERPRINT_TOLERANCE: u32 = 10;
+const DEFAULT_MIN_SIMILARITY: f64 = 0.85;
+// 指纹每项约 0.124 秒：比较前 2 分钟，前后最多错开约 2 秒
+const FINGERPRINT_COMPARE_ITEMS: usize = 1000;
+const FINGERPRINT_MAX_OFFSET: usize = 16;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct DuplicateOptions {
+    // 秒
+    pub duration_tolerance: Option<u32>,
+    // 0..1，仅 fingerprint 策略使用
+    pub min_similarity: Option<f64>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DuplicateGroup {
+    pub best_id: i64,
+    // 最佳版本排在第一位
+    pub songs: Vec<Song>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DuplicateReport {
+    pub groups: Vec<DuplicateGroup>,
+    // 除最佳版本外的副本数与其占用空间 (字节)
+    pub duplicate_count: u32,
+    pub reclaimable_size: u64,
+    // 无法参与比较的歌曲：文件缺失、读取失败或尚未计算指纹
+    pub unchecked: u32,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DuplicateResolution {
+    pub keep_id: i64,
+    pub remove_ids: Vec<i64>,
+}
+
+// 最近一次查重的分组 (歌曲 id -> 组号)，处理副本时据此校验保留与移除的是同一组
+#[derive(Default)]
+pub struct DuplicateGroups(Mutex<HashMap<i64, usize>>);
+
+impl DuplicateGroups {
+    fn remember(&self, report: &DuplicateReport) {
+        if let Ok(mut groups) = self.0.lock() {
+            *groups = report
+                .groups
+                .iter()
+                .enumerate()
+                .flat_map(|(g, group)| group.songs.iter().map(move |song| (song.id, g)))
+                .collect();
+        }
+    }
+
+    fn check(&self, keep_id: i64, remove_id: i64) -> Result<(), String> {
+        let groups = self.0.lock().map_err(|e| e.to_string())?;
+        let keep_group = groups.get(&keep_id).ok_or("保留的版本不在查重结果中，请重新查找")?;
+        if groups.get(&remove_id) != Some(keep_group) {
+            return Err("与保留的版本不在同一重复组".to_string());
+        }
+        Ok(())
+    }
+
+    fn forget(&self, song_id: i64) {
+        if let Ok(mut groups) = self.0.lock() {
+            groups.remove(&song_id);
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct ResolveReport {
+    pub processed: u32,
+    pub failed: Vec<String>,
+}
+
+struct Candidate {
+    song: Song,
+    file_size: Option<i64>,
+    audio_hash: Option<String>,
+    audio_hash_size: Option<i64>,
+}
+
+fn load_candidates(conn: &Connection) -> rusqlite::Result<Vec<Candidate>> {
+    let sql = format!("SELECT {}, file_size, audio_hash, audio_hash_size FROM songs", SONG_COLUMNS);
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(Candidate {
+                song: Song::from_row(row)?,
+                file_size: row.get(SONG_COLUMN_COUNT)?,
+                audio_hash: row.get(SONG_COLUMN_COUNT + 1)?,
+                audio_hash_size: row.get(SONG_COLUMN_COUNT + 2)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
+}
+
+// 小写、全角转半角，只保留字母数字 (含汉字、假名)，忽略空格与标点
+fn normalize(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+            _ => c,
+        })
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(|c| c.to_lowercase())
+        .collect()
+}
+
+// 按时长排序后，相邻差值不超过容差的连成一组
+fn split_by_duration(mut indices: Vec<usize>, candidates: &[Candidate], tolerance: u32) -> Vec<Vec<usize>> {
+    indices.sort_by_key(|i| candidates[*i].song.duration);
+    let mut clusters: Vec<Vec<usize>> = Vec::new();
+    for i in indices {
+        match clusters.last_mut() {
+            Some(cluster)
+                if candidates[i].song.duration - candidates[*cluster.last().unwrap()].song.duration <= tolerance =>
+            {
+                cluster.push(i)
+            }
+            _ => clusters.push(vec![i]),
+        }
+    }
+    clusters
+}
+
+fn group_by_metadata(candidates: &[Candidate], tolerance: u32) -> Vec<Vec<usize>> {
+    let mut by_name: HashMap<(String, String), Vec<usize>> = HashMap::new();
+    for (i, c) in candidates.iter().enumerate() {
+        let title = normalize(&c.song.title);
+        if title.is_empty() {
+            continue;
+        }
+        by_name.entry((normalize(&c.song.artist), title)).or_default().push(i);
+    }
+    by_name
+        .into_values()
+        .filter(|v| v.len() > 1)
+        .flat_map(|v| split_by_duration(v, candidates, tolerance))
+        .collect()
+}
+
+// 相同音频数据必然格式与时长一致，先据此筛出候选，避免哈希整个曲库
+fn group_by_audio_hash(db: &Mutex<Connection>, candidates: &[Candidate], unchecked: &mut u32) -> Result<Vec<Vec<usize>>, String> {
+    let mut by_shape: HashMap<(String, u32), Vec<usize>> = HashMap::new();
+    for (i, c) in candidates.iter().enumerate() {
+        by_shape.entry((c.song.format.to_lowercase(), c.song.duration)).or_default().push(i);
+    }
+
+    let mut by_hash: HashMap<String, Vec<usize>> = HashMap::new();
+    let mut updates: Vec<(i64, String, i64)> = Vec::new();
+    for i in by_shape.into_values().filter(|v| v.len() > 1).flatten() {
+        let c = &candidates[i];
+        let Ok(size) = fs::metadata(&c.song.path).map(|m| m.len() as i64) else {
+            *unchecked += 1;
+            continue;
+        };
+        let hash = match (&c.audio_hash, c.audio_hash_size) {
+            (Some(hash), Some(hashed_size)) if hashed_size == size => hash.clone(),
+            _ => match audio_stream_hash(Path::new(&c.song.path)) {
+                Ok(hash) => {
+                    updates.push((c.song.id, hash.clone(), size));
+                    hash
+                }
+                Err(_) => {
+                    *unchecked += 1;
+                    continue;
+                }
+            },
+        };
+        by_hash.entry(hash).or_default().push(i);
+    }
+
+    if !updates.is_empty() {
+        let conn = db.lock().map_err(|e| e.to_string())?;
+        let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+        for (id, hash, size) in &updates {
+            tx.execute(
+                "UPDATE songs SET audio_hash = ?1, audio_hash_size = ?2 WHERE id = ?3",
+                (hash, size, id),
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        tx.commit().map_err(|e| e.to_string())?;
+    }
+    Ok(by_hash.into_values().filter(|v| v.len() > 1).collect())
+}
+
+// 在小范围错位内取最佳对齐，相似度 = 1 - 不同比特数 / 总比特数
+pub fn fingerprint_similarity(a: &[u32], b: &[u32]) -> f64 {
+    let a = &a[..a.len().min(FINGERPRINT_COMPARE_ITEMS)];
+    let b = &b[..b.len().min(FINGERPRINT_COMPARE_ITEMS)];
+    let min_overlap = a.len().min(b.len()) / 2;
+    if min_overlap == 0 {
+        return 0.0;
+    }
+    let mut best: f64 = 0.0;
+    for shift in 0..=FINGERPRINT_MAX_OFFSET {
+        for (x, y) in [(&a[shift.min(a.len())..], b), (a, &b[shift.min(b.len())..])] {
+            let overlap = x.len().min(y.len());
+            if overlap < min_overlap {
+                continue;
+            }
+            let diff: u32 = x.iter().zip(y).map(|(p, q)| (p ^ q).count_ones()).sum();
+            best = best.max(1.0 - diff as f64 / (overlap as f64 * 32.0));
+        }
+    }
+    best
+}
+
+fn find_root(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+fn load_fingerprints(conn: &Connection) -> rusqlite::Result<HashMap<i64, Vec<u32>>> {
+    let mut stmt = conn.prepare("SELECT song_id, fingerprint FROM fingerprints")?;
+    let fingerprints = stmt
+        .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, Vec<u8>>(1)?)))?
+        .filter_map(|r| r.ok())
+        .map(|(id, bytes)| (id, blob_to_raw(&bytes)))
+        .collect();
+    Ok(fingerprints)
+}
+
+// 两两比较耗时较长，不持有数据库锁
+fn group_by_fingerprint(
+    candidates: &[Candidate],
+    mut fingerprints: HashMap<i64, Vec<u32>>,
+    tolerance: u32,
+    min_similarity: f64,
+    unchecked: &mut u32,
+) -> Vec<Vec<usize>> {
+    // 只比较时长相近的歌曲
+    let mut indexed: Vec<(usize, Vec<u32>)> = Vec::new();
+    for (i, c) in candidates.iter().enumerate() {
+        match fingerprints.remove(&c.song.id) {
+            Some(fp) if !fp.is_empty() => indexed.push((i, fp)),
+            _ => *unchecked += 1,
+        }
+    }
+    indexed.sort_by_key(|(i, _)| candidates[*i].song.duration);
+
+    let mut parent: Vec<usize> = (0..indexed.len()).collect();
+    for a in 0..indexed.len() {
+        let duration = candidates[indexed[a].0].song.duration;
+        for b in a + 1..indexed.len() {
+            if candidates[indexed[b].0].song.duration - duration > tolerance {
+                break;
+            }
+            if fingerprint_similarity(&indexed[a].1, &indexed[b].1) >= min_similarity {
+                let (ra, rb) = (find_root(&mut parent, a), find_root(&mut parent, b));
+                parent[rb] = ra;
+            }
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (k, (i, _)) in indexed.iter().enumerate() {
+        let root = find_root(&mut parent, k);
+        groups.entry(root).or_default().push(*i);
+    }
+    groups.into_values().filter(|v| v.len() > 1).collect()
+}
+
+// 位深 > 采样率 > 码率，全部相同时保留较早入库的一首
+fn quality_order(a: &Song, b: &Song) -> std::cmp::Ordering {
+    b.bit_depth
+        .unwrap_or(0)
+        .cmp(&a.bit_depth.unwrap_or(0))
+        .then(b.sample_rate.cmp(&a.sample_rate))
+        .then(b.bitrate.cmp(&a.bitrate))
+        .then(a.id.cmp(&b.id))
+}
+
+pub fn find(db: &Mutex<Connection>, strategy: &str, options: &DuplicateOptions) -> Result<DuplicateReport, String> {
+    let candidates = {
+        let conn = db.lock().map_err(|e| e.to_string())?;
+        load_candidates(&conn).map_err(|e| e.to_string())?
+    };
+
+    let mut unchecked = 0;
+    let clusters = match strategy {
+        "audio_hash" => group_by_audio_hash(db, &candidates, &mut unchecked)?,
+        "metadata" => group_by_metadata(
+            &candidates,
+            options.duration_tolerance.unwrap_or(DEFAULT_METADATA_TOLERANCE),
+        ),
+        "fingerprint" => {
+            let fingerprints = {
+                let conn = db.lock().map_err(|e| e.to_string())?;
+                load_fingerprints(&conn).map_err(|e| e.to_string())?
+            };
+            group_by_fingerprint(
+                &candidates,
+                fingerprints,
+                options.duration_tolerance.unwrap_or(DEFAULT_FINGERPRINT_TOLERANCE),
+                options.min_similarity.unwrap_or(DEFAULT_MIN_SIMILARITY),
+                &mut unchecked,
+            )
+        }
+        other => return Err(format!("未知的查重方式: {}", other)),
+    };
+
+    let mut report = DuplicateReport { groups: Vec::new(), duplicate_count: 0, reclaimable_size: 0, unchecked };
+    for cluster in clusters.into_iter().filter(|c| c.len() > 1) {
+        let mut members: Vec<&Candidate> = cluster.iter().map(|i| &candidates[*i]).collect();
+        members.sort_by(|a, b| quality_order(&a.song, &b.song));
+        report.duplicate_count += members.len() as u32 - 1;
+        report.reclaimable_size += members[1..].iter().map(|c| c.file_size.unwrap_or(0) as u64).sum::<u64>();
+        report.groups.push(DuplicateGroup {
+            best_id: members[0].song.id,
+            songs: members.into_iter().map(|c| c.song.clone()).collect(),
+        });
+    }
+    report.groups.sort_by(|a, b| a.songs[0].title.cmp(&b.songs[0].title).then(a.best_id.cmp(&b.best_id)));
+    Ok(report)
+}
+
+// 删除副本前把歌单条目、收藏、播放记录与评分并入保留的版本；
+// 歌单里已有保留版本时直接去掉副本的条目，不产生重复
+fn merge_into(conn: &Connection, from: i64, into: i64) -> rusqlite::Result<()> {
+    let shared: Vec<i64> = conn
+        .prepare(
+            "SELECT DISTINCT playlist_id FROM playlist_items
+             WHERE song_id = ?1 AND playlist_id IN (SELECT playlist_id FROM playlist_items WHERE song_id = ?2)",
+        )?
+        .query_map((from, into), |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    for playlist_id in shared {
+        conn.execute(
+            "DELETE FROM playlist_items WHERE playlist_id = ?1 AND song_id = ?2",
+            (playlist_id, from),
+        )?;
+        compact_positions(conn, playlist_id)?;
+    }
+    conn.execute("UPDATE playlist_items SET song_id = ?2 WHERE song_id = ?1", (from, into))?;
+    conn.execute(
+        "INSERT OR IGNORE INTO favorites (song_id, added_at) SELECT ?2, added_at FROM favorites WHERE song_id = ?1",
+        (from, into),
+    )?;
+    conn.execute("UPDATE play_events SET song_id = ?2 WHERE song_id = ?1", (from, into))?;
+    // 触发器只在插入时累加，改挂的记录需要重新汇总
+    conn.execute(
+        "UPDATE songs SET
+            play_count = (SELECT COALESCE(SUM(completed), 0) FROM play_events WHERE song_id = songs.id),
+            skip_count = (SELECT COALESCE(SUM(skipped), 0) FROM play_events WHERE song_id = songs.id),
+            last_played = (SELECT MAX(started_at) FROM play_events WHERE song_id = songs.id),
+            rating = COALESCE(rating, (SELECT rating FROM songs WHERE id = ?1))
+         WHERE id = ?2",
+        (from, into),
+    )?;
+    Ok(())
+}
+
+fn resolve_one(conn: &Connection, keep_id: i64, remove_id: i64, target: Option<&Path>) -> Result<(), String> {
+    if keep_id == remove_id {
+        return Err("不能移除保留的版本".to_string());
+    }
+    let path: String = conn
+        .query_row("SELECT path FROM songs WHERE id = ?1", [remove_id], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+
+    match target {
+        // 移动：与 move_music_file 相同，曲库记录随文件搬走
+        Some(folder) => {
+            let file_name = Path::new(&path).file_name().ok_or("无效的文件路径")?;
+            let dest = folder.join(file_name);
+            if dest.exists() {
+                return Err(format!("目标位置已存在同名文件: {}", dest.display()));
+            }
+            move_song_file(conn, &path, &dest.to_string_lossy())
+        }
+        // 删除：与 delete_music_file 相同，文件删除失败时回滚合并
+        None => {
+            let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+            merge_into(&tx, remove_id, keep_id).map_err(|e| e.to_string())?;
+            delete_song_file(&tx, &path)?;
+            tx.commit().map_err(|e| e.to_string())
+        }
+    }
+}
+
+// --- Commands ---
+
+// strategy: "audio_hash" / "metadata" / "fingerprint"
+#[tauri::command]
+pub async fn find_duplicates(
+    strategy: String,
+    options: Option<DuplicateOptions>,
+    db_state: State<'_, DbState>,
+    groups: State<'_, DuplicateGroups>,
+) -> Result<DuplicateReport, String> {
+    let db = db_state.conn.clone();
+    let report = tauri::async_runtime::spawn_blocking(move || find(&db, &strategy, &options.unwrap_or_default()))
+        .await
+        .map_err(|e| e.to_string())??;
+    groups.remember(&report);
+    Ok(report)
+}
+
+// action: "delete" 删除副本，"move" 把副本移到 target_folder
+#[tauri::command]
+pub async fn resolve_duplicates(
+    resolutions: Vec<DuplicateResolution>,
+    action: String,
+    target_folder: Option<String>,
+    db_state: State<'_, DbState>,
+    groups: State<'_, DuplicateGroups>,
+) -> Result<ResolveReport, String> {
+    let target = match action.as_str() {
+        "delete" => None,
+        "move" => Some(target_folder.ok_or("移动副本需要指定目标文件夹")?),
+        other => return Err(format!("未知的操作: {}", other)),
+    };
+
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    let mut report = ResolveReport::default();
+    for resolution in &resolutions {
+        for remove_id in &resolution.remove_ids {
+            let result = groups
+                .check(resolution.keep_id, *remove_id)
+                .and_then(|()| resolve_one(&conn, resolution.keep_id, *remove_id, target.as_deref().map(Path::new)));
+            match result {
+                Ok(()) => {
+                    groups.forget(*remove_id);
+                    report.processed += 1;
+                }
+                Err(e) => report.failed.push(format!("{}: {}", remove_id, e)),
+            }
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 固定种子的伪随机指纹
+    fn noise(seed: u32, len: usize) -> Vec<u32> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                state
+            })
+            .collect()
+    }
+
+    #[test]
+    fn identical_and_shifted_fingerprints_match() {
+        let fp = noise(1, 400);
+        assert_eq!(fingerprint_similarity(&fp, &fp), 1.0);
+        // 前面多出约 1 秒的静音 / 片头
+        let mut delayed = noise(2, 8);
+        delayed.extend_from_slice(&fp);
+        assert_eq!(fingerprint_similarity(&fp, &delayed), 1.0);
+        assert_eq!(fingerprint_similarity(&delayed, &fp), 1.0);
+    }
+
+    #[test]
+    fn small_bit_errors_lower_similarity_slightly() {
+        let fp = noise(3, 400);
+        let noisy: Vec<u32> = fp.iter().map(|v| v ^ 0b1001).collect();
+        let similarity = fingerprint_similarity(&fp, &noisy);
+        assert!((similarity - (1.0 - 2.0 / 32.0)).abs() < 1e-9, "{}", similarity);
+    }
+
+    #[test]
+    fn unrelated_or_empty_fingerprints_do_not_match() {
+        let similarity = fingerprint_similarity(&noise(4, 400), &noise(5, 400));
+        assert!(similarity < 0.6, "{}", similarity);
+        assert_eq!(fingerprint_similarity(&[], &noise(6, 10)), 0.0);
+        // 错位超过上限时不再对齐
+        let fp = noise(7, 400);
+        let mut far = noise(8, FINGERPRINT_MAX_OFFSET + 1);
+        far.extend_from_slice(&fp);
+        assert!(fingerprint_similarity(&fp, &far) < 0.6);
+    }
+
+    #[test]
+    fn only_members_of_the_same_group_can_be_removed() {
+        let groups = DuplicateGroups::default();
+        groups.0.lock().unwrap().extend([(1, 0), (2, 0), (3, 1), (4, 1)]);
+        assert!(groups.check(1, 2).is_ok());
+        assert!(groups.check(1, 3).is_err());
+        assert!(groups.check(9, 1).is_err());
+        groups.forget(2);
+        assert!(groups.check(1, 2).is_err());
+    }
+
+    #[test]
+    fn merge_drops_playlist_entries_already_holding_the_kept_song() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE songs (id INTEGER PRIMARY KEY, play_count INTEGER, skip_count INTEGER, last_played INTEGER, rating INTEGER);
+             CREATE TABLE playlist_items (id INTEGER PRIMARY KEY, playlist_id INTEGER, song_id INTEGER, position INTEGER);
+             CREATE TABLE favorites (song_id INTEGER PRIMARY KEY, added_at INTEGER);
+             CREATE TABLE play_events (id INTEGER PRIMARY KEY, song_id INTEGER, started_at INTEGER, completed INTEGER, skipped INTEGER);
+             INSERT INTO songs (id, rating) VALUES (1, NULL), (2, 4), (3, NULL);
+             -- 歌单 10 同时有 1 和 2，歌单 20 只有副本 2
+             INSERT INTO playlist_items (playlist_id, song_id, position) VALUES
+                 (10, 1, 0), (10, 3, 1), (10, 2, 2), (10, 3, 3), (20, 2, 0);
+             INSERT INTO play_events (song_id, started_at, completed, skipped) VALUES (2, 100, 1, 0), (1, 50, 0, 1);",
+        )
+        .unwrap();
+
+        merge_into(&conn, 2, 1).unwrap();
+
+        let items = |playlist_id: i64| -> Vec<(i64, i64)> {
+            conn.prepare("SELECT song_id, position FROM playlist_items WHERE playlist_id = ?1 ORDER BY position")
+                .unwrap()
+                .query_map([playlist_id], |row| Ok((row.get(0)?, row.get(1)?)))
+                .unwrap()
+                .map(|r| r.unwrap())
+                .collect()
+        };
+        assert_eq!(items(10), [(1, 0), (3, 1), (3, 2)]);
+        assert_eq!(items(20), [(1, 0)]);
+
+        let stats: (i64, i64, i64, i64) = conn
+            .query_row("SELECT play_count, skip_count, last_played, rating FROM songs WHERE id = 1", [], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .unwrap();
+        assert_eq!(stats, (1, 1, 100, 4));
+    }
+}
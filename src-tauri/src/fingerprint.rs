@@ -0,0 +1,713 @@
+use crate::database::DbState;
+use crate::playlists::now_millis;
+use crate::settings::{load_setting, save_setting};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use rodio::{Decoder, Source};
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, State};
+
+// --- 声学指纹：Chromaprint 默认算法 (TEST2) 的纯 Rust 实现，本地计算，不联网 ---
+// 解码 -> 单声道 11025Hz -> 4096 点 FFT (步长 1/3 帧) -> 12 维色度 -> 5 帧平滑 + 归一化
+// -> 16 个 Haar 型分类器，每帧得到一个 32 位子指纹
+// 数据库保存原始子指纹 (小端 u32)，提交 AcoustID 时再压缩编码
+
+const SAMPLE_RATE: u32 = 11025;
+const FRAME_SIZE: usize = 4096;
+const FRAME_STEP: usize = FRAME_SIZE / 3;
+const MIN_FREQ: f64 = 28.0;
+const MAX_FREQ: f64 = 3520.0;
+// 与 fpcalc 默认值一致，只取前 120 秒
+const MAX_SECONDS: u32 = 120;
+// 重采样低通截止为目标奈奎斯特频率的 0.8，每侧 8 个目标采样点宽
+const RESAMPLE_CUTOFF: f64 = 0.8;
+const RESAMPLE_HALF_TAPS: f64 = 8.0;
+const CHROMA_FILTER: [f64; 5] = [0.25, 0.75, 1.0, 0.75, 0.25];
+const ALGORITHM_TEST2: u8 = 1;
+
+const ACOUSTID_CONFIG_KEY: &str = "acoustid";
+const DEFAULT_ACOUSTID_URL: &str = "https://api.acoustid.org";
+
+// (类型, 色度起点, 色度跨度, 时间跨度) + 三个量化阈值，取自 Chromaprint 的 TEST2 配置
+const CLASSIFIERS: [(u8, usize, usize, usize, [f64; 3]); 16] = [
+    (0, 4, 3, 15, [1.98215, 2.35817, 2.63523]),
+    (4, 4, 6, 15, [-1.03809, -0.651211, -0.282167]),
+    (1, 0, 4, 16, [-0.298702, 0.119262, 0.558497]),
+    (3, 8, 2, 12, [-0.105439, 0.0153946, 0.135898]),
+    (3, 4, 4, 8, [-0.142891, 0.0258736, 0.200632]),
+    (4, 0, 3, 5, [-0.826319, -0.590612, -0.368214]),
+    (1, 2, 2, 9, [-0.557409, -0.233035, 0.0534525]),
+    (2, 7, 3, 4, [-0.0646826, 0.00620476, 0.0784847]),
+    (2, 6, 2, 16, [-0.192387, -0.029699, 0.215855]),
+    (2, 1, 3, 2, [-0.0397818, -0.00568076, 0.0292026]),
+    (5, 10, 1, 15, [-0.53823, -0.369934, -0.190235]),
+    (3, 6, 2, 10, [-0.124877, 0.0296483, 0.139239]),
+    (2, 1, 1, 14, [-0.101475, 0.0225617, 0.231971]),
+    (3, 5, 6, 4, [-0.0799915, -0.00729616, 0.063262]),
+    (1, 9, 2, 12, [-0.272556, 0.019424, 0.302559]),
+    (1, 4, 2, 14, [-0.164292, -0.0321188, 0.0846339]),
+];
+const MAX_FILTER_WIDTH: usize = 16;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AcoustIdConfig {
+    pub api_key: String,
+    // 为空时使用官方服务，可指向镜像或本地桩服务
+    pub base_url: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct AcoustIdFingerprint {
+    // 整首时长 (秒)，AcoustID 查询需要
+    pub duration: u32,
+    pub fingerprint: String,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct FingerprintProgress {
+    pub done: u32,
+    pub total: u32,
+    // 已是最新、无需重新计算
+    pub skipped: u32,
+    pub failed: u32,
+    pub finished: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AcoustIdArtist {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AcoustIdReleaseGroup {
+    pub id: String,
+    pub title: Option<String>,
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AcoustIdRecording {
+    pub id: String,
+    pub title: Option<String>,
+    #[serde(default)]
+    pub artists: Vec<AcoustIdArtist>,
+    pub duration: Option<f64>,
+    #[serde(default)]
+    pub releasegroups: Vec<AcoustIdReleaseGroup>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AcoustIdMatch {
+    pub id: String,
+    pub score: f64,
+    #[serde(default)]
+    pub recordings: Vec<AcoustIdRecording>,
+}
+
+#[derive(Deserialize)]
+struct LookupError {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct LookupResponse {
+    status: String,
+    #[serde(default)]
+    results: Vec<AcoustIdMatch>,
+    error: Option<LookupError>,
+}
+
+// --- 解码与重采样 ---
+
+// 解码前 MAX_SECONDS 秒并混成单声道，返回 (采样, 采样率)
+fn decode_mono(path: &Path) -> Result<(Vec<f64>, u32), String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let source = Decoder::new(BufReader::new(file)).map_err(|e| e.to_string())?;
+    let channels = source.channels().max(1) as usize;
+    let rate = source.sample_rate();
+    let limit = (rate * MAX_SECONDS) as usize * channels;
+
+    let mut mono = Vec::with_capacity(limit / channels);
+    let mut sum = 0.0;
+    for (i, sample) in source.take(limit).enumerate() {
+        sum += sample as f64;
+        if (i + 1) % channels == 0 {
+            mono.push(sum / channels as f64);
+            sum = 0.0;
+        }
+    }
+    Ok((mono, rate))
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-9 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+// 加 Hann 窗的 sinc 低通插值
+fn resample(input: &[f64], from: u32) -> Vec<f64> {
+    if from == SAMPLE_RATE {
+        return input.to_vec();
+    }
+    let ratio = SAMPLE_RATE as f64 / from as f64;
+    let cutoff = RESAMPLE_CUTOFF * ratio.min(1.0);
+    let half = (RESAMPLE_HALF_TAPS / cutoff).ceil() as i64;
+    let out_len = (input.len() as f64 * ratio) as usize;
+
+    (0..out_len)
+        .map(|n| {
+            let t = n as f64 / ratio;
+            let center = t.floor() as i64;
+            let start = (center - half + 1).max(0);
+            let end = (center + half).min(input.len() as i64 - 1);
+            (start..=end)
+                .map(|k| {
+                    let x = t - k as f64;
+                    let window = 0.5 + 0.5 * (PI * x / half as f64).cos();
+                    input[k as usize] * cutoff * sinc(cutoff * x) * window
+                })
+                .sum()
+        })
+        .collect()
+}
+
+// --- FFT 与色度 ---
+
+// 基 2 迭代 FFT，返回功率谱的前 n/2+1 项
+struct Fft {
+    n: usize,
+    twiddles: Vec<(f64, f64)>,
+    reversed: Vec<usize>,
+}
+
+impl Fft {
+    fn new(n: usize) -> Self {
+        let bits = n.trailing_zeros();
+        Self {
+            n,
+            twiddles: (0..n / 2)
+                .map(|k| {
+                    let angle = -2.0 * PI * k as f64 / n as f64;
+                    (angle.cos(), angle.sin())
+                })
+                .collect(),
+            reversed: (0..n).map(|i| i.reverse_bits() >> (usize::BITS - bits)).collect(),
+        }
+    }
+
+    fn power_spectrum(&self, input: &[f64]) -> Vec<f64> {
+        let mut re: Vec<f64> = self.reversed.iter().map(|i| input[*i]).collect();
+        let mut im = vec![0.0; self.n];
+        let mut size = 2;
+        while size <= self.n {
+            let step = self.n / size;
+            for start in (0..self.n).step_by(size) {
+                for k in 0..size / 2 {
+                    let (wr, wi) = self.twiddles[k * step];
+                    let (a, b) = (start + k, start + k + size / 2);
+                    let tr = re[b] * wr - im[b] * wi;
+                    let ti = re[b] * wi + im[b] * wr;
+                    re[b] = re[a] - tr;
+                    im[b] = im[a] - ti;
+                    re[a] += tr;
+                    im[a] += ti;
+                }
+            }
+            size *= 2;
+        }
+        (0..=self.n / 2).map(|i| re[i] * re[i] + im[i] * im[i]).collect()
+    }
+}
+
+// 频点 -> 半音 (以 A0 = 27.5Hz 为八度起点)
+fn chroma_notes() -> Vec<(usize, usize)> {
+    let index_of = |freq: f64| (FRAME_SIZE as f64 * freq / SAMPLE_RATE as f64).round() as usize;
+    let min_index = index_of(MIN_FREQ).max(1);
+    let max_index = index_of(MAX_FREQ).min(FRAME_SIZE / 2);
+    (min_index..max_index)
+        .map(|i| {
+            let freq = i as f64 * SAMPLE_RATE as f64 / FRAME_SIZE as f64;
+            let octave = (freq / 27.5).log2();
+            (i, (12.0 * (octave - octave.floor())) as usize)
+        })
+        .collect()
+}
+
+fn chroma_frames(samples: &[f64]) -> Vec<[f64; 12]> {
+    let fft = Fft::new(FRAME_SIZE);
+    let window: Vec<f64> = (0..FRAME_SIZE)
+        .map(|i| (0.54 - 0.46 * (2.0 * PI * i as f64 / (FRAME_SIZE - 1) as f64).cos()) / i16::MAX as f64)
+        .collect();
+    let notes = chroma_notes();
+
+    let mut frames = Vec::new();
+    let mut start = 0;
+    let mut buffer = vec![0.0; FRAME_SIZE];
+    while start + FRAME_SIZE <= samples.len() {
+        for (i, b) in buffer.iter_mut().enumerate() {
+            *b = samples[start + i] * window[i];
+        }
+        let spectrum = fft.power_spectrum(&buffer);
+        let mut chroma = [0.0; 12];
+        for (bin, note) in &notes {
+            chroma[*note] += spectrum[*bin];
+        }
+        frames.push(chroma);
+        start += FRAME_STEP;
+    }
+    frames
+}
+
+// 5 帧加权平滑后做欧氏归一化，能量过低的帧置零
+fn smooth_and_normalize(frames: &[[f64; 12]]) -> Vec<[f64; 12]> {
+    frames
+        .windows(CHROMA_FILTER.len())
+        .map(|window| {
+            let mut out = [0.0; 12];
+            for (row, coefficient) in window.iter().zip(CHROMA_FILTER) {
+                for (o, v) in out.iter_mut().zip(row) {
+                    *o += v * coefficient;
+                }
+            }
+            let norm = out.iter().map(|v| v * v).sum::<f64>().sqrt();
+            if norm < 0.01 {
+                [0.0; 12]
+            } else {
+                out.map(|v| v / norm)
+            }
+        })
+        .collect()
+}
+
+// --- 分类器 ---
+
+struct IntegralImage {
+    // (行数 + 1) x 13，首行首列为 0
+    sums: Vec<[f64; 13]>,
+}
+
+impl IntegralImage {
+    fn new(rows: &[[f64; 12]]) -> Self {
+        let mut sums = vec![[0.0; 13]; rows.len() + 1];
+        for (r, row) in rows.iter().enumerate() {
+            for c in 0..12 {
+                sums[r + 1][c + 1] = row[c] + sums[r][c + 1] + sums[r + 1][c] - sums[r][c];
+            }
+        }
+        Self { sums }
+    }
+
+    // 行 [r1, r2) x 列 [c1, c2) 之和
+    fn area(&self, r1: usize, c1: usize, r2: usize, c2: usize) -> f64 {
+        self.sums[r2][c2] - self.sums[r1][c2] - self.sums[r2][c1] + self.sums[r1][c1]
+    }
+}
+
+fn subtract_log(a: f64, b: f64) -> f64 {
+    (1.0 + a).ln() - (1.0 + b).ln()
+}
+
+fn apply_filter(image: &IntegralImage, kind: u8, x: usize, y: usize, h: usize, w: usize) -> f64 {
+    let area = |r1, c1, r2, c2| image.area(r1, c1, r2, c2);
+    match kind {
+        0 => subtract_log(area(x, y, x + w, y + h), 0.0),
+        1 => subtract_log(area(x, y + h / 2, x + w, y + h), area(x, y, x + w, y + h / 2)),
+        2 => subtract_log(area(x + w / 2, y, x + w, y + h), area(x, y, x + w / 2, y + h)),
+        3 => subtract_log(
+            area(x, y + h / 2, x + w / 2, y + h) + area(x + w / 2, y, x + w, y + h / 2),
+            area(x, y, x + w / 2, y + h / 2) + area(x + w / 2, y + h / 2, x + w, y + h),
+        ),
+        4 => subtract_log(
+            area(x, y + h / 3, x + w, y + 2 * (h / 3)),
+            area(x, y, x + w, y + h / 3) + area(x, y + 2 * (h / 3), x + w, y + h),
+        ),
+        _ => subtract_log(
+            area(x + w / 3, y, x + 2 * (w / 3), y + h),
+            area(x, y, x + w / 3, y + h) + area(x + 2 * (w / 3), y, x + w, y + h),
+        ),
+    }
+}
+
+fn quantize(value: f64, thresholds: &[f64; 3]) -> u32 {
+    // 量化结果按格雷码输出：0 1 3 2
+    match value {
+        v if v < thresholds[0] => 0,
+        v if v < thresholds[1] => 1,
+        v if v < thresholds[2] => 3,
+        _ => 2,
+    }
+}
+
+// 11025Hz 单声道采样 (i16 幅度) -> 原始子指纹
+pub fn compute_raw(samples: &[f64]) -> Vec<u32> {
+    let features = smooth_and_normalize(&chroma_frames(samples));
+    if features.len() < MAX_FILTER_WIDTH {
+        return Vec::new();
+    }
+    let image = IntegralImage::new(&features);
+    (0..=features.len() - MAX_FILTER_WIDTH)
+        .map(|offset| {
+            CLASSIFIERS.iter().fold(0u32, |bits, (kind, y, h, w, thresholds)| {
+                (bits << 2) | quantize(apply_filter(&image, *kind, offset, *y, *h, *w), thresholds)
+            })
+        })
+        .collect()
+}
+
+pub fn fingerprint_file(path: &Path) -> Result<Vec<u32>, String> {
+    let (mono, rate) = decode_mono(path)?;
+    let raw = compute_raw(&resample(&mono, rate));
+    if raw.is_empty() {
+        return Err("音频过短，无法计算指纹".to_string());
+    }
+    Ok(raw)
+}
+
+// --- 存储与 AcoustID 编码 ---
+
+pub fn raw_to_blob(raw: &[u32]) -> Vec<u8> {
+    raw.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+pub fn blob_to_raw(bytes: &[u8]) -> Vec<u32> {
+    bytes
+        .chunks_exact(4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
+
+// 低位在前的定长位打包
+fn pack_bits(values: &[u8], width: u32) -> Vec<u8> {
+    let mut out = vec![0u8; (values.len() * width as usize).div_ceil(8)];
+    for (i, value) in values.iter().enumerate() {
+        for bit in 0..width as usize {
+            if value >> bit & 1 != 0 {
+                let pos = i * width as usize + bit;
+                out[pos / 8] |= 1 << (pos % 8);
+            }
+        }
+    }
+    out
+}
+
+// Chromaprint 压缩格式：相邻子指纹异或后记录置位位置的差值，
+// 差值按 3 位存放，>= 7 的部分另按 5 位存放；再做无填充的 URL 安全 base64
+pub fn encode_acoustid(raw: &[u32]) -> String {
+    let mut normal: Vec<u8> = Vec::with_capacity(raw.len() * 4);
+    let mut exceptional: Vec<u8> = Vec::new();
+    for (i, value) in raw.iter().enumerate() {
+        let mut x = if i == 0 { *value } else { value ^ raw[i - 1] };
+        let (mut bit, mut last_bit) = (1u8, 0u8);
+        while x != 0 {
+            if x & 1 != 0 {
+                let delta = bit - last_bit;
+                if delta >= 7 {
+                    normal.push(7);
+                    exceptional.push(delta - 7);
+                } else {
+                    normal.push(delta);
+                }
+                last_bit = bit;
+            }
+            x >>= 1;
+            bit += 1;
+        }
+        normal.push(0);
+    }
+
+    let size = raw.len();
+    let mut bytes = vec![ALGORITHM_TEST2, (size >> 16) as u8, (size >> 8) as u8, size as u8];
+    bytes.extend(pack_bits(&normal, 3));
+    bytes.extend(pack_bits(&exceptional, 5));
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn store_fingerprint(conn: &Connection, song_id: i64, raw: &[u32], file_size: i64) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO fingerprints (song_id, fingerprint, created_at, file_size) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(song_id) DO UPDATE SET
+            fingerprint = excluded.fingerprint, created_at = excluded.created_at, file_size = excluded.file_size",
+        (song_id, raw_to_blob(raw), now_millis(), file_size),
+    )?;
+    Ok(())
+}
+
+// 文件大小与计算时一致则沿用已有指纹，否则重新计算并保存
+pub fn ensure_fingerprint(db: &Mutex<Connection>, song_id: i64) -> Result<(Vec<u32>, u32), String> {
+    let (path, duration, stored) = {
+        let conn = db.lock().map_err(|e| e.to_string())?;
+        let (path, duration): (String, Option<u32>) = conn
+            .query_row("SELECT path, duration FROM songs WHERE id = ?1", [song_id], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .map_err(|e| e.to_string())?;
+        let stored = conn
+            .query_row(
+                "SELECT fingerprint, file_size FROM fingerprints WHERE song_id = ?1",
+                [song_id],
+                |row| Ok((row.get::<_, Vec<u8>>(0)?, row.get::<_, Option<i64>>(1)?)),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        (path, duration, stored)
+    };
+
+    let file_size = fs::metadata(&path).map(|m| m.len() as i64).map_err(|e| e.to_string())?;
+    if let Some((blob, Some(size))) = stored {
+        if size == file_size {
+            return Ok((blob_to_raw(&blob), duration.unwrap_or(0)));
+        }
+    }
+    let raw = fingerprint_file(Path::new(&path))?;
+    let conn = db.lock().map_err(|e| e.to_string())?;
+    store_fingerprint(&conn, song_id, &raw, file_size).map_err(|e| e.to_string())?;
+    Ok((raw, duration.unwrap_or(0)))
+}
+
+fn stale_song_ids(conn: &Connection, song_ids: Option<&[i64]>) -> rusqlite::Result<Vec<(i64, String, Option<i64>)>> {
+    let mut stmt = conn.prepare(
+        "SELECT s.id, s.path, f.file_size FROM songs s LEFT JOIN fingerprints f ON f.song_id = s.id ORDER BY s.id",
+    )?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .filter_map(|r| r.ok())
+        .filter(|(id, _, _): &(i64, String, Option<i64>)| song_ids.is_none_or(|ids| ids.contains(id)))
+        .collect();
+    Ok(rows)
+}
+
+pub async fn lookup(base_url: &str, api_key: &str, duration: u32, fingerprint: &str) -> Result<Vec<AcoustIdMatch>, String> {
+    let url = format!("{}/v2/lookup", base_url.trim_end_matches('/'));
+    let body = reqwest::Client::new()
+        .post(url)
+        .form(&[
+            ("client", api_key),
+            ("duration", &duration.to_string()),
+            ("fingerprint", fingerprint),
+            ("meta", "recordings releasegroups"),
+            ("format", "json"),
+        ])
+        .send()
+        .await
+        .map_err(|e| e.to_string())?
+        .text()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let response: LookupResponse = serde_json::from_str(&body).map_err(|e| e.to_string())?;
+    if response.status != "ok" {
+        return Err(response.error.map(|e| e.message).unwrap_or(response.status));
+    }
+    let mut results = response.results;
+    results.sort_by(|a, b| b.score.total_cmp(&a.score));
+    Ok(results)
+}
+
+// --- Commands ---
+
+// 为整个曲库 (或指定歌曲) 补算指纹，进度通过 fingerprint:progress 事件推送
+#[tauri::command]
+pub async fn compute_fingerprints(
+    song_ids: Option<Vec<i64>>,
+    app: AppHandle,
+    db_state: State<'_, DbState>,
+) -> Result<FingerprintProgress, String> {
+    let db = db_state.conn.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let songs = {
+            let conn = db.lock().map_err(|e| e.to_string())?;
+            stale_song_ids(&conn, song_ids.as_deref()).map_err(|e| e.to_string())?
+        };
+        let mut progress = FingerprintProgress { total: songs.len() as u32, ..Default::default() };
+        for (id, path, stored_size) in songs {
+            let current_size = fs::metadata(&path).map(|m| m.len() as i64).ok();
+            match current_size {
+                Some(size) if stored_size == Some(size) => progress.skipped += 1,
+                Some(size) => match fingerprint_file(Path::new(&path)) {
+                    Ok(raw) => {
+                        let conn = db.lock().map_err(|e| e.to_string())?;
+                        if store_fingerprint(&conn, id, &raw, size).is_err() {
+                            progress.failed += 1;
+                        }
+                    }
+                    Err(_) => progress.failed += 1,
+                },
+                None => progress.failed += 1,
+            }
+            progress.done += 1;
+            let _ = app.emit("fingerprint:progress", &progress);
+        }
+        progress.finished = true;
+        let _ = app.emit("fingerprint:progress", &progress);
+        Ok(progress)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn get_acoustid_fingerprint(song_id: i64, db_state: State<'_, DbState>) -> Result<AcoustIdFingerprint, String> {
+    let db = db_state.conn.clone();
+    let (raw, duration) = tauri::async_runtime::spawn_blocking(move || ensure_fingerprint(&db, song_id))
+        .await
+        .map_err(|e| e.to_string())??;
+    Ok(AcoustIdFingerprint { duration, fingerprint: encode_acoustid(&raw) })
+}
+
+#[tauri::command]
+pub async fn lookup_acoustid(song_id: i64, db_state: State<'_, DbState>) -> Result<Vec<AcoustIdMatch>, String> {
+    let config: AcoustIdConfig = {
+        let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+        load_setting(&conn, ACOUSTID_CONFIG_KEY)
+    };
+    if config.api_key.trim().is_empty() {
+        return Err("未设置 AcoustID API Key".to_string());
+    }
+    let fingerprint = get_acoustid_fingerprint(song_id, db_state).await?;
+    let base_url = config.base_url.as_deref().filter(|u| !u.trim().is_empty()).unwrap_or(DEFAULT_ACOUSTID_URL);
+    lookup(base_url, config.api_key.trim(), fingerprint.duration, &fingerprint.fingerprint).await
+}
+
+#[tauri::command]
+pub fn get_acoustid_config(db_state: State<'_, DbState>) -> Result<AcoustIdConfig, String> {
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    Ok(load_setting(&conn, ACOUSTID_CONFIG_KEY))
+}
+
+#[tauri::command]
+pub fn set_acoustid_config(config: AcoustIdConfig, db_state: State<'_, DbState>) -> Result<(), String> {
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    save_setting(&conn, ACOUSTID_CONFIG_KEY, &config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::duplicates::fingerprint_similarity;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    // 每秒换一个音高的合成旋律，采样为 i16 幅度
+    fn melody(rate: u32, seconds: u32, gain: f64) -> Vec<f64> {
+        const NOTES: [f64; 5] = [440.0, 523.25, 659.25, 392.0, 587.33];
+        (0..rate * seconds)
+            .map(|i| {
+                let t = i as f64 / rate as f64;
+                let freq = NOTES[(t as usize) % NOTES.len()];
+                gain * 12000.0 * (2.0 * PI * freq * t).sin()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn encodes_like_chromaprint_compressor() {
+        // 与 Chromaprint 压缩器单元测试的期望值一致 (首字节为算法号)
+        let bytes = |raw: &[u32]| URL_SAFE_NO_PAD.decode(encode_acoustid(raw)).unwrap();
+        assert_eq!(bytes(&[1]), [1, 0, 0, 1, 1]);
+        assert_eq!(bytes(&[7]), [1, 0, 0, 1, 73, 0]);
+        assert_eq!(bytes(&[1 << 6]), [1, 0, 0, 1, 7, 0]);
+        assert_eq!(bytes(&[1 << 8]), [1, 0, 0, 1, 7, 2]);
+        assert_eq!(bytes(&[1, 0]), [1, 0, 0, 2, 65, 0]);
+        assert_eq!(bytes(&[1, 1]), [1, 0, 0, 2, 1, 0]);
+        assert_eq!(encode_acoustid(&[1]), "AQAAAQE");
+    }
+
+    #[test]
+    fn blob_round_trips() {
+        let raw = vec![0, 1, u32::MAX, 0xDEAD_BEEF];
+        assert_eq!(blob_to_raw(&raw_to_blob(&raw)), raw);
+    }
+
+    #[test]
+    fn tone_fingerprint_is_stable() {
+        let samples = melody(SAMPLE_RATE, 10, 1.0);
+        let raw = compute_raw(&samples);
+        // 帧数 (N - 4096) / 1365 + 1，平滑去掉 4 帧，分类器窗口再去掉 15 帧
+        assert_eq!(raw.len(), (samples.len() - FRAME_SIZE) / FRAME_STEP + 1 - 4 - (MAX_FILTER_WIDTH - 1));
+        assert_eq!(compute_raw(&samples), raw);
+
+        let quieter = compute_raw(&melody(SAMPLE_RATE, 10, 0.5));
+        assert!(fingerprint_similarity(&raw, &quieter) > 0.95);
+
+        let resampled = compute_raw(&resample(&melody(44100, 10, 1.0), 44100));
+        assert!(fingerprint_similarity(&raw, &resampled) > 0.9);
+
+        assert!(compute_raw(&melody(SAMPLE_RATE, 1, 1.0)).is_empty());
+    }
+
+    // 本地桩服务：接收一次请求，返回固定 JSON，并把请求体交回测试检查
+    fn stub_server(response: &'static str) -> (String, std::thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            loop {
+                let n = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request).to_string();
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let length = text
+                        .lines()
+                        .find_map(|l| l.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap()))
+                        .unwrap_or(0);
+                    if request.len() >= end + 4 + length || n == 0 {
+                        break;
+                    }
+                }
+            }
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                response.len(),
+                response
+            )
+            .unwrap();
+            String::from_utf8_lossy(&request).to_string()
+        });
+        (url, handle)
+    }
+
+    #[tokio::test]
+    async fn lookup_parses_and_sorts_results() {
+        let (url, server) = stub_server(
+            r#"{"status":"ok","results":[
+                {"id":"low","score":0.4,"recordings":[]},
+                {"id":"high","score":0.97,"recordings":[{"id":"rec","title":"Song",
+                    "artists":[{"id":"art","name":"Artist"}],"duration":215.0,
+                    "releasegroups":[{"id":"rg","title":"Album","type":"Album"}]}]}]}"#,
+        );
+        let results = lookup(&format!("{}/", url), "key123", 215, "AQAAAQE").await.unwrap();
+        let request = server.join().unwrap();
+
+        assert!(request.starts_with("POST /v2/lookup "));
+        assert!(request.contains("client=key123"));
+        assert!(request.contains("fingerprint=AQAAAQE"));
+        assert!(request.contains("duration=215"));
+        assert_eq!(results.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(), ["high", "low"]);
+        let recording = &results[0].recordings[0];
+        assert_eq!(recording.artists[0].name, "Artist");
+        assert_eq!(recording.releasegroups[0].kind.as_deref(), Some("Album"));
+    }
+
+    #[tokio::test]
+    async fn lookup_reports_service_errors() {
+        let (url, server) = stub_server(r#"{"status":"error","error":{"code":4,"message":"invalid API key"}}"#);
+        let error = lookup(&url, "bad", 215, "AQAAAQE").await.unwrap_err();
+        server.join().unwrap();
+        assert_eq!(error, "invalid API key");
+    }
+}
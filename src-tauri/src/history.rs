@@ -0,0 +1,192 @@
+use crate::database::DbState;
+use crate::music::{song_columns_with, Song, SONG_COLUMN_COUNT};
+use crate::playlists::now_millis;
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::State;
+
+// --- 播放记录：音频线程结束会话后交给写入线程存入 play_events，songs 上的统计列由触发器维护 ---
+
+// 听满一半或 4 分钟算一次完整播放 (与常见的 scrobble 规则一致)，否则记为跳过
+const COMPLETE_MAX_MS: u64 = 4 * 60 * 1000;
+// 不足 1 秒的播放不记录 (启动时恢复上次歌曲、连续切歌等)
+const MIN_RECORDED_MS: u64 = 1000;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct HistoryEntry {
+    pub event_id: i64,
+    // 毫秒时间戳
+    pub started_at: i64,
+    pub listened_ms: Option<u64>,
+    pub completed: bool,
+    pub skipped: bool,
+    pub song: Song,
+}
+
+#[derive(Deserialize, Default, Debug)]
+pub struct HistoryRange {
+    // 毫秒时间戳，[from, to)
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+pub fn record_play(
+    conn: &Connection,
+    path: &str,
+    started_at: i64,
+    listened_ms: u64,
+    reached_end: bool,
+) -> rusqlite::Result<()> {
+    if listened_ms < MIN_RECORDED_MS && !reached_end {
+        return Ok(());
+    }
+    // 不在曲库中的文件不记录
+    let Some((song_id, duration)) = conn
+        .query_row("SELECT id, duration FROM songs WHERE path = ?1", [path], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, Option<i64>>(1)?.unwrap_or(0).max(0) as u64))
+        })
+        .optional()?
+    else {
+        return Ok(());
+    };
+
+    let threshold = if duration > 0 { (duration * 1000 / 2).min(COMPLETE_MAX_MS) } else { COMPLETE_MAX_MS };
+    let completed = reached_end || listened_ms >= threshold;
+    conn.execute(
+        "INSERT INTO play_events (song_id, started_at, listened_ms, completed, skipped) VALUES (?1, ?2, ?3, ?4, ?5)",
+        (song_id, started_at, listened_ms as i64, completed, !completed),
+    )?;
+    Ok(())
+}
+
+struct PlaySession {
+    path: String,
+    started_at: i64,
+    listened: Duration,
+    // 正在播放时记录起点，暂停时为 None
+    playing_since: Option<Instant>,
+}
+
+impl PlaySession {
+    fn listened(&self) -> Duration {
+        self.listened + self.playing_since.map(|t| t.elapsed()).unwrap_or_default()
+    }
+}
+
+// 已结束的一次播放，等待写入数据库
+struct FinishedPlay {
+    path: String,
+    started_at: i64,
+    listened_ms: u64,
+    reached_end: bool,
+}
+
+// 写入线程：音频线程只投递消息，从不等待数据库锁；PlayTracker 销毁后通道关闭，线程随之退出
+fn spawn_writer(db: Arc<Mutex<Connection>>) -> Sender<FinishedPlay> {
+    let (tx, rx) = channel::<FinishedPlay>();
+    thread::spawn(move || {
+        for play in rx {
+            let Ok(conn) = db.lock() else { continue };
+            if let Err(e) = record_play(&conn, &play.path, play.started_at, play.listened_ms, play.reached_end) {
+                eprintln!("记录播放历史失败: {}", e);
+            }
+        }
+    });
+    tx
+}
+
+// 音频线程持有的播放会话：按实际播放的墙钟时间累计收听时长，拖动进度不计入
+pub struct PlayTracker {
+    writer: Option<Sender<FinishedPlay>>,
+    session: Option<PlaySession>,
+}
+
+impl PlayTracker {
+    pub fn new(db: Option<Arc<Mutex<Connection>>>) -> Self {
+        Self { writer: db.map(spawn_writer), session: None }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.session.is_some()
+    }
+
+    // 开始新的一首，上一首若未播完则记为中断
+    pub fn start(&mut self, path: &str) {
+        self.finish(false);
+        self.session = Some(PlaySession {
+            path: path.to_string(),
+            started_at: now_millis(),
+            listened: Duration::ZERO,
+            playing_since: Some(Instant::now()),
+        });
+    }
+
+    pub fn set_playing(&mut self, playing: bool) {
+        if let Some(session) = self.session.as_mut() {
+            match (playing, session.playing_since) {
+                (true, None) => session.playing_since = Some(Instant::now()),
+                (false, Some(since)) => {
+                    session.listened += since.elapsed();
+                    session.playing_since = None;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub fn finish(&mut self, reached_end: bool) {
+        let Some(session) = self.session.take() else { return };
+        let Some(writer) = self.writer.as_ref() else { return };
+        let listened_ms = session.listened().as_millis() as u64;
+        let _ = writer.send(FinishedPlay {
+            path: session.path,
+            started_at: session.started_at,
+            listened_ms,
+            reached_end,
+        });
+    }
+}
+
+pub fn query_history(conn: &Connection, range: &HistoryRange) -> rusqlite::Result<Vec<HistoryEntry>> {
+    let sql = format!(
+        "SELECT {}, e.id, e.started_at, e.listened_ms, e.completed, e.skipped
+         FROM play_events e
+         JOIN songs s ON s.id = e.song_id
+         WHERE (?1 IS NULL OR e.started_at >= ?1) AND (?2 IS NULL OR e.started_at < ?2)
+         ORDER BY e.started_at DESC, e.id DESC
+         LIMIT ?3 OFFSET ?4",
+        song_columns_with("s")
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let entries = stmt
+        .query_map(
+            (range.from, range.to, range.limit.unwrap_or(200), range.offset.unwrap_or(0)),
+            |row| {
+                Ok(HistoryEntry {
+                    song: Song::from_row(row)?,
+                    event_id: row.get(SONG_COLUMN_COUNT)?,
+                    started_at: row.get(SONG_COLUMN_COUNT + 1)?,
+                    listened_ms: row.get::<_, Option<i64>>(SONG_COLUMN_COUNT + 2)?.map(|ms| ms as u64),
+                    completed: row.get(SONG_COLUMN_COUNT + 3)?,
+                    skipped: row.get(SONG_COLUMN_COUNT + 4)?,
+                })
+            },
+        )?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(entries)
+}
+
+// --- Commands ---
+
+#[tauri::command]
+pub async fn get_history(range: Option<HistoryRange>, db_state: State<'_, DbState>) -> Result<Vec<HistoryEntry>, String> {
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    query_history(&conn, &range.unwrap_or_default()).map_err(|e| e.to_string())
+}
@@ -0,0 +1,259 @@
+use crate::database::DbState;
+use crate::playlists::now_millis;
+use crate::settings::{load_setting, save_setting};
+use rusqlite::{Connection, OptionalExtension};
+use serde::Serialize;
+use serde_json::Value;
+use tauri::State;
+
+// --- 旧版 localStorage 数据一次性导入 ---
+// 前端把各个 key 的原始值 (字符串或已解析的 JSON) 放进一个对象传进来，按路径匹配 songs 表。
+// 可重复执行：已导入的歌单 (按旧 id)、收藏、历史记录不会重复写入，已有的设置不会被覆盖
+
+pub const WATCHED_FOLDERS_KEY: &str = "watched_folders";
+pub const PLAY_QUEUE_KEY: &str = "play_queue";
+pub const PLAYER_SETTINGS_KEY: &str = "player_settings";
+
+#[derive(Serialize, Clone, Debug)]
+pub struct UnresolvedEntry {
+    // 来源 localStorage key，歌单条目为 "player_custom_playlists/<歌单名>"
+    pub source: String,
+    pub path: String,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct LegacyImportReport {
+    pub favorites: u32,
+    pub playlists: u32,
+    pub playlist_items: u32,
+    pub history: u32,
+    pub watched_folders: u32,
+    pub queue: u32,
+    pub settings: bool,
+    pub unresolved: Vec<UnresolvedEntry>,
+}
+
+// localStorage 里存的是 JSON 字符串，也接受前端已解析好的值
+fn parse_entry(state: &Value, key: &str) -> Option<Value> {
+    match state.get(key)? {
+        Value::String(raw) => serde_json::from_str(raw).ok(),
+        Value::Null => None,
+        other => Some(other.clone()),
+    }
+}
+
+fn song_path(value: &Value) -> Option<&str> {
+    match value {
+        Value::String(path) => Some(path),
+        other => other.get("path").and_then(Value::as_str),
+    }
+}
+
+// 先精确匹配，再忽略路径分隔符差异 (Windows 下新旧版本可能混用 / 与 \)
+fn resolve_song(conn: &Connection, path: &str) -> rusqlite::Result<Option<i64>> {
+    let exact = conn
+        .query_row("SELECT id FROM songs WHERE path = ?1", [path], |row| row.get(0))
+        .optional()?;
+    if exact.is_some() {
+        return Ok(exact);
+    }
+    conn.query_row(
+        "SELECT id FROM songs WHERE replace(path, '\\', '/') = replace(?1, '\\', '/') LIMIT 1",
+        [path],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+// "2024-03-01" -> 当天 0 点的毫秒时间戳 (UTC)
+fn parse_date_millis(date: &str) -> Option<i64> {
+    let mut parts = date.trim().splitn(3, '-').map(|p| p.parse::<i64>().ok());
+    let (y, m, d) = (parts.next()??, parts.next()??, parts.next()??);
+    if !(1..=12).contains(&m) || !(1..=31).contains(&d) {
+        return None;
+    }
+    // days_from_civil
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (m + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    Some((era * 146097 + doe - 719468) * 86_400_000)
+}
+
+fn import_favorites(conn: &Connection, entries: &[Value], report: &mut LegacyImportReport) -> rusqlite::Result<()> {
+    let now = now_millis();
+    for entry in entries {
+        let Some(path) = song_path(entry) else { continue };
+        match resolve_song(conn, path)? {
+            Some(song_id) => {
+                report.favorites += conn.execute(
+                    "INSERT OR IGNORE INTO favorites (song_id, added_at) VALUES (?1, ?2)",
+                    (song_id, now),
+                )? as u32;
+            }
+            None => report.unresolved.push(UnresolvedEntry {
+                source: "player_favorites".to_string(),
+                path: path.to_string(),
+            }),
+        }
+    }
+    Ok(())
+}
+
+fn import_playlists(conn: &Connection, entries: &[Value], report: &mut LegacyImportReport) -> rusqlite::Result<()> {
+    for entry in entries {
+        let Some(legacy_id) = entry.get("id").and_then(Value::as_str) else { continue };
+        let already = conn
+            .query_row("SELECT 1 FROM playlists WHERE legacy_id = ?1", [legacy_id], |_| Ok(()))
+            .optional()?
+            .is_some();
+        if already {
+            continue;
+        }
+
+        let name = entry.get("name").and_then(Value::as_str).unwrap_or_default();
+        let created_at = entry
+            .get("createdAt")
+            .and_then(Value::as_str)
+            .and_then(parse_date_millis)
+            .unwrap_or_else(now_millis);
+        conn.execute(
+            "INSERT INTO playlists (name, position, created_at, updated_at, legacy_id)
+             VALUES (?1, (SELECT COALESCE(MAX(position) + 1, 0) FROM playlists), ?2, ?2, ?3)",
+            (name, created_at, legacy_id),
+        )?;
+        let playlist_id = conn.last_insert_rowid();
+        report.playlists += 1;
+
+        let paths = entry.get("songPaths").and_then(Value::as_array).cloned().unwrap_or_default();
+        let mut position = 0;
+        for path in paths.iter().filter_map(Value::as_str) {
+            match resolve_song(conn, path)? {
+                Some(song_id) => {
+                    conn.execute(
+                        "INSERT INTO playlist_items (playlist_id, song_id, position, added_at) VALUES (?1, ?2, ?3, ?4)",
+                        (playlist_id, song_id, position, created_at),
+                    )?;
+                    position += 1;
+                    report.playlist_items += 1;
+                }
+                None => report.unresolved.push(UnresolvedEntry {
+                    source: format!("player_custom_playlists/{}", name),
+                    path: path.to_string(),
+                }),
+            }
+        }
+    }
+    Ok(())
+}
+
+// 历史记录写成 source = 'legacy' 的完整播放事件，(歌曲, 播放时间) 相同视为已导入
+fn import_history(conn: &Connection, entries: &[Value], report: &mut LegacyImportReport) -> rusqlite::Result<()> {
+    for entry in entries {
+        let Some(path) = entry.get("song").and_then(song_path) else { continue };
+        let Some(played_at) = entry.get("playedAt").and_then(Value::as_i64) else { continue };
+        let Some(song_id) = resolve_song(conn, path)? else {
+            report.unresolved.push(UnresolvedEntry {
+                source: "player_history".to_string(),
+                path: path.to_string(),
+            });
+            continue;
+        };
+        let exists = conn
+            .query_row(
+                "SELECT 1 FROM play_events WHERE song_id = ?1 AND started_at = ?2",
+                (song_id, played_at),
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some();
+        if !exists {
+            conn.execute(
+                "INSERT INTO play_events (song_id, started_at, completed, source) VALUES (?1, ?2, 1, 'legacy')",
+                (song_id, played_at),
+            )?;
+            report.history += 1;
+        }
+    }
+    Ok(())
+}
+
+fn import_watched_folders(conn: &Connection, entries: &[Value], report: &mut LegacyImportReport) -> Result<(), String> {
+    let mut folders: Vec<String> = load_setting(conn, WATCHED_FOLDERS_KEY);
+    for folder in entries.iter().filter_map(Value::as_str) {
+        if !folders.iter().any(|f| f == folder) {
+            folders.push(folder.to_string());
+            report.watched_folders += 1;
+        }
+    }
+    if report.watched_folders > 0 {
+        save_setting(conn, WATCHED_FOLDERS_KEY, &folders)?;
+    }
+    Ok(())
+}
+
+// 播放队列只在后端还没有队列时导入，存为歌曲 id 列表
+fn import_queue(conn: &Connection, entries: &[Value], report: &mut LegacyImportReport) -> Result<(), String> {
+    let existing: Vec<i64> = load_setting(conn, PLAY_QUEUE_KEY);
+    if !existing.is_empty() {
+        return Ok(());
+    }
+    let mut queue = Vec::new();
+    for path in entries.iter().filter_map(song_path) {
+        match resolve_song(conn, path).map_err(|e| e.to_string())? {
+            Some(song_id) => queue.push(song_id),
+            None => report.unresolved.push(UnresolvedEntry {
+                source: "player_queue".to_string(),
+                path: path.to_string(),
+            }),
+        }
+    }
+    if !queue.is_empty() {
+        report.queue = queue.len() as u32;
+        save_setting(conn, PLAY_QUEUE_KEY, &queue)?;
+    }
+    Ok(())
+}
+
+fn import_settings(conn: &Connection, settings: &Value, report: &mut LegacyImportReport) -> Result<(), String> {
+    if !settings.is_object() {
+        return Ok(());
+    }
+    let existing: Value = load_setting(conn, PLAYER_SETTINGS_KEY);
+    if existing.is_null() {
+        save_setting(conn, PLAYER_SETTINGS_KEY, settings)?;
+        report.settings = true;
+    }
+    Ok(())
+}
+
+pub fn import_state(conn: &Connection, state: &Value) -> Result<LegacyImportReport, String> {
+    let mut report = LegacyImportReport::default();
+    let list = |key: &str| parse_entry(state, key).and_then(|v| v.as_array().cloned()).unwrap_or_default();
+
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    import_favorites(&tx, &list("player_favorites"), &mut report).map_err(|e| e.to_string())?;
+    import_playlists(&tx, &list("player_custom_playlists"), &mut report).map_err(|e| e.to_string())?;
+    import_history(&tx, &list("player_history"), &mut report).map_err(|e| e.to_string())?;
+    import_watched_folders(&tx, &list("player_watched_folders"), &mut report)?;
+    import_queue(&tx, &list("player_queue"), &mut report)?;
+    if let Some(settings) = parse_entry(state, "player_settings") {
+        import_settings(&tx, &settings, &mut report)?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(report)
+}
+
+// --- Commands ---
+
+#[tauri::command]
+pub async fn import_legacy_state(json: String, db_state: State<'_, DbState>) -> Result<LegacyImportReport, String> {
+    let state: Value = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+    if !state.is_object() {
+        return Err("导入数据格式错误".to_string());
+    }
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    import_state(&conn, &state)
+}
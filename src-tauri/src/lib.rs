@@ -1,16 +1,68 @@
+mod albums;
+mod artists;
+mod artwork;
+mod audio_hash;
+mod cache;
 mod database;
+mod duplicates;
+mod fingerprint;
+mod history;
+mod legacy_import;
+mod library;
+mod lyrics;
+mod metadata;
 mod music;
+mod palette;
 mod player;
+mod playlists;
+mod ratings;
+mod scanner;
+mod search;
+mod settings;
+mod smart_playlists;
+mod sort_key;
+mod stats;
 mod toolbox;
+mod waveform;
 pub mod error;
+#[cfg(test)]
+mod test_support;
 
+use albums::{get_albums, get_album_tracks};
+use artists::{get_artists, get_artist_detail, get_genres, get_artist_split_config, set_artist_split_config};
+use artwork::{get_cover_config, set_cover_config, embed_cover, remove_embedded_cover, export_embedded_cover};
+use cache::{get_cache_stats, clear_cover_cache, set_cache_limit};
+use waveform::get_waveform;
 use database::DbState;
+use lyrics::{
+    get_parsed_lyrics, get_lyrics_candidates, get_lyrics_encoding, set_lyrics_encoding, get_lyrics_config,
+    set_lyrics_config
+};
 use toolbox::{preview_rename, apply_rename};
 use music::{
     scan_music_folder, scan_folder_as_playlists, get_song_cover_thumbnail, 
-    get_song_cover, get_song_lyrics, 
+    get_song_cover, get_song_cover_sized, get_cover_palette, get_song_lyrics, 
     batch_move_music_files, move_music_file, show_in_folder, delete_music_file,
-    run_cache_cleanup, ImageConcurrencyLimit // 引入新组件
+    ImageConcurrencyLimit // 引入新组件
+};
+use scanner::{start_scan, cancel_scan, ScanJobs};
+use search::search_library;
+use library::{list_songs, count_songs};
+use stats::get_library_stats;
+use duplicates::{find_duplicates, resolve_duplicates, DuplicateGroups};
+use fingerprint::{
+    compute_fingerprints, get_acoustid_fingerprint, lookup_acoustid, get_acoustid_config, set_acoustid_config,
+};
+use legacy_import::import_legacy_state;
+use history::get_history;
+use smart_playlists::{
+    get_smart_playlists, create_smart_playlist, update_smart_playlist, delete_smart_playlist,
+    get_smart_playlist_tracks, preview_smart_playlist,
+};
+use ratings::{get_favorites, toggle_favorite, set_favorite, set_rating, get_rating_config, set_rating_config};
+use playlists::{
+    get_playlists, get_playlist_tracks, create_playlist, rename_playlist, delete_playlist, reorder_playlists,
+    add_to_playlist, remove_from_playlist, reorder_playlist, dedupe_playlist, duplicate_playlist,
 };
 use player::{
     init_player, play_audio, pause_audio, resume_audio, seek_audio, set_volume, get_playback_progress,
@@ -21,6 +73,7 @@ use tauri::{
     tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent},
     Manager,
 };
+use std::sync::Arc;
 use tokio::sync::Semaphore; // 引入信号量
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -31,6 +84,7 @@ pub fn run() {
         .setup(|app| {
             // 1. 初始化数据库状态
             let db_state = DbState::new(app.handle())?;
+            let conn = db_state.conn.clone();
             app.manage(db_state);
 
             // 2. 初始化播放器状态
@@ -41,10 +95,16 @@ pub fn run() {
             // 这是一个全局信号量，所有图片生成请求都要先拿号
             app.manage(ImageConcurrencyLimit(Semaphore::new(4)));
 
-            // 4. 🟢 启动时执行一次缓存清理 (后台运行，不卡启动)
-            run_cache_cleanup(app.handle());
+            // 4. 扫描任务表 (后台扫描 + 取消)
+            app.manage(Arc::new(ScanJobs::default()));
+
+            // 5. 最近一次查重结果 (处理副本时校验分组)
+            app.manage(DuplicateGroups::default());
+
+            // 6. 🟢 缓存管理：启动时清理一次，之后定期清理 (后台运行，不卡启动)
+            cache::init(app.handle(), conn)?;
 
-            // 5. System Tray Setup
+            // 7. System Tray Setup
             let handle = app.handle();
             let show_i = MenuItem::with_id(handle, "show", "显示主界面", true, None::<&str>)?;
             let quit_i = MenuItem::with_id(handle, "quit", "退出", true, None::<&str>)?;
@@ -86,9 +146,71 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             scan_music_folder, 
             scan_folder_as_playlists, 
+            start_scan,
+            cancel_scan,
+            get_artists,
+            get_artist_detail,
+            get_albums,
+            get_album_tracks,
+            search_library,
+            list_songs,
+            count_songs,
+            get_library_stats,
+            find_duplicates,
+            resolve_duplicates,
+            compute_fingerprints,
+            get_acoustid_fingerprint,
+            lookup_acoustid,
+            get_acoustid_config,
+            set_acoustid_config,
+            get_cover_config,
+            set_cover_config,
+            embed_cover,
+            remove_embedded_cover,
+            export_embedded_cover,
+            get_genres,
+            get_artist_split_config,
+            set_artist_split_config,
+            get_playlists,
+            get_playlist_tracks,
+            create_playlist,
+            rename_playlist,
+            delete_playlist,
+            reorder_playlists,
+            add_to_playlist,
+            remove_from_playlist,
+            reorder_playlist,
+            dedupe_playlist,
+            duplicate_playlist,
+            import_legacy_state,
+            get_history,
+            get_favorites,
+            toggle_favorite,
+            set_favorite,
+            set_rating,
+            get_rating_config,
+            set_rating_config,
+            get_smart_playlists,
+            create_smart_playlist,
+            update_smart_playlist,
+            delete_smart_playlist,
+            get_smart_playlist_tracks,
+            preview_smart_playlist,
             get_song_cover_thumbnail, 
             get_song_cover, 
+            get_song_cover_sized,
+            get_cover_palette,
+            get_cache_stats,
+            get_waveform,
+            clear_cover_cache,
+            set_cache_limit,
             get_song_lyrics, 
+            get_parsed_lyrics,
+            get_lyrics_candidates,
+            get_lyrics_config,
+            set_lyrics_config,
+            get_lyrics_encoding,
+            set_lyrics_encoding,
             batch_move_music_files, 
             move_music_file, 
             show_in_folder, 
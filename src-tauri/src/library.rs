@@ -0,0 +1,163 @@
+use crate::database::{escape_like, DbState};
+use crate::music::{song_columns_with, Song};
+use crate::search::song_id_filter;
+use rusqlite::types::Value;
+use rusqlite::Connection;
+use serde::Deserialize;
+use tauri::State;
+
+// --- 曲库列表：只读数据库，分页 + 排序，不触碰文件系统 ---
+// 扫描 (start_scan / scan_music_folder) 负责入库，列表界面只通过这里取数据
+
+const DEFAULT_PAGE_SIZE: u32 = 200;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SongFilter {
+    // 全文检索，与 search_library 的匹配规则相同
+    pub query: Option<String>,
+    // 只列出该文件夹 (含子文件夹) 下的歌曲
+    pub folder: Option<String>,
+    pub artist_id: Option<i64>,
+    pub album_id: Option<i64>,
+    pub genre_id: Option<i64>,
+    pub format: Option<String>,
+    pub year: Option<u32>,
+    pub favorites_only: bool,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SongSort {
+    // title / artist / album / album_artist / track / duration / year / bitrate / sample_rate /
+    // bit_depth / format / path / play_count / last_played / rating
+    pub field: String,
+    pub desc: bool,
+}
+
+fn build_filter(filter: &SongFilter) -> (String, Vec<Value>) {
+    let mut conditions: Vec<String> = Vec::new();
+    let mut params: Vec<Value> = Vec::new();
+
+    if let Some((condition, query_params)) = filter.query.as_deref().and_then(|q| song_id_filter("s", q)) {
+        conditions.push(condition);
+        params.extend(query_params);
+    }
+    if let Some(folder) = filter.folder.as_deref().map(|f| f.trim_end_matches(['/', '\\'])).filter(|f| !f.is_empty()) {
+        // Windows 与 Unix 分隔符都接受
+        conditions.push("(s.path LIKE ? ESCAPE '\\' OR s.path LIKE ? ESCAPE '\\')".to_string());
+        let prefix = escape_like(folder);
+        params.push(Value::Text(format!("{}/%", prefix)));
+        params.push(Value::Text(format!("{}\\\\%", prefix)));
+    }
+    if let Some(artist_id) = filter.artist_id {
+        conditions.push("s.id IN (SELECT song_id FROM song_artists WHERE artist_id = ?)".to_string());
+        params.push(Value::Integer(artist_id));
+    }
+    if let Some(album_id) = filter.album_id {
+        conditions.push("s.album_id = ?".to_string());
+        params.push(Value::Integer(album_id));
+    }
+    if let Some(genre_id) = filter.genre_id {
+        conditions.push("s.id IN (SELECT song_id FROM song_genres WHERE genre_id = ?)".to_string());
+        params.push(Value::Integer(genre_id));
+    }
+    if let Some(format) = filter.format.as_deref().filter(|f| !f.is_empty()) {
+        conditions.push("s.format = ? COLLATE NOCASE".to_string());
+        params.push(Value::Text(format.to_string()));
+    }
+    if let Some(year) = filter.year {
+        conditions.push("s.year = ?".to_string());
+        params.push(Value::Integer(year as i64));
+    }
+    if filter.favorites_only {
+        conditions.push("s.id IN (SELECT song_id FROM favorites)".to_string());
+    }
+
+    let where_clause = if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.join(" AND "))
+    };
+    (where_clause, params)
+}
+
+// 专辑内顺序作为次级排序，最后按路径保证分页稳定
+fn build_order(sort: &SongSort) -> String {
+    const ALBUM_ORDER: &str = "COALESCE(s.disc_number, 1), COALESCE(s.track_number, 2147483647)";
+    let direction = if sort.desc { "DESC" } else { "ASC" };
+    let primary = match sort.field.as_str() {
+        "artist" => format!("s.artist_sort {d}, s.album_sort, {}", ALBUM_ORDER, d = direction),
+        "album" => format!("s.album_sort {d}, s.album_artist_sort, {}", ALBUM_ORDER, d = direction),
+        "album_artist" => format!("s.album_artist_sort {d}, s.album_sort, {}", ALBUM_ORDER, d = direction),
+        "track" => format!("COALESCE(s.disc_number, 1) {d}, COALESCE(s.track_number, 2147483647) {d}", d = direction),
+        "duration" | "year" | "bitrate" | "sample_rate" | "bit_depth" | "play_count" | "last_played" | "rating" => {
+            format!("s.{f} IS NULL, s.{f} {d}, s.title_sort", f = sort.field, d = direction)
+        }
+        "format" => format!("s.format COLLATE NOCASE {}, s.title_sort", direction),
+        "path" => format!("s.path {}", direction),
+        _ => format!("s.title_sort {}", direction),
+    };
+    format!("{}, s.path", primary)
+}
+
+pub fn query_songs(
+    conn: &Connection,
+    filter: &SongFilter,
+    sort: &SongSort,
+    offset: u32,
+    limit: u32,
+) -> rusqlite::Result<Vec<Song>> {
+    let (where_clause, mut params) = build_filter(filter);
+    let sql = format!(
+        "SELECT {} FROM songs s {} ORDER BY {} LIMIT ? OFFSET ?",
+        song_columns_with("s"),
+        where_clause,
+        build_order(sort)
+    );
+    params.push(Value::Integer(limit as i64));
+    params.push(Value::Integer(offset as i64));
+
+    let mut stmt = conn.prepare(&sql)?;
+    let songs = stmt
+        .query_map(rusqlite::params_from_iter(params.iter()), Song::from_row)?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(songs)
+}
+
+pub fn count(conn: &Connection, filter: &SongFilter) -> rusqlite::Result<u32> {
+    let (where_clause, params) = build_filter(filter);
+    conn.query_row(
+        &format!("SELECT COUNT(*) FROM songs s {}", where_clause),
+        rusqlite::params_from_iter(params.iter()),
+        |row| row.get(0),
+    )
+}
+
+// --- Commands ---
+
+#[tauri::command]
+pub async fn list_songs(
+    filter: Option<SongFilter>,
+    sort: Option<SongSort>,
+    offset: Option<u32>,
+    limit: Option<u32>,
+    db_state: State<'_, DbState>,
+) -> Result<Vec<Song>, String> {
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    query_songs(
+        &conn,
+        &filter.unwrap_or_default(),
+        &sort.unwrap_or_default(),
+        offset.unwrap_or(0),
+        limit.unwrap_or(DEFAULT_PAGE_SIZE),
+    )
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn count_songs(filter: Option<SongFilter>, db_state: State<'_, DbState>) -> Result<u32, String> {
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    count(&conn, &filter.unwrap_or_default()).map_err(|e| e.to_string())
+}
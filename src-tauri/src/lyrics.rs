@@ -0,0 +1,1568 @@
+use crate::cache::{self, CacheKind};
+use crate::database::DbState;
+use crate::metadata::UNKNOWN_ARTIST;
+use crate::settings::{load_setting, save_setting};
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use lofty::prelude::*;
+use lofty::probe::Probe;
+use lofty::tag::{ItemKey, Tag, TagType};
+use regex::Regex;
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use tauri::State;
+
+// --- 歌词解析：LRC / 增强 LRC -> 结构化的行，时间单位为毫秒 ---
+// 支持一行多个时间标签 [00:12.00][01:30.00]、不带小数的 [mm:ss]、[offset:] 与 ti/ar/al/by 等标签，
+// 同一时间的多行按 原文 / 翻译 / 罗马音 归为一组，<mm:ss.xx> 逐字时间用于卡拉 OK 高亮
+
+// 时间相差不超过该值的行视为同一组
+const GROUP_TOLERANCE_MS: u64 = 50;
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct LyricWord {
+    pub start: u64,
+    // 下一个字的开始时间；行末的字取下一行的时间，最后一行没有
+    pub end: Option<u64>,
+    pub text: String,
+}
+
+// 和声 / 背景人声，与主唱同时出现 (TTML 的 ttm:role="x-bg")
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct BackgroundVocals {
+    pub text: String,
+    pub words: Vec<LyricWord>,
+}
+
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct LyricLine {
+    // 未同步的歌词为 0
+    pub time: u64,
+    pub text: String,
+    pub translation: Option<String>,
+    pub romaji: Option<String>,
+    // 没有逐字时间时为空
+    pub words: Vec<LyricWord>,
+    pub background: Option<BackgroundVocals>,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct ParsedLyrics {
+    // false: 没有时间轴的纯文本歌词
+    pub synced: bool,
+    // [offset:] 的值 (毫秒)，已计入各行时间；正值表示歌词提前
+    pub offset: i64,
+    // ti / ar / al / by 等标签，键为小写
+    pub tags: BTreeMap<String, String>,
+    pub lines: Vec<LyricLine>,
+    pub source: LyricsSource,
+    // ISO 639-2 语言代码，来源未标明时为 None
+    pub language: Option<String>,
+    pub format: LyricsFormat,
+    // 选中的那份歌词的原文 (文件内容 / 标签字段)
+    pub content: String,
+}
+
+struct Entry {
+    time: u64,
+    text: String,
+    words: Vec<LyricWord>,
+}
+
+// mm:ss、mm:ss.x、mm:ss.xx、mm:ss.xxx，也接受 mm:ss:xx
+fn parse_timestamp(s: &str) -> Option<u64> {
+    let (min, rest) = s.split_once(':')?;
+    let (sec, frac) = match rest.find(['.', ':']) {
+        Some(i) => (&rest[..i], Some(&rest[i + 1..])),
+        None => (rest, None),
+    };
+    let is_num = |v: &str| !v.is_empty() && v.bytes().all(|b| b.is_ascii_digit());
+    if !is_num(min.trim()) || !is_num(sec) {
+        return None;
+    }
+    let min: u64 = min.trim().parse().ok()?;
+    let sec: u64 = sec.parse().ok()?;
+    let ms = match frac {
+        Some(f) if is_num(f) => {
+            let digits = &f[..f.len().min(3)];
+            digits.parse::<u64>().ok()? * 10u64.pow(3 - digits.len() as u32)
+        }
+        Some(_) => return None,
+        None => 0,
+    };
+    Some(min * 60_000 + sec * 1000 + ms)
+}
+
+fn apply_offset(time: u64, offset: i64) -> u64 {
+    (time as i64 - offset).max(0) as u64
+}
+
+// <mm:ss.xx> 逐字时间：每个时间标签开始一个字，结尾单独的标签是最后一个字的结束时间
+fn parse_words(text: &str, line_time: u64) -> (String, Vec<LyricWord>) {
+    static WORD_RE: OnceLock<Regex> = OnceLock::new();
+    let re = WORD_RE.get_or_init(|| Regex::new(r"<(\d+:\d+(?:[.:]\d+)?)>").unwrap());
+    if !re.is_match(text) {
+        return (text.trim().to_string(), Vec::new());
+    }
+
+    let mut words = Vec::new();
+    let mut start = line_time;
+    let mut last = 0;
+    for caps in re.captures_iter(text) {
+        let m = caps.get(0).unwrap();
+        let Some(time) = parse_timestamp(&caps[1]) else { continue };
+        let segment = &text[last..m.start()];
+        if !segment.is_empty() {
+            words.push(LyricWord { start, end: Some(time), text: segment.to_string() });
+        }
+        start = time;
+        last = m.end();
+    }
+    if last < text.len() {
+        words.push(LyricWord { start, end: None, text: text[last..].to_string() });
+    }
+
+    // 行首的空白不算字，字与字之间的空格保留在前一个字上
+    if let Some(first) = words.first_mut() {
+        first.text = first.text.trim_start().to_string();
+    }
+    if let Some(last) = words.last_mut() {
+        last.text = last.text.trim_end().to_string();
+    }
+    words.retain(|w| !w.text.trim().is_empty());
+    let plain = words.iter().map(|w| w.text.as_str()).collect::<String>();
+    (plain, words)
+}
+
+fn has_kana_or_hangul(s: &str) -> bool {
+    s.chars().any(|c| matches!(c, '\u{3040}'..='\u{30ff}' | '\u{ac00}'..='\u{d7af}' | '\u{1100}'..='\u{11ff}'))
+}
+
+// 只含拉丁字母的行 (罗马音)
+fn is_latin(s: &str) -> bool {
+    let mut letters = s.chars().filter(|c| c.is_alphabetic()).peekable();
+    letters.peek().is_some() && letters.all(|c| c < '\u{0250}')
+}
+
+// 同一时间的多行：第一行是原文，日文 / 韩文原文后的拉丁字母行视为罗马音，其余为翻译
+fn group_to_line(mut group: Vec<Entry>) -> LyricLine {
+    let main = group.remove(0);
+    let mut translation: Option<String> = None;
+    let mut romaji: Option<String> = None;
+    let main_is_cjk = has_kana_or_hangul(&main.text);
+    for entry in group {
+        let slot = if main_is_cjk && is_latin(&entry.text) && romaji.is_none() {
+            &mut romaji
+        } else if translation.is_none() {
+            &mut translation
+        } else {
+            &mut romaji
+        };
+        if slot.is_none() {
+            *slot = Some(entry.text);
+        }
+    }
+    LyricLine { time: main.time, text: main.text, translation, romaji, words: main.words, background: None }
+}
+
+// 按时间排序后把相近的行归组；行末的字没有结束时间时，结束于下一行开始
+fn build_lines(mut entries: Vec<Entry>) -> Vec<LyricLine> {
+    // 稳定排序，同一时间的行保持文件中的先后顺序
+    entries.sort_by_key(|e| e.time);
+
+    let mut groups: Vec<Vec<Entry>> = Vec::new();
+    for entry in entries {
+        match groups.last_mut() {
+            Some(group) if entry.time - group[0].time <= GROUP_TOLERANCE_MS => group.push(entry),
+            _ => groups.push(vec![entry]),
+        }
+    }
+    let mut lines: Vec<LyricLine> = groups.into_iter().map(group_to_line).collect();
+    close_last_words(&mut lines);
+    lines
+}
+
+fn close_last_words(lines: &mut [LyricLine]) {
+    for i in 0..lines.len().saturating_sub(1) {
+        let next = lines[i + 1].time;
+        if let Some(word) = lines[i].words.last_mut() {
+            word.end.get_or_insert(next);
+        }
+    }
+}
+
+pub fn parse_lrc(content: &str) -> ParsedLyrics {
+    let mut result = ParsedLyrics::default();
+    // (时间, 文本) 先收集，offset 可能出现在任意位置，最后统一计入
+    let mut raw: Vec<(u64, String)> = Vec::new();
+    let mut plain: Vec<String> = Vec::new();
+
+    for line in content.lines() {
+        let line = line.trim_start_matches('\u{feff}').trim();
+        let mut rest = line;
+        let mut times = Vec::new();
+        let mut is_tag = false;
+        while let Some(inner_end) = rest.strip_prefix('[').and_then(|r| r.find(']')) {
+            let inner = &rest[1..inner_end + 1];
+            if let Some(time) = parse_timestamp(inner) {
+                times.push(time);
+                rest = rest[inner_end + 2..].trim_start();
+                continue;
+            }
+            // [key:value] 标签行，只在行首且没有时间标签时识别
+            if times.is_empty() {
+                if let Some((key, value)) = inner.split_once(':') {
+                    let key = key.trim().to_lowercase();
+                    if !key.is_empty() && key.chars().all(|c| c.is_ascii_alphabetic() || c == '#') {
+                        let value = value.trim().to_string();
+                        if key == "offset" {
+                            result.offset = value.parse().unwrap_or(0);
+                        }
+                        result.tags.insert(key, value);
+                        is_tag = true;
+                    }
+                }
+            }
+            break;
+        }
+        if is_tag {
+            continue;
+        }
+        if times.is_empty() {
+            if !rest.is_empty() {
+                plain.push(rest.to_string());
+            }
+            continue;
+        }
+        for time in times {
+            raw.push((time, rest.to_string()));
+        }
+    }
+
+    if raw.is_empty() {
+        result.lines = plain
+            .into_iter()
+            .map(|text| LyricLine { text, ..Default::default() })
+            .collect();
+        return result;
+    }
+    result.synced = true;
+
+    let offset = result.offset;
+    let entries: Vec<Entry> = raw
+        .into_iter()
+        .filter_map(|(time, text)| {
+            let (text, mut words) = parse_words(&text, time);
+            for word in &mut words {
+                word.start = apply_offset(word.start, offset);
+                word.end = word.end.map(|end| apply_offset(end, offset));
+            }
+            (!text.is_empty()).then_some(Entry { time: apply_offset(time, offset), text, words })
+        })
+        .collect();
+    result.lines = build_lines(entries);
+    result
+}
+
+// --- 其他歌词格式：TTML / SRT / VTT / TXT ---
+// 都转换成与 LRC 相同的结构。TTML 按 Apple Music 的写法：<p> 为一行，带 begin 的 <span> 为一个字，
+// ttm:role="x-bg" 为背景人声，x-translation / x-roman 与 <head> 中 iTunesMetadata 的 translation /
+// transliteration 为翻译与罗马音。字幕的一个时间段内有多行时，与 LRC 同一时间的多行一样归组
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LyricsFormat {
+    #[default]
+    Lrc,
+    Ttml,
+    Srt,
+    Vtt,
+    // 纯文本，有 LRC 时间标签时按 LRC 解析
+    Txt,
+}
+
+// 同名多种格式时按此顺序取，信息多的在前
+pub const LYRICS_EXTENSIONS: [&str; 5] = ["ttml", "lrc", "vtt", "srt", "txt"];
+
+impl LyricsFormat {
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext.to_lowercase().as_str() {
+            "lrc" => Some(Self::Lrc),
+            "ttml" => Some(Self::Ttml),
+            "srt" => Some(Self::Srt),
+            "vtt" => Some(Self::Vtt),
+            "txt" => Some(Self::Txt),
+            _ => None,
+        }
+    }
+
+    // 内嵌歌词没有扩展名，按内容判断
+    pub fn detect(content: &str) -> Self {
+        let head = content.trim_start_matches('\u{feff}').trim_start();
+        if head.starts_with("<?xml") || head.starts_with("<tt") {
+            Self::Ttml
+        } else if head.starts_with("WEBVTT") {
+            Self::Vtt
+        } else {
+            Self::Lrc
+        }
+    }
+}
+
+pub fn parse_lyrics(content: &str, format: LyricsFormat) -> ParsedLyrics {
+    let mut parsed = match format {
+        LyricsFormat::Lrc | LyricsFormat::Txt => parse_lrc(content),
+        LyricsFormat::Ttml => parse_ttml(content),
+        LyricsFormat::Srt | LyricsFormat::Vtt => parse_subtitles(content),
+    };
+    parsed.format = format;
+    parsed
+}
+
+// 字幕与 TTML 的时间：hh:mm:ss.mmm、mm:ss.mmm、hh:mm:ss,mmm (SRT)，或 12.5s / 1500ms / 2m 这样的偏移量
+fn parse_clock(s: &str) -> Option<u64> {
+    let s = s.trim().replace(',', ".");
+    let seconds = |v: &str| v.trim().parse::<f64>().ok().filter(|v| v.is_finite() && *v >= 0.0);
+    for (unit, scale) in [("ms", 1.0), ("h", 3_600_000.0), ("m", 60_000.0), ("s", 1000.0)] {
+        if let Some(value) = s.strip_suffix(unit) {
+            return seconds(value).map(|v| (v * scale).round() as u64);
+        }
+    }
+    let parts: Vec<&str> = s.split(':').collect();
+    if parts.len() > 3 {
+        return None;
+    }
+    let (sec, rest) = parts.split_last()?;
+    let mut ms = (seconds(sec)? * 1000.0).round() as u64;
+    for (part, scale) in rest.iter().rev().zip([60_000, 3_600_000]) {
+        ms += part.trim().parse::<u64>().ok()? * scale;
+    }
+    Some(ms)
+}
+
+// 连续的空白 (包括缩进与换行) 合并成一个空格
+fn collapse_whitespace(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut space = false;
+    for c in s.chars() {
+        if c.is_whitespace() {
+            space = true;
+            continue;
+        }
+        if space {
+            out.push(' ');
+            space = false;
+        }
+        out.push(c);
+    }
+    if space {
+        out.push(' ');
+    }
+    out
+}
+
+// 按本地名取属性，忽略命名空间前缀 (ttm:role、itunes:key、xml:lang)
+fn attr<'a>(node: roxmltree::Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attributes().find(|a| a.name() == name).map(|a| a.value())
+}
+
+fn node_text(node: roxmltree::Node) -> String {
+    let text: String = node.descendants().filter(|n| n.is_text()).filter_map(|n| n.text()).collect();
+    collapse_whitespace(&text)
+}
+
+#[derive(Default)]
+struct Vocal {
+    text: String,
+    words: Vec<LyricWord>,
+}
+
+impl Vocal {
+    fn push_text(&mut self, text: &str) {
+        let text = collapse_whitespace(text);
+        self.text.push_str(&text);
+        // 字间空格留在前一个字上
+        if let Some(word) = self.words.last_mut() {
+            word.text.push_str(&text);
+        }
+    }
+
+    fn push_word(&mut self, start: u64, end: Option<u64>, text: String) {
+        self.text.push_str(&text);
+        // 第一个字之前没有时间的文字并入第一个字
+        let text = if self.words.is_empty() { self.text.clone() } else { text };
+        self.words.push(LyricWord { start, end, text });
+    }
+
+    fn finish(mut self) -> (String, Vec<LyricWord>) {
+        if let Some(first) = self.words.first_mut() {
+            first.text = first.text.trim_start().to_string();
+        }
+        if let Some(last) = self.words.last_mut() {
+            last.text = last.text.trim_end().to_string();
+        }
+        self.words.retain(|w| !w.text.trim().is_empty());
+        (self.text.split_whitespace().collect::<Vec<_>>().join(" "), self.words)
+    }
+}
+
+#[derive(Default)]
+struct TtmlExtras {
+    translation: Option<String>,
+    romaji: Option<String>,
+    background: Option<Vocal>,
+}
+
+fn ttml_span_times(node: roxmltree::Node) -> Option<(u64, Option<u64>)> {
+    let begin = attr(node, "begin").and_then(parse_clock)?;
+    let end = attr(node, "end")
+        .and_then(parse_clock)
+        .or_else(|| attr(node, "dur").and_then(parse_clock).map(|dur| begin + dur));
+    Some((begin, end))
+}
+
+fn collect_vocal(node: roxmltree::Node, vocal: &mut Vocal, extras: &mut TtmlExtras) {
+    for child in node.children() {
+        if child.is_text() {
+            vocal.push_text(child.text().unwrap_or_default());
+            continue;
+        }
+        if !child.is_element() {
+            continue;
+        }
+        match attr(child, "role") {
+            Some("x-bg") => {
+                let mut background = extras.background.take().unwrap_or_default();
+                collect_vocal(child, &mut background, extras);
+                extras.background = Some(background);
+            }
+            Some("x-translation") => extras.translation = non_empty(node_text(child)),
+            Some("x-roman") => extras.romaji = non_empty(node_text(child)),
+            _ => match ttml_span_times(child) {
+                Some((begin, end)) => vocal.push_word(begin, end, node_text(child)),
+                None => collect_vocal(child, vocal, extras),
+            },
+        }
+    }
+}
+
+// <head> 中按 <p itunes:key> 对应的翻译 / 罗马音
+fn ttml_metadata(doc: &roxmltree::Document, container: &str) -> BTreeMap<String, String> {
+    let mut map = BTreeMap::new();
+    for node in doc.descendants().filter(|n| n.tag_name().name() == container) {
+        for text in node.descendants().filter(|n| n.tag_name().name() == "text") {
+            if let (Some(key), Some(value)) = (attr(text, "for"), non_empty(node_text(text))) {
+                map.entry(key.to_string()).or_insert(value);
+            }
+        }
+    }
+    map
+}
+
+pub fn parse_ttml(content: &str) -> ParsedLyrics {
+    let mut result = ParsedLyrics::default();
+    let Ok(doc) = roxmltree::Document::parse(content.trim_start_matches('\u{feff}')) else { return result };
+    let root = doc.root_element();
+    // xml:lang 为 BCP 47 (ja、zh-Hans)，取主语言转成 ISO 639-2
+    result.language = attr(root, "lang").and_then(|l| l.split('-').next()).map(normalize_language);
+    let translations = ttml_metadata(&doc, "translation");
+    let transliterations = ttml_metadata(&doc, "transliteration");
+
+    for p in doc.descendants().filter(|n| n.tag_name().name() == "p") {
+        let mut vocal = Vocal::default();
+        let mut extras = TtmlExtras::default();
+        collect_vocal(p, &mut vocal, &mut extras);
+        let (text, words) = vocal.finish();
+        if text.is_empty() {
+            continue;
+        }
+        let time = ttml_span_times(p).map(|(begin, _)| begin).or_else(|| words.first().map(|w| w.start));
+        result.synced |= time.is_some();
+        let key = attr(p, "key");
+        let lookup = |map: &BTreeMap<String, String>| key.and_then(|k| map.get(k).cloned());
+        result.lines.push(LyricLine {
+            time: time.unwrap_or(0),
+            text,
+            translation: extras.translation.or_else(|| lookup(&translations)),
+            romaji: extras.romaji.or_else(|| lookup(&transliterations)),
+            words,
+            background: extras.background.map(Vocal::finish).and_then(|(text, words)| {
+                (!text.is_empty()).then_some(BackgroundVocals { text, words })
+            }),
+        });
+    }
+    if result.synced {
+        result.lines.sort_by_key(|l| l.time);
+        close_last_words(&mut result.lines);
+    }
+    result
+}
+
+// SRT / VTT：空行分隔的字幕块，含 "-->" 的行是时间，其后为文字；序号、WEBVTT 头与 NOTE / STYLE 块忽略
+pub fn parse_subtitles(content: &str) -> ParsedLyrics {
+    static TAG_RE: OnceLock<Regex> = OnceLock::new();
+    static TIME_RE: OnceLock<Regex> = OnceLock::new();
+    // <i> <b> <font ...> <c.red> <v 歌手> 等样式标签与 SRT 的 {\an8}
+    let tag_re = TAG_RE.get_or_init(|| Regex::new(r"</?[A-Za-z][^>]*>|\{\\[^}]*\}").unwrap());
+    // VTT 的逐字时间 <00:00:01.500>，转成 LRC 的 <mm:ss.xxx> 后交给 parse_words
+    let time_re = TIME_RE.get_or_init(|| Regex::new(r"<((?:\d+:)?\d+:\d+[.,]\d+)>").unwrap());
+
+    let mut result = ParsedLyrics::default();
+    let content = content.trim_start_matches('\u{feff}').replace("\r\n", "\n").replace('\r', "\n");
+    let mut entries = Vec::new();
+    for block in content.split("\n\n") {
+        let mut lines = block.lines().skip_while(|l| !l.contains("-->"));
+        let Some(timing) = lines.next() else { continue };
+        let (start, end) = timing.split_once("-->").unwrap_or_default();
+        let Some(start) = parse_clock(start) else { continue };
+        // VTT 的结束时间后面可以跟 position / align 等设置
+        let end = end.split_whitespace().next().and_then(parse_clock);
+
+        let mut cue: Vec<Entry> = Vec::new();
+        for line in lines {
+            let line = tag_re.replace_all(line, "");
+            let line = time_re.replace_all(&line, |caps: &regex::Captures| {
+                parse_clock(&caps[1]).map(|ms| format!("<{}>", lrc_time(ms))).unwrap_or_default()
+            });
+            let (text, words) = parse_words(&line, start);
+            if !text.is_empty() {
+                cue.push(Entry { time: start, text, words });
+            }
+        }
+        // 最后一个字结束于字幕块结束
+        for entry in &mut cue {
+            if let Some(word) = entry.words.last_mut() {
+                word.end = word.end.or(end);
+            }
+        }
+        entries.extend(cue);
+    }
+    result.synced = !entries.is_empty();
+    result.lines = build_lines(entries);
+    result
+}
+
+// --- 歌词文件编码 ---
+// 顺序：BOM -> 用户指定 -> 无 BOM 的 UTF-16 -> 合法的 UTF-8 -> chardetng 猜测 (GBK / Big5 / Shift-JIS 等)
+
+// 没有 BOM 的 UTF-16：LRC 大部分是 ASCII 的时间标签，高位字节几乎都是 0
+fn sniff_utf16(bytes: &[u8]) -> Option<&'static Encoding> {
+    if bytes.len() < 4 || !bytes.len().is_multiple_of(2) {
+        return None;
+    }
+    let pairs = bytes.len() / 2;
+    let zeros_at = |parity: usize| bytes.iter().skip(parity).step_by(2).filter(|b| **b == 0).count();
+    let (even, odd) = (zeros_at(0), zeros_at(1));
+    if odd * 10 >= pairs * 3 && even * 10 < pairs {
+        Some(UTF_16LE)
+    } else if even * 10 >= pairs * 3 && odd * 10 < pairs {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+pub fn detect_encoding(bytes: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return encoding;
+    }
+    if let Some(encoding) = sniff_utf16(bytes) {
+        return encoding;
+    }
+    if std::str::from_utf8(bytes).is_ok() {
+        return UTF_8;
+    }
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    detector.guess(None, true)
+}
+
+// forced 为用户指定的编码；文件带 BOM 时以 BOM 为准
+pub fn decode_lyrics(bytes: &[u8], forced: Option<&'static Encoding>) -> String {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(bytes) {
+        return encoding.decode_without_bom_handling(&bytes[bom_len..]).0.into_owned();
+    }
+    let encoding = forced.unwrap_or_else(|| detect_encoding(bytes));
+    encoding.decode_without_bom_handling(bytes).0.into_owned()
+}
+
+pub fn read_lyrics_file(path: &Path, forced: Option<&'static Encoding>) -> Option<String> {
+    fs::read(path).ok().map(|bytes| decode_lyrics(&bytes, forced))
+}
+
+fn load_override(conn: &Connection, path: &Path) -> Option<&'static Encoding> {
+    conn.query_row(
+        "SELECT encoding FROM lyrics_encodings WHERE path = ?1",
+        [&*path.to_string_lossy()],
+        |row| row.get::<_, String>(0),
+    )
+    .optional()
+    .ok()
+    .flatten()
+    .and_then(|label| Encoding::for_label(label.as_bytes()))
+}
+
+pub fn encoding_override(db: &Mutex<Connection>, path: &Path) -> Option<&'static Encoding> {
+    db.lock().ok().and_then(|conn| load_override(&conn, path))
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct LyricsEncodingInfo {
+    // 歌词文件路径
+    pub file: String,
+    // 自动检测的结果，如 "GBK"、"UTF-8"
+    pub detected: String,
+    // 用户指定的编码，没有时为 None
+    pub forced: Option<String>,
+}
+
+// --- 内嵌歌词 ---
+// ID3v2 的 USLT / SYLT 直接从文件开头的标签解析：lofty 的通用 Tag 会合并多个 USLT 并丢掉语言，也不读 SYLT。
+// Vorbis 的 LYRICS / UNSYNCEDLYRICS、MP4 的 ©lyr 与 APE 等其他标签通过 lofty 读取
+
+const LYRICS_CONFIG_KEY: &str = "lyrics";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LyricsSource {
+    // ID3v2 同步歌词帧
+    Sylt,
+    // ID3v2 非同步歌词帧
+    Uslt,
+    // Vorbis 注释 (FLAC / Ogg / Opus) 的 LYRICS / UNSYNCEDLYRICS
+    Vorbis,
+    // MP4 的 ©lyr
+    Mp4,
+    // APE 等其他标签的歌词字段
+    Tag,
+    // 内容像 LRC 的注释
+    Comment,
+    // 歌曲旁或歌词目录中的歌词文件
+    #[default]
+    File,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LyricsCandidate {
+    pub source: LyricsSource,
+    // ISO 639-2 语言代码 (如 eng / chi / jpn)，未标明时为 None
+    pub language: Option<String>,
+    pub description: Option<String>,
+    // 歌词原文，SYLT 会转换成 (增强) LRC
+    pub content: String,
+    pub format: LyricsFormat,
+    // 来自歌词文件时为文件路径
+    pub file: Option<String>,
+}
+
+impl LyricsCandidate {
+    fn embedded(source: LyricsSource, language: Option<String>, description: Option<String>, content: String) -> Self {
+        let format = LyricsFormat::detect(&content);
+        LyricsCandidate { source, language, description, content, format, file: None }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct LyricsConfig {
+    // 有多种语言的歌词时的优先顺序，如 ["chi", "jpn"]；都不匹配时取第一份
+    pub preferred_languages: Vec<String>,
+    // 歌曲所在文件夹之外，还在这些目录中按文件名查找歌词
+    pub lyrics_dirs: Vec<String>,
+}
+
+fn syncsafe(bytes: &[u8]) -> usize {
+    bytes.iter().fold(0, |acc, b| (acc << 7) | (*b as usize & 0x7f))
+}
+
+fn be_size(bytes: &[u8]) -> usize {
+    bytes.iter().fold(0, |acc, b| (acc << 8) | *b as usize)
+}
+
+// 反同步：去掉 0xFF 后面插入的 0x00
+fn remove_unsync(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut prev = 0u8;
+    for &b in data {
+        if !(prev == 0xff && b == 0) {
+            out.push(b);
+        }
+        prev = b;
+    }
+    out
+}
+
+// 文本编码：0 = ISO-8859-1 (不少中文 MP3 实际存的是 GBK，按内容检测)，1 = 带 BOM 的 UTF-16，2 = UTF-16BE，3 = UTF-8
+fn decode_id3_text(encoding: u8, bytes: &[u8]) -> String {
+    let text = match encoding {
+        0 if bytes.is_ascii() => bytes.iter().map(|&b| b as char).collect(),
+        0 => decode_lyrics(bytes, None),
+        1 => decode_lyrics(bytes, Some(UTF_16LE)),
+        2 => UTF_16BE.decode_without_bom_handling(bytes).0.into_owned(),
+        _ => decode_lyrics(bytes, Some(UTF_8)),
+    };
+    text.trim_end_matches('\0').to_string()
+}
+
+// 按文本编码找结束符，返回 (字符串, 剩余部分)
+fn split_terminated(encoding: u8, data: &[u8]) -> (&[u8], &[u8]) {
+    if matches!(encoding, 1 | 2) {
+        let mut i = 0;
+        while i + 1 < data.len() {
+            if data[i] == 0 && data[i + 1] == 0 {
+                return (&data[..i], &data[i + 2..]);
+            }
+            i += 2;
+        }
+    } else if let Some(i) = data.iter().position(|b| *b == 0) {
+        return (&data[..i], &data[i + 1..]);
+    }
+    (data, &[])
+}
+
+fn frame_language(code: &[u8]) -> Option<String> {
+    let code = String::from_utf8_lossy(code).trim_matches('\0').trim().to_lowercase();
+    let valid = code.len() == 3 && code.chars().all(|c| c.is_ascii_alphabetic());
+    (valid && code != "xxx" && code != "und").then_some(code)
+}
+
+fn non_empty(text: String) -> Option<String> {
+    let text = text.trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+fn parse_uslt(data: &[u8]) -> Option<LyricsCandidate> {
+    if data.len() < 4 {
+        return None;
+    }
+    let encoding = data[0];
+    let (description, text) = split_terminated(encoding, &data[4..]);
+    Some(LyricsCandidate::embedded(
+        LyricsSource::Uslt,
+        frame_language(&data[1..4]),
+        non_empty(decode_id3_text(encoding, description)),
+        non_empty(decode_id3_text(encoding, text))?,
+    ))
+}
+
+fn lrc_time(ms: u64) -> String {
+    format!("{:02}:{:02}.{:03}", ms / 60_000, ms / 1000 % 60, ms % 1000)
+}
+
+// SYLT 可以一行一条，也可以一个字一条 (以换行开头的条目开始新的一行)，后者转为增强 LRC 的逐字时间
+fn sylt_to_lrc(entries: &[(String, u64)]) -> String {
+    let per_word = entries.iter().skip(1).any(|(text, _)| text.starts_with(['\n', '\r']));
+    let mut out = String::new();
+    if !per_word {
+        for (text, time) in entries {
+            for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+                out.push_str(&format!("[{}]{}\n", lrc_time(*time), line));
+            }
+        }
+        return out;
+    }
+
+    let mut line = String::new();
+    for (text, time) in entries {
+        if line.is_empty() || text.starts_with(['\n', '\r']) {
+            if !line.is_empty() {
+                out.push_str(&line);
+                out.push('\n');
+            }
+            line = format!("[{}]", lrc_time(*time));
+        }
+        line.push_str(&format!("<{}>{}", lrc_time(*time), text.trim_start_matches(['\n', '\r'])));
+    }
+    if !line.is_empty() {
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out
+}
+
+fn parse_sylt(data: &[u8]) -> Option<LyricsCandidate> {
+    if data.len() < 6 {
+        return None;
+    }
+    let (encoding, time_format, content_type) = (data[0], data[4], data[5]);
+    // 时间单位 2 = 毫秒；1 = MPEG 帧数，换算需要帧长，不支持。内容类型只取 其他 / 歌词 / 文字转写
+    if time_format != 2 || content_type > 2 {
+        return None;
+    }
+    let (description, mut rest) = split_terminated(encoding, &data[6..]);
+    let mut entries = Vec::new();
+    while !rest.is_empty() {
+        let (text, after) = split_terminated(encoding, rest);
+        if after.len() < 4 {
+            break;
+        }
+        let time = u32::from_be_bytes([after[0], after[1], after[2], after[3]]) as u64;
+        entries.push((decode_id3_text(encoding, text), time));
+        rest = &after[4..];
+    }
+    Some(LyricsCandidate::embedded(
+        LyricsSource::Sylt,
+        frame_language(&data[1..4]),
+        non_empty(decode_id3_text(encoding, description)),
+        non_empty(sylt_to_lrc(&entries))?,
+    ))
+}
+
+// 文件开头 ID3v2 (2.2 / 2.3 / 2.4) 标签中的 USLT / SYLT 帧
+fn id3v2_lyrics(path: &Path) -> Vec<LyricsCandidate> {
+    let mut found = Vec::new();
+    let Ok(mut file) = File::open(path) else { return found };
+    let mut header = [0u8; 10];
+    if file.read_exact(&mut header).is_err() || &header[0..3] != b"ID3" {
+        return found;
+    }
+    let (version, flags) = (header[3], header[5]);
+    let mut body = vec![0u8; syncsafe(&header[6..10])];
+    if file.read_exact(&mut body).is_err() {
+        return found;
+    }
+    // 2.4 的反同步按帧标记，之前的版本作用于整个标签
+    if flags & 0x80 != 0 && version < 4 {
+        body = remove_unsync(&body);
+    }
+
+    let mut pos = 0;
+    if flags & 0x40 != 0 && version >= 3 && body.len() >= 4 {
+        pos = if version == 3 { 4 + be_size(&body[0..4]) } else { syncsafe(&body[0..4]) };
+    }
+    let (id_len, header_len) = if version == 2 { (3, 6) } else { (4, 10) };
+    while pos + header_len <= body.len() && body[pos] != 0 {
+        let size = match version {
+            2 => be_size(&body[pos + 3..pos + 6]),
+            3 => be_size(&body[pos + 4..pos + 8]),
+            _ => syncsafe(&body[pos + 4..pos + 8]),
+        };
+        let format_flags = if version == 2 { 0 } else { body[pos + 9] };
+        let id = &body[pos..pos + id_len];
+        let start = pos + header_len;
+        let mut data = body[start..(start + size).min(body.len())].to_vec();
+        pos = start + size;
+        if !matches!(id, b"USLT" | b"ULT" | b"SYLT" | b"SLT") {
+            continue;
+        }
+
+        // 2.3: 压缩 0x80 / 加密 0x40 跳过，分组 0x20 多 1 字节
+        // 2.4: 压缩 0x08 / 加密 0x04 跳过，分组 0x40 多 1 字节，数据长度 0x01 多 4 字节，反同步 0x02
+        let skip = match version {
+            3 if format_flags & 0xc0 != 0 => continue,
+            3 if format_flags & 0x20 != 0 => 1,
+            4 if format_flags & 0x0c != 0 => continue,
+            4 => {
+                if format_flags & 0x02 != 0 {
+                    data = remove_unsync(&data);
+                }
+                (if format_flags & 0x40 != 0 { 1 } else { 0 }) + (if format_flags & 0x01 != 0 { 4 } else { 0 })
+            }
+            _ => 0,
+        };
+        let data = data.get(skip..).unwrap_or_default();
+        let candidate = if matches!(id, b"USLT" | b"ULT") {
+            parse_uslt(data)
+        } else {
+            parse_sylt(data)
+        };
+        found.extend(candidate);
+    }
+    found
+}
+
+// id3_lyrics: ID3v2 没能直接解析出歌词帧时，退回 lofty 合并后的歌词字段
+fn tag_lyrics(tag: &Tag, id3_lyrics: bool) -> Vec<LyricsCandidate> {
+    let source = match tag.tag_type() {
+        TagType::Id3v2 => LyricsSource::Uslt,
+        TagType::VorbisComments => LyricsSource::Vorbis,
+        TagType::Mp4Ilst => LyricsSource::Mp4,
+        _ => LyricsSource::Tag,
+    };
+    let mut found = Vec::new();
+    for item in tag.items() {
+        let Some(text) = item.value().text() else { continue };
+        let source = match item.key() {
+            ItemKey::Lyrics if tag.tag_type() != TagType::Id3v2 || id3_lyrics => source,
+            ItemKey::Unknown(key) if source == LyricsSource::Vorbis && key.eq_ignore_ascii_case("UNSYNCEDLYRICS") => source,
+            ItemKey::Comment if text.contains("[00:") => LyricsSource::Comment,
+            _ => continue,
+        };
+        if let Some(content) = non_empty(text.to_string()) {
+            found.push(LyricsCandidate::embedded(source, None, None, content));
+        }
+    }
+    found
+}
+
+pub fn embedded_lyrics_candidates(path: &Path) -> Vec<LyricsCandidate> {
+    let mut found = id3v2_lyrics(path);
+    let id3_lyrics = found.is_empty();
+    if let Ok(tagged_file) = Probe::open(path).and_then(|p| p.read()) {
+        for tag in tagged_file.tags() {
+            found.extend(tag_lyrics(tag, id3_lyrics));
+        }
+    }
+    // 同一份歌词可能同时存在于多个字段
+    let mut seen = HashSet::new();
+    found.retain(|c| seen.insert(c.content.clone()));
+    found
+}
+
+// --- 歌词文件查找 ---
+// 依次在歌曲所在文件夹与设置的歌词目录中，按 <歌曲文件名> 与 <歌手> - <标题> 两种文件名查找，
+// 同名多种格式时按 LYRICS_EXTENSIONS 的顺序
+
+// 文件名比较时忽略大小写、多余空白以及文件名中不能出现的字符
+fn name_key(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .filter(|c| !matches!(c, '\\' | '/' | ':' | '*' | '?' | '"' | '<' | '>' | '|'))
+        .collect();
+    cleaned.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+// 优先取数据库中的歌手 / 标题，没有扫描过的歌曲读标签
+fn artist_title(path: &Path, db: &Mutex<Connection>) -> Option<(String, String)> {
+    let stored = db.lock().ok().and_then(|conn| {
+        conn.query_row("SELECT artist, title FROM songs WHERE path = ?1", [&*path.to_string_lossy()], |row| {
+            Ok((row.get::<_, Option<String>>(0)?, row.get::<_, Option<String>>(1)?))
+        })
+        .optional()
+        .ok()
+        .flatten()
+    });
+    let (artist, title) = match stored {
+        Some((Some(artist), Some(title))) => (artist, title),
+        _ => {
+            let tagged_file = Probe::open(path).ok()?.read().ok()?;
+            let tag = tagged_file.primary_tag().or_else(|| tagged_file.first_tag())?;
+            (tag.artist()?.to_string(), tag.title()?.to_string())
+        }
+    };
+    let valid = !artist.trim().is_empty() && artist != UNKNOWN_ARTIST && !title.trim().is_empty();
+    valid.then_some((artist, title))
+}
+
+pub fn find_lyrics_files(song_path: &Path, artist_title: Option<(String, String)>, lyrics_dirs: &[String]) -> Vec<PathBuf> {
+    let mut names = Vec::new();
+    if let Some(stem) = song_path.file_stem() {
+        names.push(name_key(&stem.to_string_lossy()));
+    }
+    if let Some((artist, title)) = artist_title {
+        names.push(name_key(&format!("{} - {}", artist, title)));
+    }
+    names.retain(|n| !n.is_empty());
+
+    let mut dirs: Vec<PathBuf> = song_path.parent().map(Path::to_path_buf).into_iter().collect();
+    dirs.extend(lyrics_dirs.iter().map(|d| d.trim()).filter(|d| !d.is_empty()).map(PathBuf::from));
+
+    let mut found: Vec<PathBuf> = Vec::new();
+    let mut seen_dirs = HashSet::new();
+    for dir in dirs {
+        if !seen_dirs.insert(dir.clone()) {
+            continue;
+        }
+        let Ok(entries) = fs::read_dir(&dir) else { continue };
+        // (文件名键, 扩展名, 路径)
+        let files: Vec<(String, String, PathBuf)> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .filter_map(|path| {
+                let ext = path.extension()?.to_string_lossy().to_lowercase();
+                if !LYRICS_EXTENSIONS.contains(&ext.as_str()) {
+                    return None;
+                }
+                Some((name_key(&path.file_stem()?.to_string_lossy()), ext, path))
+            })
+            .collect();
+        for name in &names {
+            for ext in LYRICS_EXTENSIONS {
+                for (_, _, path) in files.iter().filter(|(key, e, _)| key == name && e == ext) {
+                    if !found.contains(path) {
+                        found.push(path.clone());
+                    }
+                }
+            }
+        }
+    }
+    found
+}
+
+fn lyrics_files(path: &Path, db: &Mutex<Connection>, config: &LyricsConfig) -> Vec<PathBuf> {
+    find_lyrics_files(path, artist_title(path, db), &config.lyrics_dirs)
+}
+
+// path 可以是歌曲，也可以直接是某个歌词文件 (来自 LyricsCandidate::file)
+fn resolve_lyrics_file(path: &Path, db: &Mutex<Connection>) -> Option<PathBuf> {
+    let is_lyrics_file = path
+        .extension()
+        .and_then(|ext| LyricsFormat::from_extension(&ext.to_string_lossy()))
+        .is_some();
+    if is_lyrics_file && path.is_file() {
+        return Some(path.to_path_buf());
+    }
+    let config = load_config(db);
+    lyrics_files(path, db, &config).into_iter().next()
+}
+
+fn load_config(db: &Mutex<Connection>) -> LyricsConfig {
+    db.lock().map(|conn| load_setting(&conn, LYRICS_CONFIG_KEY)).unwrap_or_default()
+}
+
+// 内嵌歌词在前，歌词文件在后
+// 内嵌歌词要解析整个标签 (SYLT 还要转换)，结果按文件缓存在 lyrics 缓存目录
+fn cached_embedded_candidates(path: &Path) -> Vec<LyricsCandidate> {
+    let (Some(cache), Some(key)) = (cache::manager(), cache::source_key(path)) else {
+        return embedded_lyrics_candidates(path);
+    };
+    let name = format!("{}.json", key);
+    if let Some(candidates) = cache.read(CacheKind::Lyrics, &name).and_then(|bytes| serde_json::from_slice(&bytes).ok()) {
+        return candidates;
+    }
+    let candidates = embedded_lyrics_candidates(path);
+    if let Ok(json) = serde_json::to_vec(&candidates) {
+        if let Err(e) = cache.write(CacheKind::Lyrics, &name, &json) {
+            eprintln!("歌词缓存写入失败: {}", e);
+        }
+    }
+    candidates
+}
+
+fn lyrics_candidates(path: &Path, db: &Mutex<Connection>, config: &LyricsConfig) -> Vec<LyricsCandidate> {
+    let mut candidates = cached_embedded_candidates(path);
+    for file in lyrics_files(path, db, config) {
+        let Some(content) = read_lyrics_file(&file, encoding_override(db, &file)).and_then(non_empty) else { continue };
+        let format = file
+            .extension()
+            .and_then(|ext| LyricsFormat::from_extension(&ext.to_string_lossy()))
+            .unwrap_or_default();
+        candidates.push(LyricsCandidate {
+            source: LyricsSource::File,
+            language: None,
+            description: None,
+            content,
+            format,
+            file: Some(file.to_string_lossy().into_owned()),
+        });
+    }
+    candidates
+}
+
+// ISO 639-1 / 639-2 的 B、T 两种写法统一成 639-2/T
+fn normalize_language(code: &str) -> String {
+    let code = code.trim().to_lowercase();
+    match code.as_str() {
+        "zh" | "chi" => "zho",
+        "en" => "eng",
+        "ja" => "jpn",
+        "ko" => "kor",
+        "de" | "ger" => "deu",
+        "fr" | "fre" => "fra",
+        "es" => "spa",
+        "ru" => "rus",
+        _ => &code,
+    }
+    .to_string()
+}
+
+// 依次比较：语言在偏好列表中的位置 -> 有时间轴优先 -> 原有顺序
+pub fn select_lyrics(candidates: Vec<LyricsCandidate>, preferred: &[String]) -> Option<ParsedLyrics> {
+    let preferred: Vec<String> = preferred.iter().map(|l| normalize_language(l)).collect();
+    candidates
+        .into_iter()
+        .map(|candidate| {
+            let mut parsed = parse_lyrics(&candidate.content, candidate.format);
+            parsed.source = candidate.source;
+            // TTML 自带 xml:lang
+            parsed.language = candidate.language.or(parsed.language);
+            parsed.content = candidate.content;
+            parsed
+        })
+        .filter(|parsed| !parsed.lines.is_empty())
+        .enumerate()
+        .min_by_key(|(i, parsed)| {
+            let rank = parsed
+                .language
+                .as_deref()
+                .and_then(|l| preferred.iter().position(|p| *p == normalize_language(l)))
+                .unwrap_or(preferred.len());
+            (rank, !parsed.synced, *i)
+        })
+        .map(|(_, parsed)| parsed)
+}
+
+// --- Commands ---
+
+// 结构化的歌词，按偏好语言与是否同步从内嵌歌词与歌词文件中挑一份；都没有时返回 None
+// language 为本次优先的语言，其后才是设置中的偏好列表
+#[tauri::command]
+pub async fn get_parsed_lyrics(
+    path: String,
+    language: Option<String>,
+    db_state: State<'_, DbState>,
+) -> Result<Option<ParsedLyrics>, String> {
+    let db = db_state.conn.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let config = load_config(&db);
+        let candidates = lyrics_candidates(Path::new(&path), &db, &config);
+        let preferred: Vec<String> = language.into_iter().chain(config.preferred_languages).collect();
+        select_lyrics(candidates, &preferred)
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+// 歌曲的全部歌词来源，供用户切换语言 / 来源
+#[tauri::command]
+pub async fn get_lyrics_candidates(path: String, db_state: State<'_, DbState>) -> Result<Vec<LyricsCandidate>, String> {
+    let db = db_state.conn.clone();
+    tauri::async_runtime::spawn_blocking(move || lyrics_candidates(Path::new(&path), &db, &load_config(&db)))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_lyrics_config(db_state: State<'_, DbState>) -> Result<LyricsConfig, String> {
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    Ok(load_setting(&conn, LYRICS_CONFIG_KEY))
+}
+
+#[tauri::command]
+pub fn set_lyrics_config(config: LyricsConfig, db_state: State<'_, DbState>) -> Result<(), String> {
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    save_setting(&conn, LYRICS_CONFIG_KEY, &config)
+}
+
+// path 为歌曲路径 (取找到的第一个歌词文件) 或歌词文件路径；没有歌词文件时返回 None
+#[tauri::command]
+pub fn get_lyrics_encoding(path: String, db_state: State<'_, DbState>) -> Result<Option<LyricsEncodingInfo>, String> {
+    let Some(lrc_path) = resolve_lyrics_file(Path::new(&path), &db_state.conn) else { return Ok(None) };
+    let bytes = fs::read(&lrc_path).map_err(|e| e.to_string())?;
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    Ok(Some(LyricsEncodingInfo {
+        file: lrc_path.to_string_lossy().into_owned(),
+        detected: detect_encoding(&bytes).name().to_string(),
+        forced: load_override(&conn, &lrc_path).map(|e| e.name().to_string()),
+    }))
+}
+
+// encoding 为编码名称 (如 "gbk"、"big5"、"shift_jis"、"utf-16le")，None 表示恢复自动检测
+#[tauri::command]
+pub fn set_lyrics_encoding(path: String, encoding: Option<String>, db_state: State<'_, DbState>) -> Result<(), String> {
+    let lrc_path = resolve_lyrics_file(Path::new(&path), &db_state.conn).ok_or("没有找到歌词文件")?;
+    let lrc_path = lrc_path.to_string_lossy();
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    match encoding {
+        Some(label) => {
+            let encoding = Encoding::for_label(label.trim().as_bytes())
+                .ok_or_else(|| format!("不支持的编码: {}", label))?;
+            conn.execute(
+                "INSERT INTO lyrics_encodings (path, encoding) VALUES (?1, ?2)
+                 ON CONFLICT(path) DO UPDATE SET encoding = excluded.encoding",
+                (&*lrc_path, encoding.name()),
+            )
+        }
+        None => conn.execute("DELETE FROM lyrics_encodings WHERE path = ?1", [&*lrc_path]),
+    }
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+    use encoding_rs::{BIG5, GB18030, GBK, SHIFT_JIS};
+
+    fn times(parsed: &ParsedLyrics) -> Vec<u64> {
+        parsed.lines.iter().map(|l| l.time).collect()
+    }
+
+    fn word(start: u64, end: Option<u64>, text: &str) -> LyricWord {
+        LyricWord { start, end, text: text.to_string() }
+    }
+
+    #[test]
+    fn lrc_timestamps_and_tags() {
+        let parsed = parse_lrc("\u{feff}[ti:Song]\n[AR: Singer ]\n[00:12.00][01:30.00]Chorus\n[00:05]Intro\n[00:20.5]Verse\n[00:21:50]Colon\n[00:22.123]Millis");
+        assert!(parsed.synced);
+        assert_eq!(parsed.tags.get("ti").map(String::as_str), Some("Song"));
+        assert_eq!(parsed.tags.get("ar").map(String::as_str), Some("Singer"));
+        assert_eq!(times(&parsed), vec![5000, 12000, 20500, 21500, 22123, 90000]);
+        assert_eq!(parsed.lines[1].text, "Chorus");
+        assert_eq!(parsed.lines[5].text, "Chorus");
+    }
+
+    #[test]
+    fn lrc_offset_shifts_every_line() {
+        let parsed = parse_lrc("[00:01.00]<00:01.00>a<00:01.50>b\n[offset:500]\n[00:00.20]early");
+        assert_eq!(parsed.offset, 500);
+        // 提前后小于 0 的时间按 0 算
+        assert_eq!(times(&parsed), vec![0, 500]);
+        assert_eq!(parsed.lines[1].words, vec![word(500, Some(1000), "a"), word(1000, None, "b")]);
+    }
+
+    #[test]
+    fn lrc_groups_translation_and_romaji() {
+        let parsed = parse_lrc("[00:10.00]君の名は\n[00:10.00]你的名字\n[00:10.02]kimi no na wa\n[00:15.00]Hello\n[00:15.00]你好");
+        assert_eq!(parsed.lines.len(), 2);
+        let first = &parsed.lines[0];
+        assert_eq!(first.text, "君の名は");
+        assert_eq!(first.translation.as_deref(), Some("你的名字"));
+        assert_eq!(first.romaji.as_deref(), Some("kimi no na wa"));
+        // 非日韩原文后的行都是翻译
+        assert_eq!(parsed.lines[1].translation.as_deref(), Some("你好"));
+        assert_eq!(parsed.lines[1].romaji, None);
+    }
+
+    #[test]
+    fn lrc_word_timing() {
+        let parsed = parse_lrc("[00:01.00]<00:01.00>Hel<00:01.50>lo <00:02.00>world<00:02.80>\n[00:03.00]<00:03.00>one<00:03.50>two\n[00:05.00]<00:05.00>last");
+        let lines = &parsed.lines;
+        assert_eq!(lines[0].text, "Hello world");
+        assert_eq!(lines[0].words, vec![word(1000, Some(1500), "Hel"), word(1500, Some(2000), "lo "), word(2000, Some(2800), "world")]);
+        // 行末没有结束标签的字结束于下一行开始，最后一行没有结束时间
+        assert_eq!(lines[1].words, vec![word(3000, Some(3500), "one"), word(3500, Some(5000), "two")]);
+        assert_eq!(lines[2].words, vec![word(5000, None, "last")]);
+    }
+
+    #[test]
+    fn lrc_without_timestamps_is_plain_text() {
+        let parsed = parse_lrc("[ti:Song]\nline one\n\n  line two  ");
+        assert!(!parsed.synced);
+        assert_eq!(parsed.lines.iter().map(|l| l.text.as_str()).collect::<Vec<_>>(), vec!["line one", "line two"]);
+        assert_eq!(times(&parsed), vec![0, 0]);
+    }
+
+    #[test]
+    fn lrc_drops_empty_lines_and_bad_timestamps() {
+        let parsed = parse_lrc("[00:01.00]\n[00:02.00]x\n[0a:03.00]bad");
+        assert_eq!(times(&parsed), vec![2000]);
+        assert_eq!(parsed.lines[0].text, "x");
+    }
+
+    fn encode(text: &str, encoding: &'static Encoding) -> Vec<u8> {
+        encoding.encode(text).0.into_owned()
+    }
+
+    fn utf16(text: &str, big_endian: bool) -> Vec<u8> {
+        text.encode_utf16()
+            .flat_map(|u| if big_endian { u.to_be_bytes() } else { u.to_le_bytes() })
+            .collect()
+    }
+
+    const CHINESE_LRC: &str = "[ti:晴天]\n[00:01.00]故事的小黄花\n[00:05.00]从出生那年就飘着\n[00:09.00]童年的荡秋千\n[00:13.00]随记忆一直晃到现在";
+    const JAPANESE_LRC: &str = "[00:01.00]君の名前を呼んでいる\n[00:05.00]夜空に輝く星のように\n[00:09.00]ずっと忘れないでいてね";
+
+    #[test]
+    fn encoding_from_bom() {
+        let mut utf8 = b"\xEF\xBB\xBF".to_vec();
+        utf8.extend(CHINESE_LRC.as_bytes());
+        assert_eq!(detect_encoding(&utf8), UTF_8);
+
+        let mut le = b"\xFF\xFE".to_vec();
+        le.extend(utf16(CHINESE_LRC, false));
+        assert_eq!(detect_encoding(&le), UTF_16LE);
+        // BOM 优先于用户指定的编码
+        assert_eq!(decode_lyrics(&le, Some(GBK)), CHINESE_LRC);
+    }
+
+    #[test]
+    fn encoding_utf16_without_bom() {
+        assert_eq!(detect_encoding(&utf16("[00:01.00]hello world", false)), UTF_16LE);
+        assert_eq!(detect_encoding(&utf16("[00:01.00]hello world", true)), UTF_16BE);
+        assert_eq!(decode_lyrics(&utf16(CHINESE_LRC, true), None), CHINESE_LRC);
+    }
+
+    #[test]
+    fn encoding_legacy_codepages() {
+        assert_eq!(detect_encoding(CHINESE_LRC.as_bytes()), UTF_8);
+
+        let gbk = encode(CHINESE_LRC, GBK);
+        assert_eq!(detect_encoding(&gbk), GBK);
+        assert_eq!(decode_lyrics(&gbk, None), CHINESE_LRC);
+
+        let sjis = encode(JAPANESE_LRC, SHIFT_JIS);
+        assert_eq!(detect_encoding(&sjis), SHIFT_JIS);
+        assert_eq!(decode_lyrics(&sjis, None), JAPANESE_LRC);
+
+        let big5 = encode("[00:01.00]故事的小黃花\n[00:05.00]從出生那年就飄著\n[00:09.00]童年的盪鞦韆", BIG5);
+        assert_eq!(detect_encoding(&big5), BIG5);
+    }
+
+    #[test]
+    fn forced_encoding_overrides_detection() {
+        let gbk = encode(CHINESE_LRC, GBK);
+        // 用户指定的编码即使不对也照用
+        assert_ne!(decode_lyrics(&gbk, Some(BIG5)), CHINESE_LRC);
+        assert_eq!(decode_lyrics(&gbk, Some(GB18030)), CHINESE_LRC);
+    }
+
+    const TTML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<tt xmlns="http://www.w3.org/ns/ttml" xmlns:ttm="http://www.w3.org/ns/ttml#metadata" xmlns:itunes="http://music.apple.com/lyric-ttml-internal" xml:lang="ja">
+  <head><metadata><iTunesMetadata xmlns="http://music.apple.com/lyric-ttml-internal">
+    <translations><translation xml:lang="zh-Hans"><text for="L1">你的名字</text></translation></translations>
+    <transliterations><transliteration xml:lang="ja-Latn"><text for="L1">kimi no na wa</text></transliteration></transliterations>
+  </iTunesMetadata></metadata></head>
+  <body><div>
+    <p begin="00:04.000" itunes:key="L2"><span begin="00:04.000">Hello</span> <span begin="00:04.500" dur="0.5s">world</span><span ttm:role="x-translation">你好世界</span></p>
+    <p begin="00:01.000" end="00:03.000" itunes:key="L1"><span begin="00:01.000" end="00:01.500">君の</span><span begin="00:01.500">名は</span><span ttm:role="x-bg"><span begin="00:02.000" end="00:02.500">(ah)</span></span></p>
+  </div></body>
+</tt>"#;
+
+    #[test]
+    fn clock_formats() {
+        assert_eq!(parse_clock("01:02:03.5"), Some(3_723_500));
+        assert_eq!(parse_clock("00:01,250"), Some(1250));
+        assert_eq!(parse_clock("12.5"), Some(12_500));
+        assert_eq!(parse_clock("1.5s"), Some(1500));
+        assert_eq!(parse_clock("1500ms"), Some(1500));
+        assert_eq!(parse_clock("2m"), Some(120_000));
+        assert_eq!(parse_clock("1:2:3:4"), None);
+        assert_eq!(parse_clock("abc"), None);
+    }
+
+    #[test]
+    fn ttml_lines_words_and_metadata() {
+        let parsed = parse_ttml(TTML);
+        assert!(parsed.synced);
+        assert_eq!(parsed.language.as_deref(), Some("jpn"));
+        assert_eq!(times(&parsed), vec![1000, 4000]);
+
+        let first = &parsed.lines[0];
+        assert_eq!(first.text, "君の名は");
+        // 没有 end 的字结束于下一行开始
+        assert_eq!(first.words, vec![word(1000, Some(1500), "君の"), word(1500, Some(4000), "名は")]);
+        assert_eq!(first.translation.as_deref(), Some("你的名字"));
+        assert_eq!(first.romaji.as_deref(), Some("kimi no na wa"));
+        assert_eq!(first.background, Some(BackgroundVocals { text: "(ah)".to_string(), words: vec![word(2000, Some(2500), "(ah)")] }));
+
+        let second = &parsed.lines[1];
+        assert_eq!(second.text, "Hello world");
+        assert_eq!(second.words, vec![word(4000, None, "Hello "), word(4500, Some(5000), "world")]);
+        assert_eq!(second.translation.as_deref(), Some("你好世界"));
+        assert_eq!(second.background, None);
+    }
+
+    #[test]
+    fn ttml_without_timing_or_invalid() {
+        let parsed = parse_ttml(r#"<tt xmlns="http://www.w3.org/ns/ttml"><body><div><p>first   line</p><p>  </p><p>second</p></div></body></tt>"#);
+        assert!(!parsed.synced);
+        assert_eq!(parsed.lines.iter().map(|l| l.text.as_str()).collect::<Vec<_>>(), vec!["first line", "second"]);
+        assert!(parse_ttml("<tt><p>unclosed").lines.is_empty());
+    }
+
+    #[test]
+    fn srt_cues_group_and_strip_styles() {
+        let srt = "1\r\n00:00:01,000 --> 00:00:03,500\r\n<i>First line</i>\r\n第一行翻译\r\n\r\n2\r\n00:00:04,000 --> 00:00:06,000\r\n{\\an8}<font color=\"red\">Second</font>\r\n\r\n3\r\nbroken --> timing\r\nignored\r\n";
+        let parsed = parse_subtitles(srt);
+        assert!(parsed.synced);
+        assert_eq!(times(&parsed), vec![1000, 4000]);
+        assert_eq!(parsed.lines[0].text, "First line");
+        assert_eq!(parsed.lines[0].translation.as_deref(), Some("第一行翻译"));
+        assert_eq!(parsed.lines[1].text, "Second");
+    }
+
+    #[test]
+    fn vtt_skips_header_and_notes_and_keeps_word_timing() {
+        let vtt = "WEBVTT\n\nNOTE written by hand\n\nSTYLE\n::cue { color: white }\n\nintro\n00:01.000 --> 00:03.000 align:start\n<v Singer><00:01.000>Hel<00:01.500>lo\n\n00:04.000 --> 00:05.000\nPlain";
+        let parsed = parse_subtitles(vtt);
+        assert_eq!(times(&parsed), vec![1000, 4000]);
+        assert_eq!(parsed.lines[0].text, "Hello");
+        // 最后一个字结束于字幕块结束
+        assert_eq!(parsed.lines[0].words, vec![word(1000, Some(1500), "Hel"), word(1500, Some(3000), "lo")]);
+        assert_eq!(parsed.lines[1].text, "Plain");
+        assert!(parsed.lines[1].words.is_empty());
+    }
+
+    // --- ID3v2 歌词帧 ---
+
+    fn id3_text(encoding: u8, text: &str) -> Vec<u8> {
+        match encoding {
+            0 => text.chars().map(|c| c as u8).collect(),
+            1 => [0xFF, 0xFE].into_iter().chain(utf16(text, false)).collect(),
+            2 => utf16(text, true),
+            _ => text.as_bytes().to_vec(),
+        }
+    }
+
+    fn terminated(encoding: u8, text: &str) -> Vec<u8> {
+        let mut bytes = id3_text(encoding, text);
+        bytes.extend(if matches!(encoding, 1 | 2) { &[0u8, 0][..] } else { &[0u8][..] });
+        bytes
+    }
+
+    fn uslt(encoding: u8, lang: &[u8; 3], description: &str, lyrics: &str) -> Vec<u8> {
+        let mut data = vec![encoding];
+        data.extend_from_slice(lang);
+        data.extend(terminated(encoding, description));
+        data.extend(id3_text(encoding, lyrics));
+        data
+    }
+
+    // 时间单位毫秒，内容类型为歌词
+    fn sylt(encoding: u8, lang: &[u8; 3], entries: &[(&str, u32)]) -> Vec<u8> {
+        let mut data = vec![encoding];
+        data.extend_from_slice(lang);
+        data.extend([2, 1]);
+        data.extend(terminated(encoding, ""));
+        for (text, time) in entries {
+            data.extend(terminated(encoding, text));
+            data.extend(time.to_be_bytes());
+        }
+        data
+    }
+
+    fn syncsafe_bytes(n: usize) -> [u8; 4] {
+        [(n >> 21) as u8 & 0x7f, (n >> 14) as u8 & 0x7f, (n >> 7) as u8 & 0x7f, n as u8 & 0x7f]
+    }
+
+    // 0xFF 后插入 0x00
+    fn unsync(data: &[u8]) -> Vec<u8> {
+        data.iter().flat_map(|&b| if b == 0xFF { vec![b, 0] } else { vec![b] }).collect()
+    }
+
+    fn frame(version: u8, id: &[u8], format_flags: u8, data: &[u8]) -> Vec<u8> {
+        let mut bytes = id.to_vec();
+        match version {
+            2 => bytes.extend(&(data.len() as u32).to_be_bytes()[1..]),
+            3 => bytes.extend((data.len() as u32).to_be_bytes()),
+            _ => bytes.extend(syncsafe_bytes(data.len())),
+        }
+        if version > 2 {
+            bytes.extend([0, format_flags]);
+        }
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    fn id3_lyrics_of(version: u8, flags: u8, body: &[u8]) -> Vec<LyricsCandidate> {
+        let mut bytes = vec![b'I', b'D', b'3', version, 0, flags];
+        bytes.extend(syncsafe_bytes(body.len()));
+        bytes.extend_from_slice(body);
+        bytes.extend([0xFF, 0xFB, 0x90, 0x64]);
+        let dir = TempDir::new("lyrics_test");
+        id3v2_lyrics(&dir.write("song.mp3", &bytes))
+    }
+
+    fn summary(found: &[LyricsCandidate]) -> Vec<(Option<&str>, Option<&str>, &str)> {
+        found.iter().map(|c| (c.language.as_deref(), c.description.as_deref(), c.content.as_str())).collect()
+    }
+
+    #[test]
+    fn uslt_in_every_text_encoding() {
+        let mut body = frame(4, b"USLT", 0, &uslt(0, b"eng", "desc", "Hello"));
+        body.extend(frame(4, b"USLT", 0, &uslt(1, b"chi", "说明", "你好")));
+        body.extend(frame(4, b"USLT", 0, &uslt(2, b"JPN", "", "こんにちは")));
+        body.extend(frame(4, b"USLT", 0, &uslt(3, b"xxx", "", "안녕")));
+        // 填充
+        body.extend([0u8; 32]);
+
+        let found = id3_lyrics_of(4, 0, &body);
+        assert_eq!(
+            summary(&found),
+            [
+                (Some("eng"), Some("desc"), "Hello"),
+                (Some("chi"), Some("说明"), "你好"),
+                (Some("jpn"), None, "こんにちは"),
+                (None, None, "안녕"),
+            ]
+        );
+        assert!(found.iter().all(|c| c.source == LyricsSource::Uslt && c.format == LyricsFormat::Lrc));
+    }
+
+    #[test]
+    fn sylt_lines_and_words() {
+        let lines = sylt(3, b"eng", &[("first line", 1000), ("second line", 65_500)]);
+        let words = sylt(1, b"chi", &[("Hel", 1000), ("lo", 1500), ("\nworld", 3000)]);
+        let mut body = frame(3, b"SYLT", 0, &lines);
+        body.extend(frame(3, b"SYLT", 0, &words));
+        let found = id3_lyrics_of(3, 0, &body);
+
+        assert_eq!(found[0].source, LyricsSource::Sylt);
+        assert_eq!(found[0].content, "[00:01.000]first line\n[01:05.500]second line");
+        // 以换行开头的条目开始新的一行，其余条目是上一行的字
+        assert_eq!(found[1].content, "[00:01.000]<00:01.000>Hel<00:01.500>lo\n[00:03.000]<00:03.000>world");
+        let parsed = parse_lrc(&found[1].content);
+        assert_eq!(parsed.lines[0].words, vec![word(1000, Some(1500), "Hel"), word(1500, Some(3000), "lo")]);
+    }
+
+    #[test]
+    fn sylt_with_frame_timestamps_is_skipped() {
+        let mut data = sylt(3, b"eng", &[("line", 10)]);
+        data[4] = 1;
+        assert!(id3_lyrics_of(3, 0, &frame(3, b"SYLT", 0, &data)).is_empty());
+    }
+
+    #[test]
+    fn unsynchronised_v23_tag_with_extended_header() {
+        // 扩展头：长度 6 (不含自身) + 标志 + 填充长度
+        let mut body = vec![0, 0, 0, 6, 0, 0, 0, 0, 0, 0];
+        // UTF-16 的 BOM 含 0xFF，反同步后插入 0x00
+        body.extend(frame(3, b"USLT", 0, &uslt(1, b"chi", "", "第一行\n第二行")));
+        // 分组标志多一个字节
+        body.extend(frame(3, b"USLT", 0x20, &[&[7u8][..], &uslt(3, b"eng", "", "grouped")].concat()));
+        // 压缩的帧跳过
+        body.extend(frame(3, b"USLT", 0x80, &uslt(3, b"eng", "", "compressed")));
+
+        let found = id3_lyrics_of(3, 0xC0, &unsync(&body));
+        assert_eq!(summary(&found), [(Some("chi"), None, "第一行\n第二行"), (Some("eng"), None, "grouped")]);
+    }
+
+    #[test]
+    fn v24_frame_with_data_length_and_unsync() {
+        let data = uslt(1, b"jpn", "", "歌詞");
+        let mut stored = syncsafe_bytes(data.len()).to_vec();
+        stored.extend(unsync(&data));
+        // 扩展头：长度 6 (含自身)
+        let mut body = vec![0, 0, 0, 6, 1, 0];
+        body.extend(frame(4, b"USLT", 0x03, &stored));
+        body.extend(frame(4, b"TIT2", 0, b"\x03Title"));
+
+        let found = id3_lyrics_of(4, 0x40, &body);
+        assert_eq!(summary(&found), [(Some("jpn"), None, "歌詞")]);
+    }
+
+    #[test]
+    fn v22_ult_frame() {
+        let found = id3_lyrics_of(2, 0, &frame(2, b"ULT", 0, &uslt(0, b"eng", "", "old tag")));
+        assert_eq!(summary(&found), [(Some("eng"), None, "old tag")]);
+    }
+
+    // --- 多份歌词的选择 ---
+
+    fn candidate(language: Option<&str>, content: &str) -> LyricsCandidate {
+        LyricsCandidate::embedded(LyricsSource::Uslt, language.map(str::to_string), None, content.to_string())
+    }
+
+    #[test]
+    fn select_prefers_language_then_synced() {
+        let candidates = || {
+            vec![
+                candidate(Some("eng"), "plain english"),
+                candidate(None, "[00:01.00]synced, no language"),
+                candidate(Some("chi"), "纯文本中文"),
+                candidate(Some("zho"), "[00:01.00]同步的中文"),
+                candidate(Some("jpn"), ""),
+            ]
+        };
+        let text = |parsed: Option<ParsedLyrics>| parsed.unwrap().lines[0].text.clone();
+
+        // zh / chi / zho 视为同一语言，同语言中有时间轴的优先
+        assert_eq!(text(select_lyrics(candidates(), &["zh".to_string()])), "同步的中文");
+        assert_eq!(text(select_lyrics(candidates(), &["en".to_string(), "zh".to_string()])), "plain english");
+        // 没有匹配的偏好时按是否同步、再按原有顺序
+        assert_eq!(text(select_lyrics(candidates(), &["jpn".to_string()])), "synced, no language");
+        assert_eq!(text(select_lyrics(candidates(), &[])), "synced, no language");
+        assert_eq!(select_lyrics(candidates(), &["chi".to_string()]).unwrap().content, "[00:01.00]同步的中文");
+        assert!(select_lyrics(vec![candidate(Some("eng"), "  ")], &[]).is_none());
+    }
+}
+
@@ -0,0 +1,187 @@
+use lofty::prelude::*;
+use lofty::probe::Probe;
+use lofty::tag::{ItemKey, Tag};
+use std::path::Path;
+
+use crate::artists::{LinkTags, MULTI_VALUE_JOINER};
+use crate::music::{embedded_lyrics, front_cover, sidecar_lyrics};
+use crate::ratings::read_rating;
+use crate::sort_key::{sort_key, sort_key_or};
+
+// 元数据结构版本：数据库里低于该版本的行会在下次扫描时重新解析
+// 1 = 基础字段 + 音质 (v1.1.1)
+// 2 = 专辑艺人 / 流派 / 年份 / 音轨号 / 碟号 / 作曲
+// 3 = 多值艺人 / 流派 (artists / genres 关联表)
+// 4 = 全文索引 (songs_fts，含歌词)
+// 5 = 排序键 (TSOT / TSOP / TSOA / TSO2，或拼音 / 罗马字)
+// 6 = 评分 (POPM / RATING / rate)
+// 7 = 封面键与缩略图 (song_covers / cover_path)
+// 8 = 原始多值标签与艺人排序名 (songs.link_tags)，拆分配置变更时据此重建关联
+pub const METADATA_VERSION: i64 = 8;
+
+pub const UNKNOWN_ARTIST: &str = "未知歌手";
+pub const UNKNOWN_ALBUM: &str = "未知专辑";
+
+// 从文件标签中解析出的一首歌的完整元数据
+#[derive(Debug, Clone, Default)]
+pub struct SongMetadata {
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    pub album_artist: Option<String>,
+    pub genre: Option<String>,
+    pub year: Option<u32>,
+    pub track_number: Option<u32>,
+    pub track_total: Option<u32>,
+    pub disc_number: Option<u32>,
+    pub disc_total: Option<u32>,
+    pub composer: Option<String>,
+    // 标签中的原始多值，拆分交给 artists 模块
+    pub artists: Vec<String>,
+    pub album_artists: Vec<String>,
+    pub genres: Vec<String>,
+    // 内嵌或同名 .lrc 的歌词原文，仅用于全文索引
+    pub lyrics: Option<String>,
+    // 排序键：标签里的排序字段优先，否则由名字转写
+    pub title_sort: String,
+    pub artist_sort: String,
+    pub album_sort: String,
+    pub album_artist_sort: String,
+    // 标签中原样的艺人排序名 (TSOP)，单艺人时用于覆盖 artists.sort_name
+    pub artist_sort_tag: Option<String>,
+    // 标签中的星级评分 0..5，没有评分字段为 None
+    pub rating: Option<u8>,
+    // 内嵌封面原始数据，扫描时用于生成缩略图，不入库
+    pub cover: Option<Vec<u8>>,
+    pub duration: u32,
+    pub bitrate: u32,
+    pub sample_rate: u32,
+    pub bit_depth: Option<u8>,
+}
+
+impl SongMetadata {
+    pub fn link_tags(&self) -> LinkTags {
+        LinkTags {
+            artists: self.artists.clone(),
+            album_artists: self.album_artists.clone(),
+            genres: self.genres.clone(),
+            artist_sort: self.artist_sort_tag.clone(),
+        }
+    }
+}
+
+// 同一个键的全部取值 (多个 TPE1 帧、多个 ARTIST 字段等)，去空去重
+fn all_values(tag: &Tag, keys: &[ItemKey]) -> Vec<String> {
+    let mut values: Vec<String> = Vec::new();
+    for key in keys {
+        for value in tag.get_strings(key) {
+            let value = value.trim();
+            if !value.is_empty() && !values.iter().any(|v| v == value) {
+                values.push(value.to_string());
+            }
+        }
+    }
+    values
+}
+
+// lofty 没有专门 ItemKey 的字段 (如 ARTISTS) 保留原始键名，键名大小写不一
+fn custom_values(tag: &Tag, name: &str) -> Vec<String> {
+    let mut values: Vec<String> = Vec::new();
+    for item in tag.items() {
+        let ItemKey::Unknown(key) = item.key() else { continue };
+        let Some(value) = item.value().text().map(str::trim) else { continue };
+        if key.eq_ignore_ascii_case(name) && !value.is_empty() && !values.iter().any(|v| v == value) {
+            values.push(value.to_string());
+        }
+    }
+    values
+}
+
+fn non_empty(value: Option<&str>) -> Option<String> {
+    value
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+        .map(|v| v.to_string())
+}
+
+// 年份：优先 Year，其次从 RecordingDate (如 "2019-05-01") 截取前四位
+fn read_year(tag: &Tag) -> Option<u32> {
+    if let Some(year) = tag.year().filter(|y| *y > 0) {
+        return Some(year);
+    }
+    tag.get_string(&ItemKey::RecordingDate)
+        .or_else(|| tag.get_string(&ItemKey::Year))
+        .and_then(|date| date.trim().get(0..4))
+        .and_then(|y| y.parse::<u32>().ok())
+        .filter(|y| *y > 0)
+}
+
+pub fn read_metadata(path: &Path) -> Result<SongMetadata, String> {
+    let tagged_file = Probe::open(path)
+        .and_then(|p| p.read())
+        .map_err(|e| e.to_string())?;
+
+    let props = tagged_file.properties();
+    let mut meta = SongMetadata {
+        artist: UNKNOWN_ARTIST.to_string(),
+        album: UNKNOWN_ALBUM.to_string(),
+        duration: props.duration().as_secs() as u32,
+        bitrate: props.audio_bitrate().unwrap_or(0),
+        sample_rate: props.sample_rate().unwrap_or(0),
+        bit_depth: props.bit_depth(),
+        ..Default::default()
+    };
+
+    if let Some(tag) = tagged_file.primary_tag().or_else(|| tagged_file.first_tag()) {
+        // 展示用 TrackArtist；若有 ARTISTS 这类专门的多值字段，则用它建立关联
+        let display_artists = all_values(tag, &[ItemKey::TrackArtist]);
+        let listed_artists = custom_values(tag, "ARTISTS");
+        meta.album_artists = all_values(tag, &[ItemKey::AlbumArtist]);
+        meta.genres = all_values(tag, &[ItemKey::Genre]);
+
+        if !display_artists.is_empty() { meta.artist = display_artists.join(MULTI_VALUE_JOINER); }
+        meta.artists = if listed_artists.is_empty() { display_artists } else { listed_artists };
+        if let Some(alb) = non_empty(tag.album().as_deref()) { meta.album = alb; }
+        if let Some(tit) = non_empty(tag.title().as_deref()) { meta.title = tit; }
+
+        if !meta.album_artists.is_empty() { meta.album_artist = Some(meta.album_artists.join(MULTI_VALUE_JOINER)); }
+        if !meta.genres.is_empty() { meta.genre = Some(meta.genres.join(MULTI_VALUE_JOINER)); }
+        meta.year = read_year(tag);
+        meta.track_number = tag.track().filter(|n| *n > 0);
+        meta.track_total = tag.track_total().filter(|n| *n > 0);
+        meta.disc_number = tag.disk().filter(|n| *n > 0);
+        meta.disc_total = tag.disk_total().filter(|n| *n > 0);
+        meta.composer = non_empty(tag.get_string(&ItemKey::Composer));
+        meta.lyrics = embedded_lyrics(tag);
+        meta.rating = read_rating(tag);
+        meta.cover = front_cover(tag);
+    }
+    if meta.lyrics.is_none() {
+        meta.lyrics = sidecar_lyrics(path);
+    }
+
+    let sort_tag = |key: &ItemKey| -> Option<String> {
+        tagged_file
+            .primary_tag()
+            .or_else(|| tagged_file.first_tag())
+            .and_then(|tag| non_empty(tag.get_string(key)))
+    };
+    meta.artist_sort_tag = sort_tag(&ItemKey::TrackArtistSortOrder);
+    let title_for_sort = if meta.title.is_empty() {
+        path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default()
+    } else {
+        meta.title.clone()
+    };
+    meta.title_sort = sort_key_or(sort_tag(&ItemKey::TrackTitleSortOrder).as_deref(), &title_for_sort);
+    meta.artist_sort = sort_key_or(meta.artist_sort_tag.as_deref(), &meta.artist);
+    meta.album_sort = sort_key_or(sort_tag(&ItemKey::AlbumTitleSortOrder).as_deref(), &meta.album);
+    meta.album_artist_sort = match sort_tag(&ItemKey::AlbumArtistSortOrder) {
+        Some(sort) => sort_key(&sort),
+        None => match meta.album_artist.as_deref() {
+            Some(album_artist) => sort_key(album_artist),
+            None => meta.artist_sort.clone(),
+        },
+    };
+
+    Ok(meta)
+}
@@ -1,15 +1,19 @@
+use crate::artwork::{
+    find_sidecar_cover, load_cover_config, render_cover, standard_size, CoverConfig, CoverFormat, COVER_SIZES,
+};
+use crate::cache::{self, CacheKind, CacheManager};
 use crate::database::DbState;
 use crate::error::CommandError;
+use crate::lyrics::{encoding_override, read_lyrics_file};
+use crate::palette::{extract_palette, CoverPalette};
+use crate::scanner::{scan_folder, ScanControl, ScanProgress};
 use lofty::prelude::*;
 use lofty::probe::Probe;
-use lofty::tag::ItemKey;
-use rusqlite::OptionalExtension;
+use lofty::tag::{ItemKey, Tag};
 use serde::Serialize;
 use std::fs;
 use std::path::{Path, PathBuf};
 use tauri::{AppHandle, Manager, State};
-use walkdir::WalkDir;
-use image::ImageFormat;
 use sha2::{Sha256, Digest};
 use std::collections::HashMap;
 use std::process::Command;
@@ -21,6 +25,8 @@ pub struct ImageConcurrencyLimit(pub Semaphore);
 
 #[derive(Serialize, Clone, Debug)]
 pub struct Song {
+    pub id: i64,
+    pub album_id: Option<i64>,
     pub name: String,
     pub title: String,
     pub path: String,
@@ -33,6 +39,81 @@ pub struct Song {
     pub sample_rate: u32,
     pub bit_depth: Option<u8>,
     pub format: String,
+    // Full tag metadata (v1.2.0)
+    pub album_artist: Option<String>,
+    pub genre: Option<String>,
+    pub year: Option<u32>,
+    pub track_number: Option<u32>,
+    pub track_total: Option<u32>,
+    pub disc_number: Option<u32>,
+    pub disc_total: Option<u32>,
+    pub composer: Option<String>,
+    // Play statistics, aggregated from play_events
+    pub play_count: u32,
+    pub skip_count: u32,
+    pub last_played: Option<i64>,
+    // 0..5 stars, None = unrated
+    pub rating: Option<u8>,
+}
+
+// 与 Song::from_row 一一对应的查询列
+pub const SONG_COLUMNS: &str = "path, title, artist, album, duration, cover_path, bitrate, sample_rate, bit_depth, format, \
+    album_artist, genre, year, track_number, track_total, disc_number, disc_total, composer, id, album_id, \
+    play_count, skip_count, last_played, rating";
+
+// SONG_COLUMNS 的列数，调用方追加的列从该下标开始
+pub const SONG_COLUMN_COUNT: usize = 24;
+
+// 多表联查时给 SONG_COLUMNS 加上表别名，如 "s.path, s.title, ..."
+pub fn song_columns_with(alias: &str) -> String {
+    SONG_COLUMNS
+        .split(',')
+        .map(|c| format!("{}.{}", alias, c.trim()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl Song {
+    pub fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Song> {
+        let path: String = row.get(0)?;
+        let name = Path::new(&path)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let format: Option<String> = row.get(9)?;
+        Ok(Song {
+            id: row.get(18)?,
+            album_id: row.get(19)?,
+            name,
+            title: row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+            artist: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+            album: row.get::<_, Option<String>>(3)?.unwrap_or_default(),
+            duration: row.get::<_, Option<u32>>(4)?.unwrap_or_default(),
+            cover: row.get(5)?,
+            bitrate: row.get::<_, Option<u32>>(6)?.unwrap_or(0),
+            sample_rate: row.get::<_, Option<u32>>(7)?.unwrap_or(0),
+            bit_depth: row.get(8)?,
+            format: format.unwrap_or_else(|| {
+                Path::new(&path)
+                    .extension()
+                    .map(|e| e.to_string_lossy().to_lowercase())
+                    .unwrap_or_default()
+            }),
+            album_artist: row.get(10)?,
+            genre: row.get(11)?,
+            year: row.get(12)?,
+            track_number: row.get(13)?,
+            track_total: row.get(14)?,
+            disc_number: row.get(15)?,
+            disc_total: row.get(16)?,
+            composer: row.get(17)?,
+            play_count: row.get::<_, Option<u32>>(20)?.unwrap_or(0),
+            skip_count: row.get::<_, Option<u32>>(21)?.unwrap_or(0),
+            last_played: row.get(22)?,
+            rating: row.get(23)?,
+            path,
+        })
+    }
 }
 
 #[derive(Serialize)]
@@ -42,55 +123,10 @@ pub struct GeneratedFolder {
     pub songs: Vec<Song>,
 }
 
-fn get_cover_cache_dir(app: &AppHandle) -> PathBuf {
-    let dir = app.path().app_data_dir().unwrap().join("covers");
-    if !dir.exists() {
-        let _ = fs::create_dir_all(&dir);
-    }
-    dir
-}
-
-// --- 2. 磁盘清理逻辑 (Clean Up) ---
-pub fn run_cache_cleanup(app: &AppHandle) {
-    let cache_dir = get_cover_cache_dir(app);
-    let max_size = 500 * 1024 * 1024; // 500 MB
-
-    std::thread::spawn(move || {
-        if let Ok(read_dir) = fs::read_dir(&cache_dir) {
-            let mut files: Vec<_> = read_dir
-                .filter_map(|entry| entry.ok())
-                .filter_map(|entry| {
-                    let metadata = entry.metadata().ok()?;
-                    let len = metadata.len();
-                    let accessed = metadata.accessed().or(metadata.modified()).unwrap_or(SystemTime::now());
-                    Some((entry.path(), len, accessed))
-                })
-                .collect();
-
-            files.sort_by_key(|&(_, _, time)| time);
-
-            let mut total_size: u64 = files.iter().map(|&(_, len, _)| len).sum();
-
-            if total_size > max_size {
-                println!("缓存清理开始: 当前 {} MB", total_size / 1024 / 1024);
-                for (path, len, _) in files {
-                    if total_size <= max_size {
-                        break;
-                    }
-                    if fs::remove_file(&path).is_ok() {
-                        total_size = total_size.saturating_sub(len);
-                    }
-                }
-                println!("缓存清理结束: 剩余 {} MB", total_size / 1024 / 1024);
-            }
-        }
-    });
-}
-
 // --- 🔥 核心优化：基于“文件指纹”的哈希算法 ---
 // 不再包含 absolute_path，仅使用：文件名 + 大小 + 修改时间
 // 这样文件移动后，只要内容没变，缓存依然有效！
-fn generate_hash(path: &Path) -> String {
+fn generate_hash(path: &Path, sidecar: Option<&Path>, prefer_sidecar: bool) -> String {
     let mut hasher = Sha256::new();
 
     // 1. 获取元数据 (Size + Mtime)
@@ -114,64 +150,238 @@ fn generate_hash(path: &Path) -> String {
     let file_name = path.file_name().unwrap_or_default().to_string_lossy();
     hasher.update(file_name.as_bytes());           // 指纹 3: 文件名
 
+    // 3. 文件夹封面：图片被替换 (mtime 变化) 或优先级设置改变时缓存随之失效
+    if let Some(sidecar) = sidecar {
+        if let Ok(metadata) = fs::metadata(sidecar) {
+            let mtime_nanos = metadata.modified()
+                .unwrap_or(SystemTime::now())
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos();
+            hasher.update(metadata.len().to_be_bytes());
+            hasher.update(mtime_nanos.to_be_bytes());
+        }
+        hasher.update(sidecar.file_name().unwrap_or_default().to_string_lossy().as_bytes());
+        hasher.update([prefer_sidecar as u8]);
+    }
+
     hex::encode(hasher.finalize())
 }
 
-fn get_or_create_thumbnail(path: &Path, app: &AppHandle) -> Option<String> {
-    let hash = generate_hash(path);
-    let cache_dir = get_cover_cache_dir(app);
-    let cache_path = cache_dir.join(format!("{}_thumb_300.jpg", hash));
-    
-    if cache_path.exists() {
-        return Some(cache_path.to_string_lossy().into_owned());
+// 内嵌封面：优先 CoverFront，否则取第一张
+pub fn front_cover(tag: &Tag) -> Option<Vec<u8>> {
+    let pictures = tag.pictures();
+    let pic = pictures.iter()
+        .find(|p| p.pic_type() == lofty::picture::PictureType::CoverFront)
+        .or(pictures.first())?;
+    Some(pic.data().to_vec())
+}
+
+pub fn embedded_cover(path: &Path) -> Option<Vec<u8>> {
+    let tagged_file = Probe::open(path).ok()?.read().ok()?;
+    front_cover(tagged_file.primary_tag()?)
+}
+
+// 封面键：图片内容的哈希。同一张图 (如整张专辑共用的封面) 只缓存一份
+pub fn cover_key(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+// 封面来源：内嵌图片与文件夹图片按设置的先后顺序取第一个
+struct CoverSource {
+    // 文件指纹 (见 generate_hash)，用于判断 song_covers 里的映射是否过期
+    hash: String,
+    sidecar: Option<PathBuf>,
+    config: CoverConfig,
+}
+
+impl CoverSource {
+    fn resolve(path: &Path, config: CoverConfig) -> Self {
+        let sidecar = find_sidecar_cover(path, &config.sidecar_names);
+        Self {
+            hash: generate_hash(path, sidecar.as_deref(), config.prefer_sidecar),
+            sidecar,
+            config,
+        }
     }
-    
-    if let Ok(tagged_file) = Probe::open(path).ok()?.read() {
-         if let Some(tag) = tagged_file.primary_tag() {
-            let pictures = tag.pictures();
-            let pic_opt = pictures.iter()
-                .find(|p| p.pic_type() == lofty::picture::PictureType::CoverFront)
-                .or(pictures.first());
-
-            if let Some(pic) = pic_opt {
-                if let Ok(img) = image::load_from_memory(pic.data()) {
-                    let resized = img.resize(300, 300, image::imageops::FilterType::Triangle);
-                    if let Ok(mut file) = fs::File::create(&cache_path) {
-                         if resized.write_to(&mut file, ImageFormat::Jpeg).is_ok() {
-                             return Some(cache_path.to_string_lossy().into_owned());
-                         }
-                    }
-                }
+
+    // embedded: 内嵌封面的取法，扫描时直接用已解析的标签，避免重复读文件
+    fn load(&self, embedded: impl FnOnce() -> Option<Vec<u8>>) -> Option<Vec<u8>> {
+        let folder = || self.sidecar.as_ref().and_then(|p| fs::read(p).ok());
+        if self.config.prefer_sidecar {
+            folder().or_else(embedded)
+        } else {
+            embedded().or_else(folder)
+        }
+    }
+}
+
+// 缓存的封面规格，决定文件名后缀、尺寸上限与编码格式
+#[derive(Clone, Copy)]
+enum CoverSize {
+    // 旧接口的 300px JPEG 缩略图，也是 Song.cover 指向的文件
+    Thumbnail,
+    // 标准尺寸之一，见 COVER_SIZES
+    Standard(u32),
+    // 完整封面，最长边不超过设置中的 max_full_size
+    Full,
+}
+
+impl CoverSize {
+    fn spec(self, config: &CoverConfig) -> (String, u32, CoverFormat) {
+        match self {
+            CoverSize::Thumbnail => ("thumb_300".to_string(), 300, CoverFormat::Jpeg),
+            CoverSize::Standard(px) => (px.to_string(), px, config.format),
+            CoverSize::Full => {
+                let max = config.max_full_size.max(COVER_SIZES[0]);
+                (format!("full_{}", max), max, config.format)
             }
-         }
+        }
     }
-    None
 }
 
-fn get_or_create_full_cover(path: &Path, app: &AppHandle) -> Option<String> {
-    let hash = generate_hash(path);
-    let cache_dir = get_cover_cache_dir(app);
-    let cache_path = cache_dir.join(format!("{}_full.jpg", hash));
-    
-    if cache_path.exists() {
-        return Some(cache_path.to_string_lossy().into_owned());
+// 解码不了的完整封面 (如 BMP / GIF) 原样缓存在这里，交给 webview 尝试
+fn raw_cover_path(cache_dir: &Path, key: &str) -> PathBuf {
+    cache_dir.join(format!("{}_full.jpg", key))
+}
+
+fn existing_rendition(cache: &CacheManager, key: &str, size: CoverSize, config: &CoverConfig) -> Option<String> {
+    let cache_dir = cache.dir(CacheKind::Covers);
+    let (label, _, format) = size.spec(config);
+    let path = cache_dir.join(format!("{}_{}.{}", key, label, format.extension()));
+    let raw = raw_cover_path(&cache_dir, key);
+    let found = if path.exists() {
+        path
+    } else if matches!(size, CoverSize::Full) && raw.exists() {
+        raw
+    } else {
+        return None;
+    };
+    cache.touch(CacheKind::Covers, &found);
+    Some(found.to_string_lossy().into_owned())
+}
+
+fn write_rendition(cache: &'static CacheManager, key: &str, data: &[u8], size: CoverSize, config: &CoverConfig) -> Option<String> {
+    let cache_dir = cache.dir(CacheKind::Covers);
+    let (label, max_dim, format) = size.spec(config);
+    let (path, bytes) = match render_cover(data, max_dim, format) {
+        Ok(bytes) => (cache_dir.join(format!("{}_{}.{}", key, label, format.extension())), bytes),
+        Err(_) if matches!(size, CoverSize::Full) => (raw_cover_path(&cache_dir, key), data.to_vec()),
+        Err(_) => return None,
+    };
+    fs::write(&path, &bytes).ok()?;
+    cache.record_write(CacheKind::Covers, &path, bytes.len() as u64);
+    Some(path.to_string_lossy().into_owned())
+}
+
+// song_covers 中与当前文件指纹一致的封面键；Some(None) 表示已确认没有封面
+fn mapped_cover_key(conn: &rusqlite::Connection, path: &str, source_hash: &str) -> Option<Option<String>> {
+    conn.query_row(
+        "SELECT c.cover_key FROM song_covers c JOIN songs s ON s.id = c.song_id
+         WHERE s.path = ?1 AND c.source_hash = ?2",
+        (path, source_hash),
+        |row| row.get(0),
+    )
+    .ok()
+}
+
+fn store_cover_key(conn: &rusqlite::Connection, song_id: i64, key: Option<&str>, source_hash: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO song_covers (song_id, cover_key, source_hash) VALUES (?1, ?2, ?3)
+         ON CONFLICT(song_id) DO UPDATE SET cover_key = excluded.cover_key, source_hash = excluded.source_hash",
+        (song_id, key, source_hash),
+    )?;
+    Ok(())
+}
+
+fn get_or_create_rendition(path: &Path, app: &AppHandle, size: CoverSize) -> Option<String> {
+    cached_rendition(path, app, size).map(|(_, cached)| cached)
+}
+
+// 返回 (封面键, 缓存文件路径)
+fn cached_rendition(path: &Path, app: &AppHandle, size: CoverSize) -> Option<(String, String)> {
+    let source = CoverSource::resolve(path, load_cover_config(app));
+    let cache = cache::manager()?;
+    let path_str = path.to_string_lossy();
+    let db = app.try_state::<DbState>();
+
+    // 命中映射时不必读取音频文件
+    let mapped = db.as_ref()
+        .and_then(|db| db.conn.lock().ok().and_then(|conn| mapped_cover_key(&conn, &path_str, &source.hash)));
+    match &mapped {
+        Some(None) => return None,
+        Some(Some(key)) => {
+            if let Some(cached) = existing_rendition(cache, key, size, &source.config) {
+                return Some((key.clone(), cached));
+            }
+        }
+        None => {}
     }
-    
-    if let Ok(tagged_file) = Probe::open(path).ok()?.read() {
-         if let Some(tag) = tagged_file.primary_tag() {
-            let pictures = tag.pictures();
-            let pic_opt = pictures.iter()
-                .find(|p| p.pic_type() == lofty::picture::PictureType::CoverFront)
-                .or(pictures.first());
-
-            if let Some(pic) = pic_opt {
-                if fs::write(&cache_path, pic.data()).is_ok() {
-                    return Some(cache_path.to_string_lossy().into_owned());
-                }
+
+    let data = source.load(|| embedded_cover(path));
+    let key = data.as_deref().map(cover_key);
+    if mapped.as_ref() != Some(&key) {
+        if let Some(conn) = db.as_ref().and_then(|db| db.conn.lock().ok()) {
+            let song_id: Option<i64> = conn
+                .query_row("SELECT id FROM songs WHERE path = ?1", [&*path_str], |row| row.get(0))
+                .ok();
+            if let Some(song_id) = song_id {
+                let _ = store_cover_key(&conn, song_id, key.as_deref(), &source.hash);
             }
-         }
+        }
+    }
+
+    let (key, data) = (key?, data?);
+    let cached = existing_rendition(cache, &key, size, &source.config)
+        .or_else(|| write_rendition(cache, &key, &data, size, &source.config))?;
+    Some((key, cached))
+}
+
+// 取色用 64px 的缓存图，结果按封面键存入 cover_palettes，共用同一封面的歌曲只算一次
+fn get_or_create_palette(path: &Path, app: &AppHandle) -> Option<CoverPalette> {
+    let (key, cached) = cached_rendition(path, app, CoverSize::Standard(COVER_SIZES[0]))?;
+    let db = app.try_state::<DbState>();
+    let stored: Option<String> = db.as_ref().and_then(|db| db.conn.lock().ok()).and_then(|conn| {
+        conn.query_row("SELECT palette FROM cover_palettes WHERE cover_key = ?1", [&key], |row| row.get(0))
+            .ok()
+    });
+    if let Some(palette) = stored.and_then(|json| serde_json::from_str(&json).ok()) {
+        return Some(palette);
+    }
+
+    let palette = extract_palette(&image::open(&cached).ok()?.to_rgb8());
+    if let (Some(conn), Ok(json)) = (db.as_ref().and_then(|db| db.conn.lock().ok()), serde_json::to_string(&palette)) {
+        let _ = conn.execute(
+            "INSERT OR REPLACE INTO cover_palettes (cover_key, palette) VALUES (?1, ?2)",
+            (&key, &json),
+        );
+    }
+    Some(palette)
+}
+
+// 扫描时算出封面键并生成缩略图，缩略图路径写入 songs.cover_path (即 Song.cover)
+pub struct ScannedCover {
+    source_hash: String,
+    key: Option<String>,
+    pub thumbnail: Option<String>,
+}
+
+impl ScannedCover {
+    pub fn prepare(path: &Path, embedded: Option<Vec<u8>>, config: &CoverConfig) -> Self {
+        let source = CoverSource::resolve(path, config.clone());
+        let data = source.load(|| embedded);
+        let key = data.as_deref().map(cover_key);
+        let thumbnail = match (cache::manager(), &key, &data) {
+            (Some(cache), Some(key), Some(data)) => existing_rendition(cache, key, CoverSize::Thumbnail, config)
+                .or_else(|| write_rendition(cache, key, data, CoverSize::Thumbnail, config)),
+            _ => None,
+        };
+        Self { source_hash: source.hash, key, thumbnail }
+    }
+
+    pub fn store(&self, conn: &rusqlite::Connection, song_id: i64) -> rusqlite::Result<()> {
+        store_cover_key(conn, song_id, self.key.as_deref(), &self.source_hash)
     }
-    None
 }
 
 // --- 3. 注入并发控制的 Command ---
@@ -190,7 +400,7 @@ pub async fn get_song_cover_thumbnail(
     let p_buf = p.to_path_buf();
     
     let result = tauri::async_runtime::spawn_blocking(move || {
-        get_or_create_thumbnail(&p_buf, &app_clone)
+        get_or_create_rendition(&p_buf, &app_clone, CoverSize::Thumbnail)
     }).await.map_err(|e| e.to_string())?;
 
     if let Some(cache_path_str) = result {
@@ -212,7 +422,7 @@ pub async fn get_song_cover(
     let p_buf = p.to_path_buf();
 
     let result = tauri::async_runtime::spawn_blocking(move || {
-        get_or_create_full_cover(&p_buf, &app_clone)
+        get_or_create_rendition(&p_buf, &app_clone, CoverSize::Full)
     }).await.map_err(|e| e.to_string())?;
 
     if let Some(cache_path_str) = result {
@@ -221,6 +431,40 @@ pub async fn get_song_cover(
     Ok(String::new())
 }
 
+// size 为最长边像素，向上取到最近的标准尺寸；0 表示完整封面
+#[tauri::command]
+pub async fn get_song_cover_sized(
+    path: String,
+    size: u32,
+    app: AppHandle,
+    semaphore: State<'_, ImageConcurrencyLimit>
+) -> Result<String, String> {
+    let _permit = semaphore.0.acquire().await.map_err(|e| e.to_string())?;
+
+    let p_buf = PathBuf::from(&path);
+    let cover_size = if size == 0 { CoverSize::Full } else { CoverSize::Standard(standard_size(size)) };
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        get_or_create_rendition(&p_buf, &app, cover_size)
+    }).await.map_err(|e| e.to_string())?;
+
+    Ok(result.unwrap_or_default())
+}
+
+// 封面的主色 / 鲜艳色 / 柔和色及文字颜色；没有封面时返回 None
+#[tauri::command]
+pub async fn get_cover_palette(
+    path: String,
+    app: AppHandle,
+    semaphore: State<'_, ImageConcurrencyLimit>
+) -> Result<Option<CoverPalette>, String> {
+    let _permit = semaphore.0.acquire().await.map_err(|e| e.to_string())?;
+
+    let p_buf = PathBuf::from(&path);
+    tauri::async_runtime::spawn_blocking(move || get_or_create_palette(&p_buf, &app))
+        .await
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn scan_music_folder(
     folder_path: String,
@@ -230,107 +474,9 @@ pub async fn scan_music_folder(
     let db_conn = db_state.conn.clone();
     
     let result = tauri::async_runtime::spawn_blocking(move || {
-        let mut songs = Vec::new();
-        let conn = db_conn.lock().map_err(|e| e.to_string())?;
-        
-        for entry in WalkDir::new(folder_path).into_iter().filter_map(|e| e.ok()) {
-            let path = entry.path();
-            if path.is_file() {
-                 if let Some(ext) = path.extension() {
-                    let ext_str = ext.to_string_lossy().to_lowercase();
-                    if ["mp3", "flac", "wav"].contains(&ext_str.as_str()) {
-                        let path_str = path.to_string_lossy().to_string();
-                        let format = ext_str.clone();
-                        
-                        // Read from DB with new columns
-                        let mut stmt = conn.prepare("SELECT title, artist, album, duration, cover_path, bitrate, sample_rate, bit_depth, format FROM songs WHERE path = ?1").unwrap();
-                        let db_song = stmt.query_row([&path_str], |row| {
-                            Ok((
-                                Song {
-                                    name: path.file_name().unwrap().to_string_lossy().to_string(),
-                                    path: path_str.clone(),
-                                    title: row.get(0).unwrap_or_default(),
-                                    artist: row.get(1).unwrap_or_default(),
-                                    album: row.get(2).unwrap_or_default(),
-                                    duration: row.get(3).unwrap_or_default(),
-                                    cover: row.get(4).unwrap_or_default(),
-                                    bitrate: row.get(5).unwrap_or(0),
-                                    sample_rate: row.get(6).unwrap_or(0),
-                                    bit_depth: row.get::<_, Option<u8>>(7).unwrap_or(None),
-                                    format: row.get(8).unwrap_or_else(|_| format.clone()),
-                                },
-                                row.get::<_, Option<u32>>(5).unwrap_or(None), // bitrate for null check
-                            ))
-                        }).optional().unwrap_or(None);
-
-                        if let Some((mut song, db_bitrate)) = db_song {
-                            // Legacy data: if bitrate is NULL, re-extract metadata
-                            if db_bitrate.is_none() {
-                                if let Some(tagged_file) = Probe::open(path).ok().and_then(|p| p.read().ok()) {
-                                    let props = tagged_file.properties();
-                                    song.bitrate = props.audio_bitrate().unwrap_or(0);
-                                    song.sample_rate = props.sample_rate().unwrap_or(0);
-                                    song.bit_depth = props.bit_depth().map(|b| b as u8);
-                                    song.format = format.clone();
-                                    
-                                    // Update DB with new values
-                                    conn.execute(
-                                        "UPDATE songs SET bitrate = ?1, sample_rate = ?2, bit_depth = ?3, format = ?4 WHERE path = ?5",
-                                        (&song.bitrate, &song.sample_rate, &song.bit_depth, &song.format, &path_str),
-                                    ).ok();
-                                }
-                            }
-                            songs.push(song);
-                        } else {
-                            // New file: extract all metadata
-                            let mut artist = String::from("未知歌手");
-                            let mut album = String::from("未知专辑");
-                            let mut title = String::new();
-                            let mut duration = 0u32;
-                            let mut bitrate = 0u32;
-                            let mut sample_rate = 0u32;
-                            let mut bit_depth: Option<u8> = None;
-                            
-                            if let Ok(tagged_file) = Probe::open(path).map_err(|e| e.to_string()).and_then(|p| p.read().map_err(|e| e.to_string())) {
-                                let props = tagged_file.properties();
-                                duration = props.duration().as_secs() as u32;
-                                bitrate = props.audio_bitrate().unwrap_or(0);
-                                sample_rate = props.sample_rate().unwrap_or(0);
-                                bit_depth = props.bit_depth().map(|b| b as u8);
-                                
-                                if let Some(tag) = tagged_file.primary_tag() {
-                                    if let Some(art) = tag.artist() { artist = art.to_string(); }
-                                    if let Some(alb) = tag.album() { album = alb.to_string(); }
-                                    if let Some(tit) = tag.title() { title = tit.to_string(); }
-                                }
-                            }
-                            
-                            let cover_path: Option<String> = None;
-                            
-                            conn.execute(
-                                "INSERT INTO songs (path, title, artist, album, duration, cover_path, bitrate, sample_rate, bit_depth, format) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
-                                (&path_str, &title, &artist, &album, &duration, &cover_path, &bitrate, &sample_rate, &bit_depth, &format),
-                            ).ok();
-
-                            songs.push(Song {
-                                name: path.file_name().unwrap().to_string_lossy().to_string(),
-                                path: path_str,
-                                title,
-                                artist,
-                                album,
-                                duration,
-                                cover: cover_path,
-                                bitrate,
-                                sample_rate,
-                                bit_depth,
-                                format,
-                            });
-                        }
-                    }
-                 }
-            }
-        }
-        Ok::<Vec<Song>, String>(songs)
+        let mut progress = ScanProgress::default();
+        let control = ScanControl { cancel: None, on_progress: &|_: &ScanProgress| {} };
+        scan_folder(&db_conn, Path::new(&folder_path), &mut progress, &control)
     }).await.map_err(|e| e.to_string())??;
     
     Ok(result)
@@ -368,49 +514,81 @@ pub async fn scan_folder_as_playlists(
     Ok(result)
 }
 
+// 内嵌歌词：Lyrics 字段，其次是内容像 LRC 的 Comment
+pub fn embedded_lyrics(tag: &Tag) -> Option<String> {
+    if let Some(lyrics) = tag.get_string(&ItemKey::Lyrics) { return Some(lyrics.to_string()); }
+    for item in tag.items() { if item.key() == &ItemKey::Comment { if let Some(text) = item.value().text() { if text.contains("[00:") { return Some(text.to_string()); } } } }
+    None
+}
+
+// 同目录同名的 .lrc 文件
+pub fn sidecar_lyrics_path(path: &Path) -> Option<PathBuf> {
+    let stem = path.file_stem()?;
+    let lrc_path = path.parent()?.join(format!("{}.lrc", stem.to_string_lossy()));
+    if lrc_path.exists() { Some(lrc_path) } else { None }
+}
+
+// 扫描建索引时用，只做自动检测，不读取用户指定的编码
+pub fn sidecar_lyrics(path: &Path) -> Option<String> {
+    read_lyrics_file(&sidecar_lyrics_path(path)?, None)
+}
+
 #[tauri::command]
-pub async fn get_song_lyrics(path: String) -> Result<String, String> {
+pub async fn get_song_lyrics(path: String, db_state: State<'_, DbState>) -> Result<String, String> {
     if let Ok(tagged_file) = Probe::open(&path).map_err(|e| e.to_string())?.read() {
         if let Some(tag) = tagged_file.primary_tag() {
-            if let Some(lyrics) = tag.get_string(&ItemKey::Lyrics) { return Ok(lyrics.to_string()); }
-            for item in tag.items() { if item.key() == &ItemKey::Comment { if let Some(text) = item.value().text() { if text.contains("[00:") { return Ok(text.to_string()); } } } }
-        }
-    }
-    let path_obj = Path::new(&path);
-    if let Some(stem) = path_obj.file_stem() {
-        if let Some(parent) = path_obj.parent() {
-            let lrc_path = parent.join(format!("{}.lrc", stem.to_string_lossy()));
-            if lrc_path.exists() { if let Ok(content) = fs::read_to_string(lrc_path) { return Ok(content); } }
+            if let Some(lyrics) = embedded_lyrics(tag) { return Ok(lyrics); }
         }
     }
-    Ok(String::new())
+    let Some(lrc_path) = sidecar_lyrics_path(Path::new(&path)) else { return Ok(String::new()) };
+    let forced = encoding_override(&db_state.conn, &lrc_path);
+    Ok(read_lyrics_file(&lrc_path, forced).unwrap_or_default())
+}
+
+// 文件移动/重命名后把数据库中的路径一并改掉，歌曲 id 不变，歌单等引用随之有效。
+// 目标路径上若残留旧记录 (被覆盖的文件)，先删掉以免唯一约束冲突
+pub fn relocate_song(conn: &rusqlite::Connection, old_path: &str, new_path: &str) -> rusqlite::Result<()> {
+    if old_path == new_path { return Ok(()); }
+    conn.execute("DELETE FROM songs WHERE path = ?1", [new_path])?;
+    conn.execute("UPDATE songs SET path = ?1 WHERE path = ?2", (new_path, old_path))?;
+    Ok(())
 }
 
 #[tauri::command]
-pub fn batch_move_music_files(paths: Vec<String>, target_folder: String) -> Result<u32, CommandError> { 
+pub fn batch_move_music_files(paths: Vec<String>, target_folder: String, db_state: State<'_, DbState>) -> Result<u32, CommandError> { 
     let mut success_count = 0; 
     let target = Path::new(&target_folder); 
     if !target.exists() || !target.is_dir() { 
         return Err(CommandError::new("TARGET_NOT_FOUND", "目标文件夹不存在")); 
     } 
+    let conn = db_state.conn.lock().map_err(|e| CommandError::new("DB_LOCK", &e.to_string()))?;
     for path_str in paths { 
         let src = Path::new(&path_str); 
         if let Some(file_name) = src.file_name() { 
             let dest = target.join(file_name); 
-            if fs::rename(src, &dest).is_ok() { success_count += 1; } 
+            if fs::rename(src, &dest).is_ok() { 
+                success_count += 1; 
+                if let Err(e) = relocate_song(&conn, &path_str, &dest.to_string_lossy()) { eprintln!("更新歌曲路径失败: {}", e); }
+            } 
         } 
     } 
     Ok(success_count) 
 }
 
 #[tauri::command]
-pub fn move_music_file(old_path: String, new_path: String) -> Result<(), String> { 
-    let src = Path::new(&old_path); 
-    let dest = Path::new(&new_path); 
+pub fn move_music_file(old_path: String, new_path: String, db_state: State<'_, DbState>) -> Result<(), String> { 
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    move_song_file(&conn, &old_path, &new_path)
+}
+
+// 移动文件并同步曲库路径，播放记录、歌单等按 id 关联，随之保留
+pub fn move_song_file(conn: &rusqlite::Connection, old_path: &str, new_path: &str) -> Result<(), String> {
+    let src = Path::new(old_path); 
+    let dest = Path::new(new_path); 
     if !src.exists() { return Err("源文件不存在".to_string()); } 
     if let Some(parent) = dest.parent() { if !parent.exists() { fs::create_dir_all(parent).map_err(|e| e.to_string())?; } } 
     fs::rename(src, dest).map_err(|e| e.to_string())?; 
-    Ok(()) 
+    relocate_song(conn, old_path, new_path).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -427,4 +605,14 @@ pub fn show_in_folder(path: String) {
 fn child_dummy() -> std::process::Child { Command::new("true").spawn().unwrap() }
 
 #[tauri::command]
-pub fn delete_music_file(path: String) -> Result<(), String> { fs::remove_file(path).map_err(|e| e.to_string()) }
\ No newline at end of file
+pub fn delete_music_file(path: String, db_state: State<'_, DbState>) -> Result<(), String> { 
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    delete_song_file(&conn, &path)
+}
+
+pub fn delete_song_file(conn: &rusqlite::Connection, path: &str) -> Result<(), String> {
+    fs::remove_file(path).map_err(|e| e.to_string())?; 
+    // 歌单条目、艺人关联等随外键级联删除
+    conn.execute("DELETE FROM songs WHERE path = ?1", [path]).map_err(|e| e.to_string())?;
+    Ok(()) 
+}
\ No newline at end of file
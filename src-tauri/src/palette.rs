@@ -0,0 +1,201 @@
+use image::RgbImage;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+
+// --- 封面取色：主色、鲜艳色、柔和色，以及叠在其上的文字颜色 ---
+// 按 RGB 各 4 位量化统计像素，合并相近的桶得到色板，再按饱和度 / 亮度的目标值挑选
+// 结果按封面键缓存在 cover_palettes 表中，见 music::get_cover_palette
+
+const MAX_SWATCHES: usize = 6;
+// 两个色块的 RGB 距离小于该值视为同一种颜色
+const MERGE_DISTANCE: f32 = 40.0;
+// 过暗 / 过亮的像素不参与取色 (0..255 的感知亮度)
+const MIN_BRIGHTNESS: f32 = 20.0;
+const MAX_BRIGHTNESS: f32 = 240.0;
+const FALLBACK_COLOR: [u8; 3] = [0x9c, 0xa3, 0xaf];
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Swatch {
+    // "#rrggbb"
+    pub color: String,
+    // 占参与取色像素的比例 0..1
+    pub population: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CoverPalette {
+    pub dominant: String,
+    pub vibrant: String,
+    pub muted: String,
+    // 对应底色上对比度更高的文字颜色：#ffffff 或 #000000
+    pub text_on_dominant: String,
+    pub text_on_vibrant: String,
+    pub text_on_muted: String,
+    // 按占比从高到低
+    pub swatches: Vec<Swatch>,
+}
+
+#[derive(Clone, Copy)]
+struct Cluster {
+    sum: [f32; 3],
+    count: u32,
+}
+
+impl Cluster {
+    fn color(&self) -> [f32; 3] {
+        self.sum.map(|c| c / self.count as f32)
+    }
+}
+
+fn hex(rgb: [f32; 3]) -> String {
+    let [r, g, b] = rgb.map(|c| c.round().clamp(0.0, 255.0) as u8);
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+fn distance(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum::<f32>().sqrt()
+}
+
+// (色相 0..360, 饱和度 0..1, 亮度 0..1)
+fn to_hsl(rgb: [f32; 3]) -> (f32, f32, f32) {
+    let [r, g, b] = rgb.map(|c| c / 255.0);
+    let (max, min) = (r.max(g).max(b), r.min(g).min(b));
+    let (l, d) = ((max + min) / 2.0, max - min);
+    if d < f32::EPSILON {
+        return (0.0, 0.0, l);
+    }
+    let s = if l > 0.5 { d / (2.0 - max - min) } else { d / (max + min) };
+    let h = if max == r {
+        (g - b) / d + if g < b { 6.0 } else { 0.0 }
+    } else if max == g {
+        (b - r) / d + 2.0
+    } else {
+        (r - g) / d + 4.0
+    };
+    (h * 60.0, s, l)
+}
+
+fn from_hsl(h: f32, s: f32, l: f32) -> [f32; 3] {
+    let c = (1.0 - (2.0 * l - 1.0).abs()) * s;
+    let x = c * (1.0 - ((h / 60.0) % 2.0 - 1.0).abs());
+    let m = l - c / 2.0;
+    let (r, g, b) = match (h.rem_euclid(360.0) / 60.0) as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    [r, g, b].map(|v| (v + m) * 255.0)
+}
+
+// WCAG 相对亮度
+fn luminance(rgb: [f32; 3]) -> f32 {
+    let [r, g, b] = rgb.map(|c| {
+        let c = c / 255.0;
+        if c <= 0.03928 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+    });
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+// 白字与黑字中对比度更高的一个
+fn text_color(background: [f32; 3]) -> String {
+    let l = luminance(background);
+    let on_white = 1.05 / (l + 0.05);
+    let on_black = (l + 0.05) / 0.05;
+    if on_white >= on_black { "#ffffff" } else { "#000000" }.to_string()
+}
+
+// 与 Android Palette 类似的打分：越接近目标饱和度 / 亮度越好，占比作为次要因素
+fn pick(
+    swatches: &[([f32; 3], f32)],
+    target_s: f32,
+    target_l: f32,
+    accept: impl Fn(f32, f32) -> bool,
+) -> Option<[f32; 3]> {
+    swatches
+        .iter()
+        .filter_map(|(rgb, population)| {
+            let (_, s, l) = to_hsl(*rgb);
+            accept(s, l).then(|| {
+                let score = (1.0 - (s - target_s).abs()) * 3.0 + (1.0 - (l - target_l).abs()) * 6.0 + population;
+                (*rgb, score)
+            })
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(rgb, _)| rgb)
+}
+
+// 按 RGB 各取高 4 位分桶；skip_extremes 时跳过过暗 / 过亮的像素
+fn histogram(img: &RgbImage, skip_extremes: bool) -> (Vec<Cluster>, u32) {
+    let mut buckets = vec![Cluster { sum: [0.0; 3], count: 0 }; 4096];
+    let mut total = 0u32;
+    for pixel in img.pixels() {
+        let [r, g, b] = pixel.0;
+        let brightness = (r as f32 * 299.0 + g as f32 * 587.0 + b as f32 * 114.0) / 1000.0;
+        if skip_extremes && !(MIN_BRIGHTNESS..=MAX_BRIGHTNESS).contains(&brightness) {
+            continue;
+        }
+        let bucket = &mut buckets[((r as usize >> 4) << 8) | ((g as usize >> 4) << 4) | (b as usize >> 4)];
+        for (sum, c) in bucket.sum.iter_mut().zip(pixel.0) {
+            *sum += c as f32;
+        }
+        bucket.count += 1;
+        total += 1;
+    }
+    (buckets, total)
+}
+
+pub fn extract_palette(img: &RgbImage) -> CoverPalette {
+    let (mut buckets, mut total) = histogram(img, true);
+    // 整张图都是黑白等极端颜色时，退回到不过滤
+    if total == 0 {
+        (buckets, total) = histogram(img, false);
+    }
+
+    // 从占比最高的桶开始，把相近的桶并入已有色块
+    buckets.retain(|b| b.count > 0);
+    buckets.sort_by_key(|b| Reverse(b.count));
+    let mut clusters: Vec<Cluster> = Vec::new();
+    for bucket in buckets {
+        match clusters.iter_mut().find(|c| distance(c.color(), bucket.color()) < MERGE_DISTANCE) {
+            Some(cluster) => {
+                for (sum, add) in cluster.sum.iter_mut().zip(bucket.sum) {
+                    *sum += add;
+                }
+                cluster.count += bucket.count;
+            }
+            None => clusters.push(bucket),
+        }
+    }
+    clusters.sort_by_key(|c| Reverse(c.count));
+
+    let swatches: Vec<([f32; 3], f32)> = clusters
+        .iter()
+        .map(|c| (c.color(), c.count as f32 / total.max(1) as f32))
+        .collect();
+    let dominant = swatches.first().map(|s| s.0).unwrap_or(FALLBACK_COLOR.map(f32::from));
+    let (h, s, l) = to_hsl(dominant);
+
+    // 找不到合适色块时由主色调整饱和度 / 亮度得到；黑白灰封面不凭空加色相
+    let vibrant_s = if s < 0.1 { s } else { s.max(0.6) };
+    let vibrant = pick(&swatches, 1.0, 0.5, |s, l| s >= 0.35 && (0.3..=0.7).contains(&l))
+        .unwrap_or_else(|| from_hsl(h, vibrant_s, l.clamp(0.4, 0.6)));
+    let muted = pick(&swatches, 0.3, 0.5, |s, l| s <= 0.4 && (0.3..=0.7).contains(&l))
+        .unwrap_or_else(|| from_hsl(h, s.min(0.25), l.clamp(0.35, 0.65)));
+
+    CoverPalette {
+        dominant: hex(dominant),
+        vibrant: hex(vibrant),
+        muted: hex(muted),
+        text_on_dominant: text_color(dominant),
+        text_on_vibrant: text_color(vibrant),
+        text_on_muted: text_color(muted),
+        swatches: swatches
+            .iter()
+            .take(MAX_SWATCHES)
+            .map(|(rgb, population)| Swatch { color: hex(*rgb), population: *population })
+            .collect(),
+    }
+}
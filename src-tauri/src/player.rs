@@ -1,6 +1,6 @@
 use std::sync::{Arc, Mutex};
 use std::sync::atomic::{AtomicU64, AtomicU32, Ordering};
-use std::sync::mpsc::{channel, Sender};
+use std::sync::mpsc::{channel, Sender, RecvTimeoutError};
 use std::thread;
 use std::fs::File;
 use std::io::BufReader;
@@ -11,6 +11,8 @@ use raw_window_handle::{HasWindowHandle, RawWindowHandle};
 use souvlaki::{MediaControls, PlatformConfig, MediaMetadata, MediaControlEvent, MediaPlayback, MediaPosition};
 use cpal::traits::{HostTrait, DeviceTrait};
 use serde::Serialize;
+use crate::database::DbState;
+use crate::history::PlayTracker;
 
 pub struct TimedSource<S> { pub inner: S, pub samples_played: Arc<AtomicU64> }
 impl<S> Iterator for TimedSource<S> where S: Source<Item = f32> { type Item = f32; fn next(&mut self) -> Option<Self::Item> { let sample = self.inner.next(); if sample.is_some() { self.samples_played.fetch_add(1, Ordering::Relaxed); } sample } }
@@ -18,6 +20,9 @@ impl<S> Source for TimedSource<S> where S: Source<Item = f32> { fn channels(&sel
 
 pub struct SharedProgress { pub samples_played: Arc<AtomicU64>, pub sample_rate: Arc<AtomicU32>, pub channels: Arc<AtomicU32> }
 
+// 音频线程空闲时检查一次是否自然播完，用于记录完整播放
+const PLAYBACK_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
 pub enum AudioCommand { Play(String), Pause, Resume, Seek(u32, bool), SetVolume(f32), SetDevice(String) }
 
 pub struct PlayerState {
@@ -57,6 +62,7 @@ pub fn init_player(app: &AppHandle) -> PlayerState {
     let (tx, rx) = channel::<AudioCommand>();
     let shared_progress = Arc::new(SharedProgress { samples_played: Arc::new(AtomicU64::new(0)), sample_rate: Arc::new(AtomicU32::new(44100)), channels: Arc::new(AtomicU32::new(2)) });
     let thread_progress = shared_progress.clone();
+    let history_db = app.try_state::<DbState>().map(|db| db.conn.clone());
 
     // Initialize MediaControls
     let controls = Arc::new(Mutex::new(None));
@@ -124,15 +130,27 @@ pub fn init_player(app: &AppHandle) -> PlayerState {
         let mut current_path: String = String::new();
         let mut current_volume: f32 = 1.0;
         let mut is_playing_flag = false;
+        let mut tracker = PlayTracker::new(history_db);
 
         // Try to create initial sink
         if let Some((_, ref handle)) = stream_data {
              current_sink = Sink::try_new(handle).ok();
         }
 
-        while let Ok(cmd) = rx.recv() {
+        loop {
+            let cmd = match rx.recv_timeout(PLAYBACK_CHECK_INTERVAL) {
+                Ok(cmd) => cmd,
+                Err(RecvTimeoutError::Timeout) => {
+                    if tracker.is_active() && current_sink.as_ref().map(|sink| sink.empty()).unwrap_or(false) {
+                        tracker.finish(true);
+                    }
+                    continue;
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
             match cmd {
                 AudioCommand::Play(path) => {
+                    tracker.finish(false);
                     current_path = path.clone();
                     is_playing_flag = true;
                     if let Some((_, ref handle)) = stream_data {
@@ -153,6 +171,7 @@ pub fn init_player(app: &AppHandle) -> PlayerState {
                                     sink.append(timed_source); 
                                     sink.set_volume(current_volume); 
                                     sink.play(); 
+                                    tracker.start(&current_path);
                                 }
                             }
                         }
@@ -160,15 +179,18 @@ pub fn init_player(app: &AppHandle) -> PlayerState {
                 }
                 AudioCommand::Pause => { 
                     is_playing_flag = false;
+                    tracker.set_playing(false);
                     if let Some(sink) = &current_sink { sink.pause(); } 
                 }
                 AudioCommand::Resume => { 
                     is_playing_flag = true;
+                    tracker.set_playing(true);
                     if let Some(sink) = &current_sink { sink.play(); } 
                 }
                 AudioCommand::Seek(time, is_playing) => {
                     let jump_target = Duration::from_secs(time as u64);
                     is_playing_flag = is_playing;
+                    tracker.set_playing(is_playing);
                     if !current_path.is_empty() {
                         if let Some((_, ref handle)) = stream_data {
                             if let Some(sink) = &current_sink { sink.stop(); }
@@ -0,0 +1,372 @@
+use crate::database::DbState;
+use crate::music::{song_columns_with, Song, SONG_COLUMN_COUNT};
+use rusqlite::{Connection, OptionalExtension};
+use serde::Serialize;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::State;
+
+// --- 歌单：playlists + playlist_items (按 song_id 引用，移动/重命名文件后依然有效) ---
+// 条目自身有 id，同一首歌允许在歌单中出现多次，删除/排序按条目 id 操作
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PlaylistSummary {
+    pub id: i64,
+    pub name: String,
+    pub track_count: u32,
+    pub total_duration: u64,
+    // 毫秒时间戳
+    pub created_at: i64,
+    pub updated_at: i64,
+    // 第一首歌，可用于 get_song_cover_thumbnail
+    pub cover_song_path: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PlaylistItem {
+    pub item_id: i64,
+    pub position: u32,
+    pub added_at: i64,
+    pub song: Song,
+}
+
+pub fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+fn touch(conn: &Connection, playlist_id: i64) -> rusqlite::Result<()> {
+    let changed = conn.execute(
+        "UPDATE playlists SET updated_at = ?1 WHERE id = ?2",
+        (now_millis(), playlist_id),
+    )?;
+    if changed == 0 {
+        return Err(rusqlite::Error::QueryReturnedNoRows);
+    }
+    Ok(())
+}
+
+fn item_ids_in_order(conn: &Connection, playlist_id: i64) -> rusqlite::Result<Vec<i64>> {
+    let mut stmt = conn.prepare("SELECT id FROM playlist_items WHERE playlist_id = ?1 ORDER BY position, id")?;
+    let ids = stmt.query_map([playlist_id], |row| row.get(0))?.collect();
+    ids
+}
+
+fn write_positions(conn: &Connection, item_ids: &[i64]) -> rusqlite::Result<()> {
+    let mut stmt = conn.prepare("UPDATE playlist_items SET position = ?1 WHERE id = ?2")?;
+    for (position, id) in item_ids.iter().enumerate() {
+        stmt.execute((position as i64, id))?;
+    }
+    Ok(())
+}
+
+// 删除、去重后把 position 重新压成 0..n
+pub fn compact_positions(conn: &Connection, playlist_id: i64) -> rusqlite::Result<()> {
+    let ids = item_ids_in_order(conn, playlist_id)?;
+    write_positions(conn, &ids)
+}
+
+pub fn create(conn: &Connection, name: &str) -> rusqlite::Result<i64> {
+    let now = now_millis();
+    conn.execute(
+        "INSERT INTO playlists (name, position, created_at, updated_at)
+         VALUES (?1, (SELECT COALESCE(MAX(position) + 1, 0) FROM playlists), ?2, ?2)",
+        (name, now),
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+// 追加到末尾或插入到 position 处；allow_duplicates 为 false 时跳过已在歌单中的歌
+pub fn add_songs(
+    conn: &Connection,
+    playlist_id: i64,
+    song_ids: &[i64],
+    position: Option<u32>,
+    allow_duplicates: bool,
+) -> rusqlite::Result<u32> {
+    let tx = conn.unchecked_transaction()?;
+    touch(&tx, playlist_id)?;
+    let len: u32 = tx.query_row(
+        "SELECT COUNT(*) FROM playlist_items WHERE playlist_id = ?1",
+        [playlist_id],
+        |row| row.get(0),
+    )?;
+    let mut at = position.unwrap_or(len).min(len);
+
+    let mut added = 0;
+    let now = now_millis();
+    for &song_id in song_ids {
+        if !allow_duplicates {
+            let exists = tx
+                .query_row(
+                    "SELECT 1 FROM playlist_items WHERE playlist_id = ?1 AND song_id = ?2",
+                    (playlist_id, song_id),
+                    |_| Ok(()),
+                )
+                .optional()?
+                .is_some();
+            if exists {
+                continue;
+            }
+        }
+        tx.execute(
+            "UPDATE playlist_items SET position = position + 1 WHERE playlist_id = ?1 AND position >= ?2",
+            (playlist_id, at),
+        )?;
+        tx.execute(
+            "INSERT INTO playlist_items (playlist_id, song_id, position, added_at) VALUES (?1, ?2, ?3, ?4)",
+            (playlist_id, song_id, at, now),
+        )?;
+        at += 1;
+        added += 1;
+    }
+    tx.commit()?;
+    Ok(added)
+}
+
+pub fn query_playlists(conn: &Connection) -> rusqlite::Result<Vec<PlaylistSummary>> {
+    let mut stmt = conn.prepare(
+        "SELECT p.id, p.name, p.created_at, p.updated_at,
+                COUNT(i.id), COALESCE(SUM(s.duration), 0),
+                (SELECT s2.path FROM playlist_items i2 JOIN songs s2 ON s2.id = i2.song_id
+                 WHERE i2.playlist_id = p.id ORDER BY i2.position LIMIT 1)
+         FROM playlists p
+         LEFT JOIN playlist_items i ON i.playlist_id = p.id
+         LEFT JOIN songs s ON s.id = i.song_id
+         GROUP BY p.id
+         ORDER BY p.position, p.id",
+    )?;
+    let playlists = stmt
+        .query_map([], |row| {
+            Ok(PlaylistSummary {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                created_at: row.get(2)?,
+                updated_at: row.get(3)?,
+                track_count: row.get(4)?,
+                total_duration: row.get::<_, i64>(5)? as u64,
+                cover_song_path: row.get(6)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(playlists)
+}
+
+pub fn query_playlist_items(conn: &Connection, playlist_id: i64) -> rusqlite::Result<Vec<PlaylistItem>> {
+    let sql = format!(
+        "SELECT {}, i.id, i.position, i.added_at
+         FROM playlist_items i
+         JOIN songs s ON s.id = i.song_id
+         WHERE i.playlist_id = ?1
+         ORDER BY i.position, i.id",
+        song_columns_with("s")
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let items = stmt
+        .query_map([playlist_id], |row| {
+            Ok(PlaylistItem {
+                song: Song::from_row(row)?,
+                item_id: row.get(SONG_COLUMN_COUNT)?,
+                position: row.get(SONG_COLUMN_COUNT + 1)?,
+                added_at: row.get(SONG_COLUMN_COUNT + 2)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(items)
+}
+
+// --- Commands ---
+
+#[tauri::command]
+pub async fn get_playlists(db_state: State<'_, DbState>) -> Result<Vec<PlaylistSummary>, String> {
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    query_playlists(&conn).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_playlist_tracks(playlist_id: i64, db_state: State<'_, DbState>) -> Result<Vec<PlaylistItem>, String> {
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    query_playlist_items(&conn, playlist_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn create_playlist(
+    name: String,
+    song_ids: Option<Vec<i64>>,
+    db_state: State<'_, DbState>,
+) -> Result<i64, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("歌单名称不能为空".to_string());
+    }
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    let id = create(&conn, name).map_err(|e| e.to_string())?;
+    if let Some(song_ids) = song_ids {
+        add_songs(&conn, id, &song_ids, None, false).map_err(|e| e.to_string())?;
+    }
+    Ok(id)
+}
+
+#[tauri::command]
+pub async fn rename_playlist(playlist_id: i64, name: String, db_state: State<'_, DbState>) -> Result<(), String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("歌单名称不能为空".to_string());
+    }
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    conn.execute(
+        "UPDATE playlists SET name = ?1, updated_at = ?2 WHERE id = ?3",
+        (name, now_millis(), playlist_id),
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn delete_playlist(playlist_id: i64, db_state: State<'_, DbState>) -> Result<(), String> {
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM playlists WHERE id = ?1", [playlist_id])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// 侧栏中歌单自身的顺序，playlist_ids 为完整的新顺序
+#[tauri::command]
+pub async fn reorder_playlists(playlist_ids: Vec<i64>, db_state: State<'_, DbState>) -> Result<(), String> {
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    for (position, id) in playlist_ids.iter().enumerate() {
+        tx.execute("UPDATE playlists SET position = ?1 WHERE id = ?2", (position as i64, id))
+            .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn add_to_playlist(
+    playlist_id: i64,
+    song_ids: Vec<i64>,
+    position: Option<u32>,
+    allow_duplicates: Option<bool>,
+    db_state: State<'_, DbState>,
+) -> Result<u32, String> {
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    add_songs(&conn, playlist_id, &song_ids, position, allow_duplicates.unwrap_or(false))
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn remove_from_playlist(
+    playlist_id: i64,
+    item_ids: Vec<i64>,
+    db_state: State<'_, DbState>,
+) -> Result<u32, String> {
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    touch(&tx, playlist_id).map_err(|e| e.to_string())?;
+    let mut removed = 0;
+    for item_id in item_ids {
+        removed += tx
+            .execute(
+                "DELETE FROM playlist_items WHERE id = ?1 AND playlist_id = ?2",
+                (item_id, playlist_id),
+            )
+            .map_err(|e| e.to_string())? as u32;
+    }
+    compact_positions(&tx, playlist_id).map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(removed)
+}
+
+// 把一组条目移动到 to_position (按移动前的下标计)，保持它们的相对顺序
+#[tauri::command]
+pub async fn reorder_playlist(
+    playlist_id: i64,
+    item_ids: Vec<i64>,
+    to_position: u32,
+    db_state: State<'_, DbState>,
+) -> Result<(), String> {
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    let current = item_ids_in_order(&conn, playlist_id).map_err(|e| e.to_string())?;
+
+    let to = (to_position as usize).min(current.len());
+    let insert_at = current[..to].iter().filter(|id| !item_ids.contains(*id)).count();
+    let moved: Vec<i64> = current.iter().copied().filter(|id| item_ids.contains(id)).collect();
+    let mut order: Vec<i64> = current.into_iter().filter(|id| !item_ids.contains(id)).collect();
+    order.splice(insert_at..insert_at, moved);
+
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    touch(&tx, playlist_id).map_err(|e| e.to_string())?;
+    write_positions(&tx, &order).map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+// 去掉重复的歌，每首只保留最靠前的一条，返回删除的条目数
+#[tauri::command]
+pub async fn dedupe_playlist(playlist_id: i64, db_state: State<'_, DbState>) -> Result<u32, String> {
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    touch(&tx, playlist_id).map_err(|e| e.to_string())?;
+    let removed = tx
+        .execute(
+            "DELETE FROM playlist_items
+             WHERE playlist_id = ?1
+               AND id NOT IN (
+                   SELECT id FROM (
+                       SELECT id, ROW_NUMBER() OVER (PARTITION BY song_id ORDER BY position, id) AS rn
+                       FROM playlist_items WHERE playlist_id = ?1
+                   ) WHERE rn = 1
+               )",
+            [playlist_id],
+        )
+        .map_err(|e| e.to_string())?;
+    compact_positions(&tx, playlist_id).map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(removed as u32)
+}
+
+// 复制歌单 (含条目顺序)，新歌单排在原歌单之后，返回新 id
+#[tauri::command]
+pub async fn duplicate_playlist(
+    playlist_id: i64,
+    name: Option<String>,
+    db_state: State<'_, DbState>,
+) -> Result<i64, String> {
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let (source_name, source_position): (String, i64) = tx
+        .query_row(
+            "SELECT name, position FROM playlists WHERE id = ?1",
+            [playlist_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| e.to_string())?;
+    let name = name
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| format!("{} (副本)", source_name));
+
+    let now = now_millis();
+    tx.execute(
+        "UPDATE playlists SET position = position + 1 WHERE position > ?1",
+        [source_position],
+    )
+    .map_err(|e| e.to_string())?;
+    tx.execute(
+        "INSERT INTO playlists (name, position, created_at, updated_at) VALUES (?1, ?2, ?3, ?3)",
+        (&name, source_position + 1, now),
+    )
+    .map_err(|e| e.to_string())?;
+    let new_id = tx.last_insert_rowid();
+    tx.execute(
+        "INSERT INTO playlist_items (playlist_id, song_id, position, added_at)
+         SELECT ?1, song_id, position, added_at FROM playlist_items WHERE playlist_id = ?2",
+        (new_id, playlist_id),
+    )
+    .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(new_id)
+}
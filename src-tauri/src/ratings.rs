@@ -0,0 +1,313 @@
+use crate::database::DbState;
+use crate::music::{song_columns_with, Song};
+use crate::playlists::now_millis;
+use crate::settings::{load_setting, save_setting};
+use lofty::config::{ParseOptions, WriteOptions};
+use lofty::file::FileType;
+use lofty::id3::v2::{Frame, Id3v2Tag, PopularimeterFrame};
+use lofty::iff::wav::WavFile;
+use lofty::mpeg::MpegFile;
+use lofty::prelude::*;
+use lofty::probe::Probe;
+use lofty::tag::{ItemKey, Tag, TagType};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::path::Path;
+use tauri::State;
+
+// --- 收藏与评分：按歌曲 id 存在数据库中，评分可选写回文件标签 ---
+// ID3v2 -> POPM (rating 0..255，沿用 Windows Media Player 的星级对应)；POPM 不会进入 lofty 的通用 Tag，
+//          直接读写 Id3v2Tag，按邮箱字段只替换自己的那一帧，其他播放器写的 POPM 保留
+// Vorbis -> RATING (0..100)，MP4 -> iTunes 的 rate 原子 (0..100)，在 lofty 中都对应 ItemKey::Popularimeter
+
+const RATING_CONFIG_KEY: &str = "ratings";
+const POPM_EMAIL: &str = "Windows Media Player 9 Series";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RatingConfig {
+    // 设置评分时同时写入文件标签，默认关闭
+    pub write_to_file: bool,
+}
+
+fn stars_to_popm(stars: u8) -> u8 {
+    match stars {
+        0 => 0,
+        1 => 1,
+        2 => 64,
+        3 => 128,
+        4 => 196,
+        _ => 255,
+    }
+}
+
+fn popm_to_stars(rating: u8) -> u8 {
+    match rating {
+        0 => 0,
+        1..=31 => 1,
+        32..=95 => 2,
+        96..=159 => 3,
+        160..=223 => 4,
+        _ => 5,
+    }
+}
+
+// 0..100 的百分制，部分软件直接写 1..5 星
+fn percent_to_stars(value: &str) -> Option<u8> {
+    let value: u32 = value.trim().parse().ok()?;
+    Some(match value {
+        0..=5 => value as u8,
+        _ => ((value.min(100) + 10) / 20) as u8,
+    })
+}
+
+// 写入评分前读取 MP3 / WAV 中的 ID3v2 标签；其他格式返回 None
+fn read_id3v2(path: &Path) -> Result<Option<Id3v2Tag>, String> {
+    let options = ParseOptions::new().read_properties(false);
+    let mut file = File::open(path).map_err(|e| e.to_string())?;
+    let tag = match FileType::from_path(path) {
+        Some(FileType::Mpeg) => MpegFile::read_from(&mut file, options).map_err(|e| e.to_string())?.id3v2().cloned(),
+        Some(FileType::Wav) => WavFile::read_from(&mut file, options).map_err(|e| e.to_string())?.id3v2().cloned(),
+        _ => return Ok(None),
+    };
+    Ok(Some(tag.unwrap_or_default()))
+}
+
+// 优先取自己写入的 POPM，没有时取第一个有评分的
+fn popm_rating(tag: &Id3v2Tag) -> Option<u8> {
+    let ratings: Vec<(&str, u8)> = tag
+        .into_iter()
+        .filter_map(|frame| match frame {
+            Frame::Popularimeter(popm) if popm.rating > 0 => Some((popm.email.as_str(), popm.rating)),
+            _ => None,
+        })
+        .collect();
+    ratings
+        .iter()
+        .find(|(email, _)| *email == POPM_EMAIL)
+        .or_else(|| ratings.first())
+        .map(|(_, rating)| *rating)
+}
+
+// 扫描时从已读出的标签取评分，没有评分字段时返回 None。
+// 通用 Tag 里的 POPM 帧随 ID3v2 附带标签保留，转回 Id3v2Tag 即可取到，不必再读一次文件
+pub fn read_rating(tag: &Tag) -> Option<u8> {
+    let stars = match tag.tag_type() {
+        TagType::Id3v2 => popm_rating(&Id3v2Tag::from(tag.clone())).map(popm_to_stars),
+        TagType::VorbisComments | TagType::Mp4Ilst => tag.get_string(&ItemKey::Popularimeter).and_then(percent_to_stars),
+        _ => None,
+    }?;
+    Some(stars.min(5))
+}
+
+fn write_popm(path: &Path, mut tag: Id3v2Tag, stars: u8) -> Result<(), String> {
+    if stars > 0 {
+        // 同一邮箱的 POPM 会被替换，保留原有的播放计数
+        let counter = (&tag)
+            .into_iter()
+            .find_map(|frame| match frame {
+                Frame::Popularimeter(popm) if popm.email == POPM_EMAIL => Some(popm.counter),
+                _ => None,
+            })
+            .unwrap_or(0);
+        let popm = PopularimeterFrame::new(POPM_EMAIL.to_string(), stars_to_popm(stars), counter);
+        tag.insert(Frame::Popularimeter(popm));
+    } else {
+        tag.retain(|frame| !matches!(frame, Frame::Popularimeter(popm) if popm.email == POPM_EMAIL));
+    }
+    tag.save_to_path(path, WriteOptions::default()).map_err(|e| e.to_string())
+}
+
+pub fn write_rating(path: &Path, stars: u8) -> Result<(), String> {
+    if let Some(tag) = read_id3v2(path)? {
+        return write_popm(path, tag, stars);
+    }
+
+    let mut tagged_file = Probe::open(path)
+        .and_then(|p| p.read())
+        .map_err(|e| e.to_string())?;
+    let tag_type = tagged_file.primary_tag_type();
+    if !matches!(tag_type, TagType::VorbisComments | TagType::Mp4Ilst) {
+        return Err(format!("不支持写入评分的标签类型: {:?}", tag_type));
+    }
+    if tagged_file.tag(tag_type).is_none() {
+        tagged_file.insert_tag(Tag::new(tag_type));
+    }
+    let tag = tagged_file.tag_mut(tag_type).ok_or("无法创建标签")?;
+
+    tag.remove_key(&ItemKey::Popularimeter);
+    if stars > 0 && !tag.insert_text(ItemKey::Popularimeter, (u32::from(stars) * 20).to_string()) {
+        return Err(format!("{:?} 标签不接受评分字段", tag_type));
+    }
+    tag.save_to_path(path, WriteOptions::default()).map_err(|e| e.to_string())
+}
+
+pub fn query_favorites(conn: &Connection) -> rusqlite::Result<Vec<Song>> {
+    let sql = format!(
+        "SELECT {} FROM favorites f JOIN songs s ON s.id = f.song_id ORDER BY f.added_at DESC",
+        song_columns_with("s")
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let songs = stmt
+        .query_map([], Song::from_row)?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(songs)
+}
+
+fn set_favorite_state(conn: &Connection, song_id: i64, favorite: bool) -> rusqlite::Result<()> {
+    if favorite {
+        conn.execute(
+            "INSERT OR IGNORE INTO favorites (song_id, added_at) VALUES (?1, ?2)",
+            (song_id, now_millis()),
+        )?;
+    } else {
+        conn.execute("DELETE FROM favorites WHERE song_id = ?1", [song_id])?;
+    }
+    Ok(())
+}
+
+// --- Commands ---
+
+#[tauri::command]
+pub async fn get_favorites(db_state: State<'_, DbState>) -> Result<Vec<Song>, String> {
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    query_favorites(&conn).map_err(|e| e.to_string())
+}
+
+// 返回切换后的状态
+#[tauri::command]
+pub async fn toggle_favorite(song_id: i64, db_state: State<'_, DbState>) -> Result<bool, String> {
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    let is_favorite: bool = conn
+        .query_row("SELECT EXISTS(SELECT 1 FROM favorites WHERE song_id = ?1)", [song_id], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    set_favorite_state(&conn, song_id, !is_favorite).map_err(|e| e.to_string())?;
+    Ok(!is_favorite)
+}
+
+#[tauri::command]
+pub async fn set_favorite(song_id: i64, favorite: bool, db_state: State<'_, DbState>) -> Result<(), String> {
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    set_favorite_state(&conn, song_id, favorite).map_err(|e| e.to_string())
+}
+
+// rating 为 0..=5，0 表示清除评分
+#[tauri::command]
+pub async fn set_rating(song_id: i64, rating: u8, db_state: State<'_, DbState>) -> Result<(), String> {
+    if rating > 5 {
+        return Err("评分必须在 0 到 5 之间".to_string());
+    }
+    let (path, config) = {
+        let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "UPDATE songs SET rating = ?1 WHERE id = ?2",
+            (if rating == 0 { None } else { Some(rating) }, song_id),
+        )
+        .map_err(|e| e.to_string())?;
+        let path: String = conn
+            .query_row("SELECT path FROM songs WHERE id = ?1", [song_id], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        (path, load_setting::<RatingConfig>(&conn, RATING_CONFIG_KEY))
+    };
+
+    // 写文件较慢，不占用数据库锁
+    if config.write_to_file {
+        tauri::async_runtime::spawn_blocking(move || write_rating(Path::new(&path), rating))
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| format!("评分已保存，但写入文件失败: {}", e))?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_rating_config(db_state: State<'_, DbState>) -> Result<RatingConfig, String> {
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    Ok(load_setting(&conn, RATING_CONFIG_KEY))
+}
+
+#[tauri::command]
+pub fn set_rating_config(config: RatingConfig, db_state: State<'_, DbState>) -> Result<(), String> {
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    save_setting(&conn, RATING_CONFIG_KEY, &config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    // 10 个 MPEG-1 Layer III 128kbps 空帧
+    fn mp3_bytes() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for _ in 0..10 {
+            bytes.extend_from_slice(&[0xFF, 0xFB, 0x90, 0x64]);
+            bytes.extend(std::iter::repeat_n(0u8, 413));
+        }
+        bytes
+    }
+
+    // STREAMINFO (44.1kHz / 双声道 / 16bit) + PADDING，后跟一个帧头
+    fn flac_bytes() -> Vec<u8> {
+        let mut bytes = b"fLaC".to_vec();
+        bytes.extend_from_slice(&[0x00, 0, 0, 34]);
+        bytes.extend_from_slice(&[0x10, 0x00, 0x10, 0x00, 0, 0, 0, 0, 0, 0]);
+        bytes.extend_from_slice(&[0x0A, 0xC4, 0x42, 0xF0, 0, 0, 0, 0]);
+        bytes.extend_from_slice(&[0; 16]);
+        bytes.extend_from_slice(&[0x81, 0, 0, 16]);
+        bytes.extend_from_slice(&[0; 16]);
+        bytes.extend_from_slice(&[0xFF, 0xF8, 0x69, 0x08, 0, 0, 0, 0]);
+        bytes
+    }
+
+    fn rating_of(path: &Path) -> Option<u8> {
+        let tagged_file = Probe::open(path).unwrap().read().unwrap();
+        read_rating(tagged_file.primary_tag()?)
+    }
+
+    #[test]
+    fn star_mapping_round_trips() {
+        for stars in 0..=5 {
+            assert_eq!(popm_to_stars(stars_to_popm(stars)), stars);
+        }
+        assert_eq!(percent_to_stars("60"), Some(3));
+        assert_eq!(percent_to_stars("4"), Some(4));
+        assert_eq!(percent_to_stars("x"), None);
+    }
+
+    #[test]
+    fn mp3_rating_round_trips_and_keeps_foreign_popm() {
+        let dir = TempDir::new("ratings_test");
+        let path = dir.write("rating.mp3", &mp3_bytes());
+        let mut tag = Id3v2Tag::default();
+        tag.insert(Frame::Popularimeter(PopularimeterFrame::new("other@player".to_string(), 255, 7)));
+        tag.save_to_path(&path, WriteOptions::default()).unwrap();
+
+        write_rating(&path, 4).unwrap();
+        assert_eq!(rating_of(&path), Some(4));
+        write_rating(&path, 2).unwrap();
+        assert_eq!(rating_of(&path), Some(2));
+
+        let tag = read_id3v2(&path).unwrap().unwrap();
+        let popms = tag.into_iter().filter(|frame| matches!(frame, Frame::Popularimeter(_))).count();
+        assert_eq!(popms, 2);
+
+        // 清除后回落到其他播放器写的 POPM
+        write_rating(&path, 0).unwrap();
+        assert_eq!(rating_of(&path), Some(5));
+    }
+
+    #[test]
+    fn flac_rating_round_trips() {
+        let dir = TempDir::new("ratings_test");
+        let path = dir.write("rating.flac", &flac_bytes());
+        write_rating(&path, 3).unwrap();
+        assert_eq!(rating_of(&path), Some(3));
+        write_rating(&path, 5).unwrap();
+        assert_eq!(rating_of(&path), Some(5));
+        write_rating(&path, 0).unwrap();
+        assert_eq!(rating_of(&path), None);
+    }
+}
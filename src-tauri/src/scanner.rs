@@ -0,0 +1,397 @@
+use crate::albums::{prune_empty_albums, upsert_album};
+use crate::artists::{link_song_tags, prune_orphans, ArtistSplitConfig};
+use crate::artwork::CoverConfig;
+use crate::database::DbState;
+use crate::metadata::{read_metadata, SongMetadata, METADATA_VERSION};
+use crate::music::{ScannedCover, Song, SONG_COLUMNS, SONG_COLUMN_COUNT};
+use crate::search::index_song;
+use rusqlite::{Connection, OptionalExtension};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, State};
+use walkdir::WalkDir;
+
+pub const SUPPORTED_EXTENSIONS: [&str; 3] = ["mp3", "flac", "wav"];
+
+// 每批处理的文件数：一批一个事务，取消时已提交的批次会保留在库里
+const BATCH_SIZE: usize = 100;
+// 进度事件的最小间隔，避免大库扫描时把前端淹没
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+// 进度事件里最多携带的错误条数
+const MAX_REPORTED_ERRORS: usize = 100;
+
+// --- 扫描任务表 (job_id -> 取消标记) ---
+#[derive(Default)]
+pub struct ScanJobs {
+    next_id: AtomicU64,
+    jobs: Mutex<HashMap<u64, Arc<AtomicBool>>>,
+}
+
+impl ScanJobs {
+    fn register(&self) -> (u64, Arc<AtomicBool>) {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let flag = Arc::new(AtomicBool::new(false));
+        if let Ok(mut jobs) = self.jobs.lock() {
+            jobs.insert(id, flag.clone());
+        }
+        (id, flag)
+    }
+
+    fn finish(&self, id: u64) {
+        if let Ok(mut jobs) = self.jobs.lock() {
+            jobs.remove(&id);
+        }
+    }
+
+    fn cancel(&self, id: u64) -> bool {
+        match self.jobs.lock() {
+            Ok(jobs) => match jobs.get(&id) {
+                Some(flag) => {
+                    flag.store(true, Ordering::Relaxed);
+                    true
+                }
+                None => false,
+            },
+            Err(_) => false,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ScanError {
+    pub path: String,
+    pub message: String,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct ScanProgress {
+    pub job_id: u64,
+    pub files_seen: u64,
+    pub files_processed: u64,
+    pub current_path: String,
+    pub error_count: u64,
+    pub errors: Vec<ScanError>,
+    pub finished: bool,
+    pub cancelled: bool,
+}
+
+impl ScanProgress {
+    fn push_error(&mut self, path: &Path, message: String) {
+        self.error_count += 1;
+        if self.errors.len() < MAX_REPORTED_ERRORS {
+            self.errors.push(ScanError {
+                path: path.to_string_lossy().into_owned(),
+                message,
+            });
+        }
+    }
+}
+
+// 扫描过程中的控制面：取消标记 + 进度回调
+pub struct ScanControl<'a> {
+    pub cancel: Option<&'a AtomicBool>,
+    pub on_progress: &'a dyn Fn(&ScanProgress),
+}
+
+impl ScanControl<'_> {
+    fn is_cancelled(&self) -> bool {
+        self.cancel.map(|c| c.load(Ordering::Relaxed)).unwrap_or(false)
+    }
+}
+
+fn is_supported(path: &Path) -> Option<String> {
+    let ext = path.extension()?.to_string_lossy().to_lowercase();
+    if SUPPORTED_EXTENSIONS.contains(&ext.as_str()) {
+        Some(ext)
+    } else {
+        None
+    }
+}
+
+// 一次扫描内共享的配置，在扫描开始时从数据库读取一次
+pub struct ScanOptions {
+    pub split: ArtistSplitConfig,
+    pub covers: CoverConfig,
+}
+
+impl ScanOptions {
+    pub fn load(conn: &Connection) -> Self {
+        Self {
+            split: ArtistSplitConfig::load(conn),
+            covers: CoverConfig::load(conn),
+        }
+    }
+}
+
+// --- 单个文件：命中数据库且元数据版本最新则直接读取，否则用 lofty 解析并入库 ---
+// 分成三步，便于批量扫描时只在查库与写库时持有数据库锁：
+// cached_song (查库) -> parse_file (解析标签、生成封面缩略图，不碰数据库) -> store_parsed (写库)
+
+// 解析完成、等待写库的文件
+pub struct ParsedFile {
+    path: PathBuf,
+    format: String,
+    meta: SongMetadata,
+    cover: ScannedCover,
+    file_size: Option<i64>,
+}
+
+// 库里的记录已是最新元数据版本时返回该歌曲，否则返回 None 表示需要重新解析
+pub fn cached_song(conn: &Connection, path: &Path) -> Result<Option<Song>, String> {
+    let path_str = path.to_string_lossy().to_string();
+
+    let sql = format!("SELECT {}, meta_version, file_size FROM songs WHERE path = ?1", SONG_COLUMNS);
+    let mut stmt = conn.prepare_cached(&sql).map_err(|e| e.to_string())?;
+    let db_song = stmt
+        .query_row([&path_str], |row| {
+            Ok((
+                Song::from_row(row)?,
+                row.get::<_, Option<i64>>(SONG_COLUMN_COUNT)?,
+                row.get::<_, Option<i64>>(SONG_COLUMN_COUNT + 1)?,
+            ))
+        })
+        .optional()
+        .map_err(|e| e.to_string())?;
+
+    match db_song {
+        Some((song, Some(version), stored_size)) if version >= METADATA_VERSION => {
+            // 文件大小不影响标签，旧行直接补上即可
+            if stored_size.is_none() {
+                if let Ok(m) = std::fs::metadata(path) {
+                    conn.execute("UPDATE songs SET file_size = ?1 WHERE id = ?2", (m.len() as i64, song.id))
+                        .map_err(|e| e.to_string())?;
+                }
+            }
+            Ok(Some(song))
+        }
+        _ => Ok(None),
+    }
+}
+
+// New file or legacy row: extract all metadata
+pub fn parse_file(path: &Path, format: &str, options: &ScanOptions) -> Result<ParsedFile, String> {
+    let mut meta = read_metadata(path)?;
+    let cover = ScannedCover::prepare(path, meta.cover.take(), &options.covers);
+    Ok(ParsedFile {
+        path: path.to_path_buf(),
+        format: format.to_string(),
+        meta,
+        cover,
+        file_size: std::fs::metadata(path).map(|m| m.len() as i64).ok(),
+    })
+}
+
+pub fn store_parsed(conn: &Connection, parsed: &ParsedFile, options: &ScanOptions) -> Result<Song, String> {
+    let ParsedFile { path, format, meta, cover, file_size } = parsed;
+    let path_str = path.to_string_lossy().to_string();
+    let cover_path = cover.thumbnail.clone();
+    let link_tags = meta.link_tags();
+    let link_tags_json = serde_json::to_string(&link_tags).map_err(|e| e.to_string())?;
+    let album_id = upsert_album(
+        conn,
+        &meta.album,
+        meta.album_artist.as_deref().unwrap_or(&meta.artist),
+        &meta.album_sort,
+        &meta.album_artist_sort,
+    )
+    .map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "INSERT INTO songs (path, title, artist, album, duration, cover_path, bitrate, sample_rate, bit_depth, format,
+            album_artist, genre, year, track_number, track_total, disc_number, disc_total, composer, album_id,
+            title_sort, artist_sort, album_sort, album_artist_sort, meta_version, rating, file_size, link_tags)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27)
+         ON CONFLICT(path) DO UPDATE SET
+            title = excluded.title, artist = excluded.artist, album = excluded.album, duration = excluded.duration,
+            bitrate = excluded.bitrate, sample_rate = excluded.sample_rate, bit_depth = excluded.bit_depth, format = excluded.format,
+            album_artist = excluded.album_artist, genre = excluded.genre, year = excluded.year,
+            track_number = excluded.track_number, track_total = excluded.track_total,
+            disc_number = excluded.disc_number, disc_total = excluded.disc_total,
+            composer = excluded.composer, album_id = excluded.album_id,
+            title_sort = excluded.title_sort, artist_sort = excluded.artist_sort,
+            album_sort = excluded.album_sort, album_artist_sort = excluded.album_artist_sort,
+            meta_version = excluded.meta_version,
+            rating = COALESCE(excluded.rating, songs.rating),
+            file_size = excluded.file_size, cover_path = excluded.cover_path, link_tags = excluded.link_tags",
+        rusqlite::params![
+            &path_str, &meta.title, &meta.artist, &meta.album, &meta.duration, &cover_path,
+            &meta.bitrate, &meta.sample_rate, &meta.bit_depth, format,
+            &meta.album_artist, &meta.genre, &meta.year, &meta.track_number, &meta.track_total,
+            &meta.disc_number, &meta.disc_total, &meta.composer, album_id,
+            &meta.title_sort, &meta.artist_sort, &meta.album_sort, &meta.album_artist_sort, METADATA_VERSION,
+            &meta.rating, file_size, &link_tags_json,
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+
+    // 回读整行，保留播放统计等不由标签决定的列
+    let song = conn
+        .query_row(&format!("SELECT {} FROM songs WHERE path = ?1", SONG_COLUMNS), [&path_str], Song::from_row)
+        .map_err(|e| e.to_string())?;
+    let song_id = song.id;
+    cover.store(conn, song_id).map_err(|e| e.to_string())?;
+    link_song_tags(conn, song_id, &link_tags, &options.split).map_err(|e| e.to_string())?;
+    index_song(conn, song_id, path, meta).map_err(|e| e.to_string())?;
+
+    Ok(song)
+}
+
+// --- 扫描主流程：先枚举文件，再分批入库 ---
+// 每一批先持锁查出已是最新的文件，再放开锁解析标签、生成缩略图，最后持锁一次性写入，
+// 解析期间其他命令仍可读写数据库
+pub fn scan_folder(
+    conn: &Arc<Mutex<Connection>>,
+    folder_path: &Path,
+    progress: &mut ScanProgress,
+    control: &ScanControl,
+) -> Result<Vec<Song>, String> {
+    let mut songs = Vec::new();
+    let mut last_emit = Instant::now();
+    let mut files: Vec<(PathBuf, String)> = Vec::new();
+    let options = {
+        let conn = conn.lock().map_err(|e| e.to_string())?;
+        ScanOptions::load(&conn)
+    };
+
+    // 1. 枚举阶段
+    for entry in WalkDir::new(folder_path) {
+        if control.is_cancelled() {
+            progress.cancelled = true;
+            return Ok(songs);
+        }
+        let entry = match entry {
+            Ok(e) => e,
+            Err(e) => {
+                let p = e.path().map(|p| p.to_path_buf()).unwrap_or_default();
+                progress.push_error(&p, e.to_string());
+                continue;
+            }
+        };
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        if let Some(format) = is_supported(entry.path()) {
+            progress.files_seen += 1;
+            progress.current_path = entry.path().to_string_lossy().into_owned();
+            files.push((entry.into_path(), format));
+
+            if last_emit.elapsed() >= PROGRESS_INTERVAL {
+                (control.on_progress)(progress);
+                last_emit = Instant::now();
+            }
+        }
+    }
+
+    // 2. 入库阶段
+    for batch in files.chunks(BATCH_SIZE) {
+        if control.is_cancelled() {
+            progress.cancelled = true;
+            break;
+        }
+
+        // a. 查库 (持锁)
+        let cached: Vec<Result<Option<Song>, String>> = {
+            let mut guard = conn.lock().map_err(|e| e.to_string())?;
+            let tx = guard.transaction().map_err(|e| e.to_string())?;
+            let cached = batch.iter().map(|(path, _)| cached_song(&tx, path)).collect();
+            tx.commit().map_err(|e| e.to_string())?;
+            cached
+        };
+
+        // b. 解析标签与封面 (不持锁)；slots 按文件顺序放结果，parsed 记下待写入文件对应的位置
+        let mut slots: Vec<Option<Song>> = Vec::with_capacity(batch.len());
+        let mut parsed: Vec<(usize, ParsedFile)> = Vec::new();
+        for ((path, format), cached) in batch.iter().zip(cached) {
+            if control.is_cancelled() {
+                progress.cancelled = true;
+                break;
+            }
+            progress.current_path = path.to_string_lossy().into_owned();
+            match cached.and_then(|song| match song {
+                Some(song) => Ok(Some(song)),
+                None => parse_file(path, format, &options).map(|file| {
+                    parsed.push((slots.len(), file));
+                    None
+                }),
+            }) {
+                Ok(song) => slots.push(song),
+                Err(e) => {
+                    progress.push_error(path, e);
+                    slots.push(None);
+                }
+            }
+            progress.files_processed += 1;
+
+            if last_emit.elapsed() >= PROGRESS_INTERVAL {
+                (control.on_progress)(progress);
+                last_emit = Instant::now();
+            }
+        }
+
+        // c. 写库 (持锁，一批一个事务)；取消时已解析的文件也写入
+        if !parsed.is_empty() {
+            let mut guard = conn.lock().map_err(|e| e.to_string())?;
+            let tx = guard.transaction().map_err(|e| e.to_string())?;
+            for (slot, file) in &parsed {
+                match store_parsed(&tx, file, &options) {
+                    Ok(song) => slots[*slot] = Some(song),
+                    Err(e) => progress.push_error(&file.path, e),
+                }
+            }
+            tx.commit().map_err(|e| e.to_string())?;
+        }
+        songs.extend(slots.into_iter().flatten());
+    }
+
+    if progress.files_processed > 0 {
+        let conn = conn.lock().map_err(|e| e.to_string())?;
+        prune_orphans(&conn).map_err(|e| e.to_string())?;
+        prune_empty_albums(&conn).map_err(|e| e.to_string())?;
+    }
+
+    Ok(songs)
+}
+
+// --- 后台扫描任务 ---
+
+#[tauri::command]
+pub fn start_scan(
+    folder_path: String,
+    app: AppHandle,
+    db_state: State<'_, DbState>,
+    jobs: State<'_, Arc<ScanJobs>>,
+) -> Result<u64, String> {
+    let db_conn = db_state.conn.clone();
+    let jobs = jobs.inner().clone();
+    let (job_id, cancel_flag) = jobs.register();
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut progress = ScanProgress { job_id, ..Default::default() };
+        let emit = |p: &ScanProgress| {
+            let _ = app.emit("scan:progress", p);
+        };
+        let control = ScanControl {
+            cancel: Some(&cancel_flag),
+            on_progress: &emit,
+        };
+
+        if let Err(e) = scan_folder(&db_conn, Path::new(&folder_path), &mut progress, &control) {
+            progress.push_error(Path::new(&folder_path), e);
+        }
+
+        progress.finished = true;
+        emit(&progress);
+        jobs.finish(job_id);
+    });
+
+    Ok(job_id)
+}
+
+#[tauri::command]
+pub fn cancel_scan(job_id: u64, jobs: State<'_, Arc<ScanJobs>>) -> Result<bool, String> {
+    Ok(jobs.cancel(job_id))
+}
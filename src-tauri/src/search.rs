@@ -0,0 +1,241 @@
+use crate::database::{escape_like, DbState};
+use crate::metadata::SongMetadata;
+use crate::music::{song_columns_with, Song};
+use regex::Regex;
+use rusqlite::functions::FunctionFlags;
+use rusqlite::types::Value;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::path::Path;
+use std::sync::OnceLock;
+use tauri::State;
+
+// trigram 分词器：子串匹配 (覆盖前缀匹配)、大小写与变音符不敏感、中文可直接检索。
+// 少于 3 个字符的词无法命中 trigram 索引，退化为 LIKE 过滤，两边都经 search_fold 折叠以保持一致
+const MIN_TRIGRAM_CHARS: usize = 3;
+// 短词逐行调用 search_fold，只查这几个短列；歌词等长文本只走 trigram 索引
+const SHORT_TERM_COLUMNS: [&str; 3] = ["title", "artist", "album"];
+
+// bm25 列权重，顺序与 songs_fts 的列一致
+const BM25_WEIGHTS: &str = "10.0, 6.0, 4.0, 3.0, 2.0, 1.0, 0.5";
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SearchResults {
+    pub total: u32,
+    pub songs: Vec<Song>,
+}
+
+// 去掉 LRC 时间轴与标签行，只保留可检索的歌词文本
+fn lyrics_plain_text(lyrics: &str) -> String {
+    static TAG_RE: OnceLock<Regex> = OnceLock::new();
+    let re = TAG_RE.get_or_init(|| Regex::new(r"\[[^\]]*\]|<\d+:\d+(?:[.:]\d+)?>").unwrap());
+    lyrics
+        .lines()
+        .map(|line| re.replace_all(line, "").trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// --- 变音符折叠 ---
+// 与 FTS5 的 remove_diacritics 一样只处理拉丁字母：带变音符的字母映射为基本字母，'.' 表示保持原样
+
+const LATIN1_BASE: &str = "aaaaaa.ceeeeiiiidnooooo.ouuuuy..aaaaaa.ceeeeiiiidnooooo.ouuuuy.y";
+const LATIN_EXT_A_BASE: &str =
+    "aaaaaaccccccccddddeeeeeeeeeegggggggghhhhiiiiiiiiii..jjkk.llllllllllnnnnnn...oooooo..rrrrrrssssssssttttttuuuuuuuuuuuuwwyyyzzzzzzs";
+
+fn fold_char(c: char) -> Option<char> {
+    let base = match c as u32 {
+        // 组合用变音符 (NFD 形式的输入) 直接去掉
+        0x0300..=0x036F => return None,
+        code @ 0x00C0..=0x00FF => LATIN1_BASE.as_bytes()[(code - 0x00C0) as usize],
+        code @ 0x0100..=0x017F => LATIN_EXT_A_BASE.as_bytes()[(code - 0x0100) as usize],
+        _ => b'.',
+    };
+    Some(if base == b'.' { c } else { base as char })
+}
+
+// 小写并去掉变音符，用于短词 LIKE 的两侧
+pub fn fold_text(text: &str) -> String {
+    text.chars().flat_map(char::to_lowercase).filter_map(fold_char).collect()
+}
+
+// 注册 SQL 函数 search_fold(text)，打开连接后调用一次
+pub fn register_functions(conn: &Connection) -> rusqlite::Result<()> {
+    conn.create_scalar_function(
+        "search_fold",
+        1,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| Ok(ctx.get::<Option<String>>(0)?.map(|text| fold_text(&text))),
+    )
+}
+
+// --- 索引维护 ---
+
+pub fn index_song(conn: &Connection, song_id: i64, path: &Path, meta: &SongMetadata) -> rusqlite::Result<()> {
+    let file_name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let lyrics = meta.lyrics.as_deref().map(lyrics_plain_text);
+
+    conn.execute("DELETE FROM songs_fts WHERE rowid = ?1", [song_id])?;
+    conn.execute(
+        "INSERT INTO songs_fts (rowid, title, artist, album, album_artist, genre, file_name, lyrics)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        rusqlite::params![
+            song_id, &meta.title, &meta.artist, &meta.album, &meta.album_artist, &meta.genre, &file_name, &lyrics,
+        ],
+    )?;
+    Ok(())
+}
+
+// --- 查询构造 ---
+
+// 把用户输入拆成 FTS5 短语 (AND 连接) 与短词 LIKE 条件
+fn build_query(query: &str) -> (Option<String>, Vec<String>) {
+    let mut phrases = Vec::new();
+    let mut short_terms = Vec::new();
+    for term in query.split_whitespace() {
+        if term.chars().count() >= MIN_TRIGRAM_CHARS {
+            phrases.push(format!("\"{}\"", term.replace('"', "\"\"")));
+        } else {
+            short_terms.push(format!("%{}%", escape_like(&fold_text(term))));
+        }
+    }
+    let match_expr = if phrases.is_empty() { None } else { Some(phrases.join(" AND ")) };
+    (match_expr, short_terms)
+}
+
+// songs_fts 上的 WHERE 条件与参数，空查询返回 None
+fn fts_condition(query: &str) -> Option<(String, Vec<Value>, bool)> {
+    let (match_expr, short_terms) = build_query(query);
+    if match_expr.is_none() && short_terms.is_empty() {
+        return None;
+    }
+
+    let mut conditions = Vec::new();
+    let mut params: Vec<Value> = Vec::new();
+    let ranked = match_expr.is_some();
+    if let Some(expr) = match_expr {
+        conditions.push("songs_fts MATCH ?".to_string());
+        params.push(Value::Text(expr));
+    }
+    for term in &short_terms {
+        let any_column = SHORT_TERM_COLUMNS
+            .iter()
+            .map(|c| format!("search_fold(songs_fts.{}) LIKE ? ESCAPE '\\'", c))
+            .collect::<Vec<_>>()
+            .join(" OR ");
+        conditions.push(format!("({})", any_column));
+        for _ in SHORT_TERM_COLUMNS {
+            params.push(Value::Text(term.clone()));
+        }
+    }
+    Some((conditions.join(" AND "), params, ranked))
+}
+
+// 供其他列表查询复用的过滤条件：alias.id IN (全文检索命中)
+pub fn song_id_filter(alias: &str, query: &str) -> Option<(String, Vec<Value>)> {
+    let (where_clause, params, _) = fts_condition(query)?;
+    Some((format!("{}.id IN (SELECT rowid FROM songs_fts WHERE {})", alias, where_clause), params))
+}
+
+pub fn search(conn: &Connection, query: &str, limit: u32, offset: u32) -> rusqlite::Result<SearchResults> {
+    let Some((where_clause, mut params, ranked)) = fts_condition(query) else {
+        return Ok(SearchResults { total: 0, songs: Vec::new() });
+    };
+
+    let total: u32 = conn.query_row(
+        &format!("SELECT COUNT(*) FROM songs_fts WHERE {}", where_clause),
+        rusqlite::params_from_iter(params.iter()),
+        |row| row.get(0),
+    )?;
+
+    let order_by = if ranked {
+        format!("bm25(songs_fts, {})", BM25_WEIGHTS)
+    } else {
+        "s.title COLLATE NOCASE".to_string()
+    };
+    let sql = format!(
+        "SELECT {} FROM songs_fts
+         JOIN songs s ON s.id = songs_fts.rowid
+         WHERE {}
+         ORDER BY {}
+         LIMIT ? OFFSET ?",
+        song_columns_with("s"),
+        where_clause,
+        order_by
+    );
+    params.push(Value::Integer(limit as i64));
+    params.push(Value::Integer(offset as i64));
+
+    let mut stmt = conn.prepare(&sql)?;
+    let songs = stmt
+        .query_map(rusqlite::params_from_iter(params.iter()), Song::from_row)?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(SearchResults { total, songs })
+}
+
+// --- Commands ---
+
+#[tauri::command]
+pub async fn search_library(
+    query: String,
+    limit: Option<u32>,
+    offset: Option<u32>,
+    db_state: State<'_, DbState>,
+) -> Result<SearchResults, String> {
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    search(&conn, &query, limit.unwrap_or(50), offset.unwrap_or(0)).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base_tables_cover_their_ranges() {
+        assert_eq!(LATIN1_BASE.len(), 0x40);
+        assert_eq!(LATIN_EXT_A_BASE.len(), 0x80);
+    }
+
+    #[test]
+    fn folds_case_and_diacritics() {
+        assert_eq!(fold_text("Beyoncé"), "beyonce");
+        assert_eq!(fold_text("ÆON Łódź"), "æon lodz");
+        // NFD 形式：e + U+0301
+        assert_eq!(fold_text("Cafe\u{301}"), "cafe");
+        assert_eq!(fold_text("周杰伦"), "周杰伦");
+    }
+
+    #[test]
+    fn short_terms_match_across_diacritics() {
+        let conn = Connection::open_in_memory().unwrap();
+        register_functions(&conn).unwrap();
+        conn.execute_batch(
+            "CREATE VIRTUAL TABLE songs_fts USING fts5(
+                title, artist, album, album_artist, genre, file_name, lyrics,
+                tokenize = 'trigram remove_diacritics 1'
+            );
+            INSERT INTO songs_fts (rowid, title) VALUES (1, 'Né'), (2, 'Ne'), (3, 'Other');
+            INSERT INTO songs_fts (rowid, album) VALUES (4, 'Nè');
+            INSERT INTO songs_fts (rowid, title, lyrics) VALUES (5, 'Lyrics only', 'ne ne ne');",
+        )
+        .unwrap();
+
+        for query in ["né", "NE"] {
+            let (where_clause, params, ranked) = fts_condition(query).unwrap();
+            assert!(!ranked);
+            let mut stmt = conn
+                .prepare(&format!("SELECT rowid FROM songs_fts WHERE {} ORDER BY rowid", where_clause))
+                .unwrap();
+            let ids: Vec<i64> = stmt
+                .query_map(rusqlite::params_from_iter(params.iter()), |row| row.get(0))
+                .unwrap()
+                .map(|r| r.unwrap())
+                .collect();
+            // 短词不查歌词列
+            assert_eq!(ids, [1, 2, 4], "query {:?}", query);
+        }
+    }
+}
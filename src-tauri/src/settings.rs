@@ -0,0 +1,30 @@
+use rusqlite::{Connection, OptionalExtension};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+// --- 后端配置项：app_settings 表中的 key -> JSON ---
+// 读取失败或未设置时返回类型的默认值，保证旧库/脏数据不影响启动
+
+pub fn load_setting<T: DeserializeOwned + Default>(conn: &Connection, key: &str) -> T {
+    conn.query_row(
+        "SELECT value FROM app_settings WHERE key = ?1",
+        [key],
+        |row| row.get::<_, String>(0),
+    )
+    .optional()
+    .ok()
+    .flatten()
+    .and_then(|json| serde_json::from_str(&json).ok())
+    .unwrap_or_default()
+}
+
+pub fn save_setting<T: Serialize>(conn: &Connection, key: &str, value: &T) -> Result<(), String> {
+    let json = serde_json::to_string(value).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO app_settings (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        (key, &json),
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
@@ -0,0 +1,579 @@
+use crate::database::{escape_like, DbState};
+use crate::music::{song_columns_with, Song};
+use crate::playlists::now_millis;
+use rusqlite::types::Value;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+// --- 智能歌单：规则以 JSON 存储，取歌时编译成带参数的 SQL ---
+// 例：{"match":"all","rules":[{"field":"genre","op":"is","value":"Jazz"},
+//      {"field":"rating","op":">=","value":4},{"field":"last_played","op":"not_in_last","value":30,"unit":"days"}],
+//      "sort":{"field":"random"},"limit":{"tracks":50,"minutes":180}}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchMode {
+    #[default]
+    All,
+    Any,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operator {
+    #[serde(alias = "=")]
+    Is,
+    #[serde(alias = "!=")]
+    IsNot,
+    Contains,
+    NotContains,
+    StartsWith,
+    EndsWith,
+    #[serde(alias = ">")]
+    Gt,
+    #[serde(alias = ">=")]
+    Gte,
+    #[serde(alias = "<")]
+    Lt,
+    #[serde(alias = "<=")]
+    Lte,
+    // value 为 [下限, 上限]，含两端
+    Between,
+    // 时间字段：最近 N 个单位内 / 不在最近 N 个单位内 (含从未发生)
+    InLast,
+    NotInLast,
+    IsEmpty,
+    IsNotEmpty,
+    IsTrue,
+    IsFalse,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeUnit {
+    Hours,
+    #[default]
+    Days,
+    Weeks,
+    Months,
+}
+
+impl TimeUnit {
+    fn millis(self) -> i64 {
+        const HOUR: i64 = 3_600_000;
+        match self {
+            TimeUnit::Hours => HOUR,
+            TimeUnit::Days => 24 * HOUR,
+            TimeUnit::Weeks => 7 * 24 * HOUR,
+            TimeUnit::Months => 30 * 24 * HOUR,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rule {
+    pub field: String,
+    pub op: Operator,
+    #[serde(default)]
+    pub value: serde_json::Value,
+    // in_last / not_in_last 的单位
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<TimeUnit>,
+    // play_count / skip_count 只统计最近 N 天的播放记录
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub within_days: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleGroup {
+    #[serde(rename = "match", default)]
+    pub mode: MatchMode,
+    pub rules: Vec<RuleNode>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RuleNode {
+    Rule(Rule),
+    Group(RuleGroup),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmartSort {
+    // 任一可排序字段，或 "random"
+    pub field: String,
+    #[serde(default)]
+    pub desc: bool,
+}
+
+// 两个上限同时给出时先到者为准
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SmartLimit {
+    pub tracks: Option<u32>,
+    pub minutes: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SmartRules {
+    #[serde(rename = "match", default)]
+    pub mode: MatchMode,
+    #[serde(default)]
+    pub rules: Vec<RuleNode>,
+    #[serde(default)]
+    pub sort: Option<SmartSort>,
+    #[serde(default)]
+    pub limit: Option<SmartLimit>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SmartPlaylist {
+    pub id: i64,
+    pub name: String,
+    pub rules: SmartRules,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+// --- 字段表：规则字段名 -> SQL 表达式 (songs 别名为 s) ---
+
+enum FieldKind {
+    Text,
+    // 多值字段：songs 上的拼接值，或 artists / genres 关联表中任一名字
+    Multi { link: &'static str },
+    Number,
+    // 毫秒时间戳
+    Date,
+    Flag,
+}
+
+struct Field {
+    expr: &'static str,
+    kind: FieldKind,
+    // 排序用的列 (排序键优先)，None 表示不可排序
+    sort: Option<&'static str>,
+}
+
+const ARTIST_LINK: &str = "SELECT 1 FROM song_artists sa JOIN artists a ON a.id = sa.artist_id \
+    WHERE sa.song_id = s.id AND sa.role = 'artist' AND {}";
+const ALBUM_ARTIST_LINK: &str = "SELECT 1 FROM song_artists sa JOIN artists a ON a.id = sa.artist_id \
+    WHERE sa.song_id = s.id AND sa.role = 'album_artist' AND {}";
+const GENRE_LINK: &str = "SELECT 1 FROM song_genres sg JOIN genres g ON g.id = sg.genre_id \
+    WHERE sg.song_id = s.id AND {}";
+
+fn lookup_field(name: &str) -> Option<Field> {
+    let (expr, kind, sort) = match name {
+        "title" => ("s.title", FieldKind::Text, Some("title_sort")),
+        "album" => ("s.album", FieldKind::Text, Some("album_sort")),
+        "composer" => ("s.composer", FieldKind::Text, Some("composer")),
+        "format" => ("s.format", FieldKind::Text, Some("format")),
+        "path" => ("s.path", FieldKind::Text, Some("path")),
+        "artist" => ("s.artist", FieldKind::Multi { link: ARTIST_LINK }, Some("artist_sort")),
+        "album_artist" => ("s.album_artist", FieldKind::Multi { link: ALBUM_ARTIST_LINK }, Some("album_artist_sort")),
+        "genre" => ("s.genre", FieldKind::Multi { link: GENRE_LINK }, Some("genre")),
+        "duration" => ("s.duration", FieldKind::Number, Some("duration")),
+        "bitrate" => ("s.bitrate", FieldKind::Number, Some("bitrate")),
+        "sample_rate" => ("s.sample_rate", FieldKind::Number, Some("sample_rate")),
+        "bit_depth" => ("s.bit_depth", FieldKind::Number, Some("bit_depth")),
+        "year" => ("s.year", FieldKind::Number, Some("year")),
+        "track_number" => ("s.track_number", FieldKind::Number, Some("track_number")),
+        "track_total" => ("s.track_total", FieldKind::Number, Some("track_total")),
+        "disc_number" => ("s.disc_number", FieldKind::Number, Some("disc_number")),
+        "disc_total" => ("s.disc_total", FieldKind::Number, Some("disc_total")),
+        "play_count" => ("s.play_count", FieldKind::Number, Some("play_count")),
+        "skip_count" => ("s.skip_count", FieldKind::Number, Some("skip_count")),
+        "rating" => ("COALESCE(s.rating, 0)", FieldKind::Number, Some("rating")),
+        "last_played" => ("s.last_played", FieldKind::Date, Some("last_played")),
+        "favorite" => ("EXISTS(SELECT 1 FROM favorites f WHERE f.song_id = s.id)", FieldKind::Flag, None),
+        _ => return None,
+    };
+    Some(Field { expr, kind, sort })
+}
+
+// --- 规则编译 ---
+
+fn text_value(rule: &Rule) -> Result<String, String> {
+    match &rule.value {
+        serde_json::Value::String(s) => Ok(s.clone()),
+        serde_json::Value::Number(n) => Ok(n.to_string()),
+        _ => Err(format!("字段 {} 需要文本值", rule.field)),
+    }
+}
+
+fn number_value(field: &str, value: &serde_json::Value) -> Result<Value, String> {
+    match value {
+        serde_json::Value::Number(n) => Ok(match n.as_i64() {
+            Some(i) => Value::Integer(i),
+            None => Value::Real(n.as_f64().unwrap_or(0.0)),
+        }),
+        serde_json::Value::String(s) => s
+            .trim()
+            .parse::<f64>()
+            .map(Value::Real)
+            .map_err(|_| format!("字段 {} 需要数值", field)),
+        _ => Err(format!("字段 {} 需要数值", field)),
+    }
+}
+
+// 文本比较 (仅正向)，返回条件模板 ("{}" 处填入被比较的表达式) 与参数
+fn text_condition(op: &Operator, value: String) -> Option<(&'static str, String)> {
+    Some(match op {
+        Operator::Is | Operator::IsNot => ("{} = ? COLLATE NOCASE", value),
+        Operator::Contains | Operator::NotContains => ("{} LIKE ? ESCAPE '\\'", format!("%{}%", escape_like(&value))),
+        Operator::StartsWith => ("{} LIKE ? ESCAPE '\\'", format!("{}%", escape_like(&value))),
+        Operator::EndsWith => ("{} LIKE ? ESCAPE '\\'", format!("%{}", escape_like(&value))),
+        _ => return None,
+    })
+}
+
+fn compile_rule(rule: &Rule, params: &mut Vec<Value>, now: i64) -> Result<String, String> {
+    let field = lookup_field(&rule.field).ok_or_else(|| format!("未知字段: {}", rule.field))?;
+    let unsupported = || format!("字段 {} 不支持操作 {:?}", rule.field, rule.op);
+    let negated = matches!(rule.op, Operator::IsNot | Operator::NotContains);
+
+    match field.kind {
+        FieldKind::Text | FieldKind::Multi { .. } => {
+            if let Operator::IsEmpty | Operator::IsNotEmpty = rule.op {
+                let not = if matches!(rule.op, Operator::IsEmpty) { "" } else { "NOT " };
+                return Ok(format!("{}(COALESCE({}, '') = '')", not, field.expr));
+            }
+            let (template, value) = text_condition(&rule.op, text_value(rule)?).ok_or_else(unsupported)?;
+            let mut condition = template.replace("{}", field.expr);
+            params.push(Value::Text(value.clone()));
+            if let FieldKind::Multi { link } = field.kind {
+                let name_column = if link == GENRE_LINK { "g.name" } else { "a.name" };
+                condition = format!(
+                    "({} OR EXISTS({}))",
+                    condition,
+                    link.replace("{}", &template.replace("{}", name_column))
+                );
+                params.push(Value::Text(value));
+            }
+            Ok(if negated { format!("NOT COALESCE({}, 0)", condition) } else { condition })
+        }
+        FieldKind::Number => {
+            // play_count / skip_count 指定时间窗口时改为统计 play_events
+            let expr = match (rule.field.as_str(), rule.within_days) {
+                ("play_count" | "skip_count", Some(days)) => {
+                    params.push(Value::Integer(now - days as i64 * TimeUnit::Days.millis()));
+                    let column = if rule.field == "play_count" { "completed" } else { "skipped" };
+                    format!(
+                        "(SELECT COALESCE(SUM(e.{}), 0) FROM play_events e WHERE e.song_id = s.id AND e.started_at >= ?)",
+                        column
+                    )
+                }
+                _ => field.expr.to_string(),
+            };
+            let sql = match rule.op {
+                Operator::Is => "{} = ?",
+                Operator::IsNot => "{} IS NOT ?",
+                Operator::Gt => "{} > ?",
+                Operator::Gte => "{} >= ?",
+                Operator::Lt => "{} < ?",
+                Operator::Lte => "{} <= ?",
+                Operator::Between => "{} BETWEEN ? AND ?",
+                Operator::IsEmpty => "{} IS NULL",
+                Operator::IsNotEmpty => "{} IS NOT NULL",
+                _ => return Err(unsupported()),
+            };
+            match rule.op {
+                Operator::Between => {
+                    let bounds = rule.value.as_array().filter(|b| b.len() == 2).ok_or_else(|| {
+                        format!("字段 {} 的 between 需要 [下限, 上限]", rule.field)
+                    })?;
+                    params.push(number_value(&rule.field, &bounds[0])?);
+                    params.push(number_value(&rule.field, &bounds[1])?);
+                }
+                Operator::IsEmpty | Operator::IsNotEmpty => {}
+                _ => params.push(number_value(&rule.field, &rule.value)?),
+            }
+            Ok(sql.replace("{}", &expr))
+        }
+        FieldKind::Date => {
+            let sql = match rule.op {
+                Operator::InLast | Operator::NotInLast => {
+                    let amount = match number_value(&rule.field, &rule.value)? {
+                        Value::Integer(i) => i as f64,
+                        Value::Real(r) => r,
+                        _ => 0.0,
+                    };
+                    let unit = rule.unit.unwrap_or_default();
+                    params.push(Value::Integer(now - (amount * unit.millis() as f64) as i64));
+                    if matches!(rule.op, Operator::InLast) { "{} >= ?" } else { "({} IS NULL OR {} < ?)" }
+                }
+                Operator::Gt | Operator::Gte | Operator::Lt | Operator::Lte | Operator::Is => {
+                    params.push(number_value(&rule.field, &rule.value)?);
+                    match rule.op {
+                        Operator::Gt => "{} > ?",
+                        Operator::Gte => "{} >= ?",
+                        Operator::Lt => "{} < ?",
+                        Operator::Lte => "{} <= ?",
+                        _ => "{} = ?",
+                    }
+                }
+                Operator::IsEmpty => "{} IS NULL",
+                Operator::IsNotEmpty => "{} IS NOT NULL",
+                _ => return Err(unsupported()),
+            };
+            Ok(sql.replace("{}", field.expr))
+        }
+        FieldKind::Flag => {
+            let wanted = match (&rule.op, &rule.value) {
+                (Operator::IsTrue, _) => true,
+                (Operator::IsFalse, _) => false,
+                (Operator::Is, serde_json::Value::Bool(b)) => *b,
+                (Operator::IsNot, serde_json::Value::Bool(b)) => !*b,
+                _ => return Err(unsupported()),
+            };
+            Ok(if wanted { field.expr.to_string() } else { format!("NOT {}", field.expr) })
+        }
+    }
+}
+
+fn compile_group(mode: MatchMode, rules: &[RuleNode], params: &mut Vec<Value>, now: i64) -> Result<String, String> {
+    if rules.is_empty() {
+        return Ok("1".to_string());
+    }
+    let parts = rules
+        .iter()
+        .map(|node| match node {
+            RuleNode::Rule(rule) => compile_rule(rule, params, now),
+            RuleNode::Group(group) => compile_group(group.mode, &group.rules, params, now),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let joiner = match mode {
+        MatchMode::All => " AND ",
+        MatchMode::Any => " OR ",
+    };
+    Ok(format!("({})", parts.join(joiner)))
+}
+
+fn compile_sort(sort: Option<&SmartSort>) -> Result<String, String> {
+    let Some(sort) = sort else {
+        return Ok("m.artist_sort, m.album_sort, COALESCE(m.disc_number, 1), COALESCE(m.track_number, 2147483647), m.path".to_string());
+    };
+    if sort.field == "random" {
+        return Ok("m.rnd".to_string());
+    }
+    let column = lookup_field(&sort.field)
+        .and_then(|f| f.sort)
+        .ok_or_else(|| format!("无法按 {} 排序", sort.field))?;
+    let direction = if sort.desc { "DESC" } else { "ASC" };
+    Ok(format!("m.{} IS NULL, m.{} {}, m.path", column, column, direction))
+}
+
+pub fn query_smart_tracks(conn: &Connection, rules: &SmartRules) -> Result<Vec<Song>, String> {
+    let mut params = Vec::new();
+    let condition = compile_group(rules.mode, &rules.rules, &mut params, now_millis())?;
+    let order = compile_sort(rules.sort.as_ref())?;
+    let limit = rules.limit.clone().unwrap_or_default();
+
+    // 随机排序先给每行一个固定的随机键，保证累计时长与最终顺序一致
+    let sql = format!(
+        "WITH matched AS (
+            SELECT s.*, RANDOM() AS rnd FROM songs s WHERE {}
+        ),
+        ordered AS (
+            SELECT m.*,
+                   ROW_NUMBER() OVER (ORDER BY {order}) AS rn,
+                   SUM(COALESCE(m.duration, 0)) OVER (ORDER BY {order} ROWS UNBOUNDED PRECEDING) AS running
+            FROM matched m
+        )
+        SELECT {} FROM ordered o
+        WHERE (? IS NULL OR o.rn <= ?) AND (? IS NULL OR o.running <= ?)
+        ORDER BY o.rn",
+        condition,
+        song_columns_with("o"),
+        order = order,
+    );
+    let max_tracks = limit.tracks.map(|t| Value::Integer(t as i64)).unwrap_or(Value::Null);
+    let max_seconds = limit.minutes.map(|m| Value::Integer(m as i64 * 60)).unwrap_or(Value::Null);
+    params.extend([max_tracks.clone(), max_tracks, max_seconds.clone(), max_seconds]);
+
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let songs = stmt
+        .query_map(rusqlite::params_from_iter(params.iter()), Song::from_row)
+        .map_err(|e| e.to_string())?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(songs)
+}
+
+fn load_smart_playlist(conn: &Connection, id: i64) -> Result<SmartPlaylist, String> {
+    let (name, rules, created_at, updated_at): (String, String, i64, i64) = conn
+        .query_row(
+            "SELECT name, rules, created_at, updated_at FROM smart_playlists WHERE id = ?1",
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .map_err(|e| e.to_string())?;
+    let rules = serde_json::from_str(&rules).map_err(|e| e.to_string())?;
+    Ok(SmartPlaylist { id, name, rules, created_at, updated_at })
+}
+
+// 保存前先编译一遍，提前暴露字段名 / 操作符错误
+fn validate(rules: &SmartRules) -> Result<String, String> {
+    compile_group(rules.mode, &rules.rules, &mut Vec::new(), 0)?;
+    compile_sort(rules.sort.as_ref())?;
+    serde_json::to_string(rules).map_err(|e| e.to_string())
+}
+
+// --- Commands ---
+
+#[tauri::command]
+pub async fn get_smart_playlists(db_state: State<'_, DbState>) -> Result<Vec<SmartPlaylist>, String> {
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    let ids: Vec<i64> = conn
+        .prepare("SELECT id FROM smart_playlists ORDER BY position, id")
+        .and_then(|mut stmt| stmt.query_map([], |row| row.get(0))?.collect())
+        .map_err(|e| e.to_string())?;
+    ids.into_iter().map(|id| load_smart_playlist(&conn, id)).collect()
+}
+
+#[tauri::command]
+pub async fn create_smart_playlist(name: String, rules: SmartRules, db_state: State<'_, DbState>) -> Result<i64, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("歌单名称不能为空".to_string());
+    }
+    let json = validate(&rules)?;
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    let now = now_millis();
+    conn.execute(
+        "INSERT INTO smart_playlists (name, rules, position, created_at, updated_at)
+         VALUES (?1, ?2, (SELECT COALESCE(MAX(position) + 1, 0) FROM smart_playlists), ?3, ?3)",
+        (name, json, now),
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(conn.last_insert_rowid())
+}
+
+#[tauri::command]
+pub async fn update_smart_playlist(
+    id: i64,
+    name: Option<String>,
+    rules: Option<SmartRules>,
+    db_state: State<'_, DbState>,
+) -> Result<(), String> {
+    let json = rules.as_ref().map(validate).transpose()?;
+    let name = name.map(|n| n.trim().to_string()).filter(|n| !n.is_empty());
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    conn.execute(
+        "UPDATE smart_playlists SET name = COALESCE(?1, name), rules = COALESCE(?2, rules), updated_at = ?3 WHERE id = ?4",
+        (name, json, now_millis(), id),
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn delete_smart_playlist(id: i64, db_state: State<'_, DbState>) -> Result<(), String> {
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM smart_playlists WHERE id = ?1", [id])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_smart_playlist_tracks(id: i64, db_state: State<'_, DbState>) -> Result<Vec<Song>, String> {
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    let playlist = load_smart_playlist(&conn, id)?;
+    query_smart_tracks(&conn, &playlist.rules)
+}
+
+// 编辑器里未保存的规则实时预览
+#[tauri::command]
+pub async fn preview_smart_playlist(rules: SmartRules, db_state: State<'_, DbState>) -> Result<Vec<Song>, String> {
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    query_smart_tracks(&conn, &rules)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000_000;
+
+    fn compile(json: &str) -> Result<(String, Vec<Value>), String> {
+        let rules: SmartRules = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        let mut params = Vec::new();
+        let sql = compile_group(rules.mode, &rules.rules, &mut params, NOW)?;
+        Ok((sql, params))
+    }
+
+    #[test]
+    fn compiles_documented_example() {
+        let (sql, params) = compile(
+            r#"{"match":"all","rules":[{"field":"genre","op":"is","value":"Jazz"},
+                {"field":"rating","op":">=","value":4},
+                {"field":"last_played","op":"not_in_last","value":30,"unit":"days"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            sql,
+            "((s.genre = ? COLLATE NOCASE OR EXISTS(SELECT 1 FROM song_genres sg JOIN genres g ON g.id = sg.genre_id \
+             WHERE sg.song_id = s.id AND g.name = ? COLLATE NOCASE)) \
+             AND COALESCE(s.rating, 0) >= ? \
+             AND (s.last_played IS NULL OR s.last_played < ?))"
+        );
+        assert_eq!(
+            params,
+            [
+                Value::Text("Jazz".into()),
+                Value::Text("Jazz".into()),
+                Value::Integer(4),
+                Value::Integer(NOW - 30 * TimeUnit::Days.millis()),
+            ]
+        );
+    }
+
+    #[test]
+    fn nested_groups_and_negation() {
+        let (sql, params) = compile(
+            r#"{"match":"any","rules":[{"field":"title","op":"not_contains","value":"50%_off"},
+                {"match":"all","rules":[{"field":"favorite","op":"is_true"},{"field":"year","op":"between","value":[1990,1999]}]}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            sql,
+            "(NOT COALESCE(s.title LIKE ? ESCAPE '\\', 0) OR \
+             (EXISTS(SELECT 1 FROM favorites f WHERE f.song_id = s.id) AND s.year BETWEEN ? AND ?))"
+        );
+        assert_eq!(
+            params,
+            [Value::Text("%50\\%\\_off%".into()), Value::Integer(1990), Value::Integer(1999)]
+        );
+    }
+
+    #[test]
+    fn windowed_play_count_counts_recent_events() {
+        let (sql, params) =
+            compile(r#"{"rules":[{"field":"play_count","op":">","value":"2","within_days":7}]}"#).unwrap();
+        assert_eq!(
+            sql,
+            "((SELECT COALESCE(SUM(e.completed), 0) FROM play_events e WHERE e.song_id = s.id AND e.started_at >= ?) > ?)"
+        );
+        assert_eq!(params, [Value::Integer(NOW - 7 * TimeUnit::Days.millis()), Value::Real(2.0)]);
+    }
+
+    #[test]
+    fn empty_rules_match_everything() {
+        assert_eq!(compile("{}").unwrap(), ("1".to_string(), Vec::new()));
+    }
+
+    #[test]
+    fn rejects_bad_rules() {
+        assert!(compile(r#"{"rules":[{"field":"mood","op":"is","value":"happy"}]}"#).is_err());
+        assert!(compile(r#"{"rules":[{"field":"favorite","op":"contains","value":"x"}]}"#).is_err());
+        assert!(compile(r#"{"rules":[{"field":"year","op":"between","value":[1990]}]}"#).is_err());
+        assert!(compile(r#"{"rules":[{"field":"rating","op":"gt","value":"high"}]}"#).is_err());
+    }
+
+    #[test]
+    fn sort_uses_sort_keys_and_rejects_unsortable_fields() {
+        let sort = |field: &str, desc: bool| compile_sort(Some(&SmartSort { field: field.to_string(), desc }));
+        assert_eq!(sort("artist", true).unwrap(), "m.artist_sort IS NULL, m.artist_sort DESC, m.path");
+        assert_eq!(sort("random", false).unwrap(), "m.rnd");
+        assert!(sort("favorite", false).is_err());
+    }
+}
@@ -0,0 +1,260 @@
+use pinyin::ToPinyin;
+use rusqlite::Connection;
+use serde::Serialize;
+
+// --- 排序键：把名字转换成可按字母排序的小写拉丁串 ---
+// 汉字 -> 拼音 (无声调)，假名 -> 罗马字，带变音符的拉丁字母去掉变音符。
+// 标签里有 TSOP / TSOA 等排序字段时优先使用标签，见 metadata::read_metadata
+
+#[derive(Serialize, Clone, Debug)]
+pub struct IndexBucket {
+    // "A".."Z" 或 "#"
+    pub letter: String,
+    // 该字母第一项在列表中的下标
+    pub offset: usize,
+    pub count: usize,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct IndexedList<T> {
+    pub items: Vec<T>,
+    pub index: Vec<IndexBucket>,
+}
+
+// 平假名罗马字表，片假名先平移到平假名再查表
+const KANA_ROMAJI: &[(char, &str)] = &[
+    ('あ', "a"), ('い', "i"), ('う', "u"), ('え', "e"), ('お', "o"),
+    ('ぁ', "a"), ('ぃ', "i"), ('ぅ', "u"), ('ぇ', "e"), ('ぉ', "o"),
+    ('か', "ka"), ('き', "ki"), ('く', "ku"), ('け', "ke"), ('こ', "ko"),
+    ('が', "ga"), ('ぎ', "gi"), ('ぐ', "gu"), ('げ', "ge"), ('ご', "go"),
+    ('さ', "sa"), ('し', "shi"), ('す', "su"), ('せ', "se"), ('そ', "so"),
+    ('ざ', "za"), ('じ', "ji"), ('ず', "zu"), ('ぜ', "ze"), ('ぞ', "zo"),
+    ('た', "ta"), ('ち', "chi"), ('つ', "tsu"), ('て', "te"), ('と', "to"),
+    ('だ', "da"), ('ぢ', "ji"), ('づ', "zu"), ('で', "de"), ('ど', "do"),
+    ('な', "na"), ('に', "ni"), ('ぬ', "nu"), ('ね', "ne"), ('の', "no"),
+    ('は', "ha"), ('ひ', "hi"), ('ふ', "fu"), ('へ', "he"), ('ほ', "ho"),
+    ('ば', "ba"), ('び', "bi"), ('ぶ', "bu"), ('べ', "be"), ('ぼ', "bo"),
+    ('ぱ', "pa"), ('ぴ', "pi"), ('ぷ', "pu"), ('ぺ', "pe"), ('ぽ', "po"),
+    ('ま', "ma"), ('み', "mi"), ('む', "mu"), ('め', "me"), ('も', "mo"),
+    ('や', "ya"), ('ゆ', "yu"), ('よ', "yo"), ('ゃ', "ya"), ('ゅ', "yu"), ('ょ', "yo"),
+    ('ら', "ra"), ('り', "ri"), ('る', "ru"), ('れ', "re"), ('ろ', "ro"),
+    ('わ', "wa"), ('ゐ', "i"), ('ゑ', "e"), ('を', "o"), ('ん', "n"),
+    ('ゔ', "vu"), ('ゎ', "wa"), ('ゕ', "ka"), ('ゖ', "ke"),
+];
+
+fn kana_romaji(c: char) -> Option<&'static str> {
+    let hira = match c {
+        // 片假名 ァ..ヶ 与平假名 ぁ..ゖ 相差 0x60
+        '\u{30A1}'..='\u{30F6}' => char::from_u32(c as u32 - 0x60)?,
+        '\u{3041}'..='\u{3096}' => c,
+        _ => return None,
+    };
+    KANA_ROMAJI.iter().find(|(k, _)| *k == hira).map(|(_, r)| *r)
+}
+
+// 常见带变音符拉丁字母 -> 基础字母
+fn fold_latin(c: char) -> Option<&'static str> {
+    Some(match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' | 'ǎ' => "a",
+        'æ' => "ae",
+        'ç' | 'ć' | 'č' => "c",
+        'ď' | 'đ' | 'ð' => "d",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ė' | 'ę' | 'ě' => "e",
+        'ğ' => "g",
+        'ì' | 'í' | 'î' | 'ï' | 'ī' | 'ǐ' | 'ı' => "i",
+        'ł' => "l",
+        'ñ' | 'ń' | 'ň' => "n",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ő' | 'ǒ' => "o",
+        'œ' => "oe",
+        'ř' => "r",
+        'ś' | 'š' | 'ş' => "s",
+        'ß' => "ss",
+        'ť' | 'ţ' => "t",
+        'ù' | 'ú' | 'û' | 'ü' | 'ū' | 'ů' | 'ű' | 'ǔ' | 'ǖ' | 'ǘ' | 'ǚ' | 'ǜ' => "u",
+        'ý' | 'ÿ' => "y",
+        'ź' | 'ż' | 'ž' => "z",
+        'þ' => "th",
+        _ => return None,
+    })
+}
+
+pub fn sort_key(name: &str) -> String {
+    let mut key = String::with_capacity(name.len());
+    let mut chars = name.trim().chars().peekable();
+    while let Some(c) = chars.next() {
+        let lower = c.to_lowercase().next().unwrap_or(c);
+        if lower.is_ascii_alphanumeric() {
+            key.push(lower);
+        } else if let Some(folded) = fold_latin(lower) {
+            key.push_str(folded);
+        } else if let Some(py) = c.to_pinyin() {
+            // 汉字之间用空格隔开，"zhou jie lun" 才能与 "zhoujie" 之类正确比较
+            if !key.is_empty() && !key.ends_with(' ') {
+                key.push(' ');
+            }
+            key.push_str(py.plain());
+            key.push(' ');
+        } else if c == 'っ' || c == 'ッ' {
+            // 促音：重复下一个假名的首辅音
+            if let Some(next) = chars.peek().and_then(|n| kana_romaji(*n)) {
+                key.push_str(&next[..1]);
+            }
+        } else if matches!(c, 'ゃ' | 'ゅ' | 'ょ' | 'ャ' | 'ュ' | 'ョ') && key.ends_with('i') {
+            // 拗音：きゃ -> kya，しゃ -> sha，じゃ -> ja
+            let vowel = &kana_romaji(c).unwrap_or("ya")[1..];
+            key.pop();
+            if !(key.ends_with("sh") || key.ends_with("ch") || key.ends_with('j')) {
+                key.push('y');
+            }
+            key.push_str(vowel);
+        } else if c == 'ー' {
+            // 长音：重复上一个元音
+            if let Some(last) = key.chars().last().filter(|l| "aeiou".contains(*l)) {
+                key.push(last);
+            }
+        } else if let Some(romaji) = kana_romaji(c) {
+            key.push_str(romaji);
+        } else if c.is_whitespace() {
+            if !key.is_empty() && !key.ends_with(' ') {
+                key.push(' ');
+            }
+        } else if c.is_alphanumeric() {
+            key.extend(c.to_lowercase());
+        }
+        // 其余标点符号不参与排序
+    }
+    key.trim().to_string()
+}
+
+// 标签排序字段优先，否则由名字计算
+pub fn sort_key_or(tag_sort: Option<&str>, name: &str) -> String {
+    match tag_sort.map(|s| s.trim()).filter(|s| !s.is_empty()) {
+        Some(sort) => sort_key(sort),
+        None => sort_key(name),
+    }
+}
+
+pub fn index_letter(key: &str) -> String {
+    match key.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() => c.to_ascii_uppercase().to_string(),
+        _ => "#".to_string(),
+    }
+}
+
+// 列表需已按排序键排好；"#" 组 (数字、符号等) 整体移到末尾，与常见字母索引栏一致
+pub fn build_index<T>(mut items: Vec<T>, key_of: impl Fn(&T) -> &str) -> IndexedList<T> {
+    items.sort_by_cached_key(|item| index_letter(key_of(item)) == "#");
+
+    let mut index: Vec<IndexBucket> = Vec::new();
+    for (i, item) in items.iter().enumerate() {
+        let letter = index_letter(key_of(item));
+        match index.last_mut() {
+            Some(bucket) if bucket.letter == letter => bucket.count += 1,
+            _ => index.push(IndexBucket { letter, offset: i, count: 1 }),
+        }
+    }
+    IndexedList { items, index }
+}
+
+// --- 旧数据回填：升级后第一次启动时为已有行补上排序键 ---
+pub fn backfill_sort_keys(conn: &Connection) -> rusqlite::Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    {
+        let mut select = tx.prepare(
+            "SELECT id, title, artist, album, album_artist, path FROM songs WHERE title_sort IS NULL",
+        )?;
+        type Row = (i64, Option<String>, Option<String>, Option<String>, Option<String>, String);
+        let rows: Vec<Row> = select
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?)))?
+            .filter_map(|r| r.ok())
+            .collect();
+        let mut update = tx.prepare(
+            "UPDATE songs SET title_sort = ?1, artist_sort = ?2, album_sort = ?3, album_artist_sort = ?4 WHERE id = ?5",
+        )?;
+        for (id, title, artist, album, album_artist, path) in rows {
+            let title = title.filter(|t| !t.is_empty()).unwrap_or_else(|| {
+                std::path::Path::new(&path)
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().to_string())
+                    .unwrap_or_default()
+            });
+            let artist = artist.unwrap_or_default();
+            let album_artist_key = sort_key(album_artist.as_deref().unwrap_or(&artist));
+            update.execute((
+                sort_key(&title),
+                sort_key(&artist),
+                sort_key(&album.unwrap_or_default()),
+                album_artist_key,
+                id,
+            ))?;
+        }
+
+        for table in ["artists", "genres"] {
+            let rows: Vec<(i64, String)> = tx
+                .prepare(&format!("SELECT id, name FROM {} WHERE sort_name IS NULL", table))?
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .filter_map(|r| r.ok())
+                .collect();
+            for (id, name) in rows {
+                tx.execute(&format!("UPDATE {} SET sort_name = ?1 WHERE id = ?2", table), (sort_key(&name), id))?;
+            }
+        }
+
+        let rows: Vec<(i64, String, String)> = tx
+            .prepare("SELECT id, title, album_artist FROM albums WHERE title_sort IS NULL")?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .filter_map(|r| r.ok())
+            .collect();
+        for (id, title, album_artist) in rows {
+            tx.execute(
+                "UPDATE albums SET title_sort = ?1, album_artist_sort = ?2 WHERE id = ?3",
+                (sort_key(&title), sort_key(&album_artist), id),
+            )?;
+        }
+    }
+    tx.commit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latin_names_fold_case_and_diacritics() {
+        assert_eq!(sort_key("Beyoncé"), "beyonce");
+        assert_eq!(sort_key("Ænima"), "aenima");
+        assert_eq!(sort_key("  The   Beatles! "), "the beatles");
+        assert_eq!(sort_key("AC/DC"), "acdc");
+    }
+
+    #[test]
+    fn hanzi_become_spaced_pinyin() {
+        assert_eq!(sort_key("周杰伦"), "zhou jie lun");
+        assert_eq!(sort_key("G.E.M.邓紫棋"), "gem deng zi qi");
+    }
+
+    #[test]
+    fn kana_become_romaji() {
+        assert_eq!(sort_key("さくら"), "sakura");
+        assert_eq!(sort_key("しゃ"), "sha");
+        assert_eq!(sort_key("キャッツ"), "kyattsu");
+        assert_eq!(sort_key("ラーメン"), "raamen");
+    }
+
+    #[test]
+    fn tag_sort_field_wins_when_present() {
+        assert_eq!(sort_key_or(Some(" Beatles, The "), "The Beatles"), "beatles the");
+        assert_eq!(sort_key_or(Some("  "), "The Beatles"), "the beatles");
+        assert_eq!(sort_key_or(None, "周杰伦"), "zhou jie lun");
+    }
+
+    #[test]
+    fn index_moves_symbols_to_the_end() {
+        let keys = vec!["1999", "apple", "avocado", "banana", "zebra", "ärger"];
+        let list = build_index(keys, |k| k);
+        assert_eq!(list.items, ["apple", "avocado", "banana", "zebra", "1999", "ärger"]);
+        let buckets: Vec<(&str, usize, usize)> =
+            list.index.iter().map(|b| (b.letter.as_str(), b.offset, b.count)).collect();
+        assert_eq!(buckets, [("A", 0, 2), ("B", 2, 1), ("Z", 3, 1), ("#", 4, 2)]);
+    }
+}
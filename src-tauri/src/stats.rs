@@ -0,0 +1,171 @@
+use crate::database::DbState;
+use crate::metadata::UNKNOWN_ARTIST;
+use rusqlite::Connection;
+use serde::Serialize;
+use tauri::State;
+
+// --- 曲库统计：只读 songs 及关联表，供曲库健康度面板使用 ---
+
+const TOP_LIMIT: u32 = 10;
+
+// 无损格式；m4a 可能是 AAC 也可能是 ALAC，有位深的视为 ALAC
+const LOSSLESS_CONDITION: &str = "(LOWER(s.format) IN ('flac', 'wav', 'aiff', 'aif', 'ape', 'wv', 'tta', 'dsf', 'dff') \
+    OR (LOWER(s.format) IN ('m4a', 'mp4') AND s.bit_depth IS NOT NULL))";
+
+// 播放时长：优先实际收听时长，旧版导入的记录按整首计
+const PLAY_TIME_MS: &str = "SUM(COALESCE(e.listened_ms, s.duration * 1000))";
+
+#[derive(Serialize, Clone, Debug)]
+pub struct StatBucket {
+    // 格式名、采样率或位深 (未知为 "unknown")
+    pub key: String,
+    pub track_count: u32,
+    pub total_duration: u64,
+    pub total_size: u64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct RankedName {
+    pub id: i64,
+    pub name: String,
+    pub track_count: u32,
+    // 毫秒
+    pub play_time: u64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct LibraryStats {
+    pub track_count: u32,
+    // 秒
+    pub total_duration: u64,
+    // 字节
+    pub total_size: u64,
+    pub formats: Vec<StatBucket>,
+    pub sample_rates: Vec<StatBucket>,
+    pub bit_depths: Vec<StatBucket>,
+    pub lossless_count: u32,
+    pub lossy_count: u32,
+    // 按曲目数计的无损占比 0..1
+    pub lossless_share: f64,
+    pub top_artists_by_tracks: Vec<RankedName>,
+    pub top_artists_by_play_time: Vec<RankedName>,
+    pub top_genres_by_tracks: Vec<RankedName>,
+    pub top_genres_by_play_time: Vec<RankedName>,
+    pub albums_without_cover: u32,
+    // 没有标题或没有艺人的曲目
+    pub tracks_without_tags: u32,
+}
+
+fn buckets(conn: &Connection, key_expr: &str) -> rusqlite::Result<Vec<StatBucket>> {
+    let sql = format!(
+        "SELECT COALESCE(CAST({} AS TEXT), 'unknown') AS k, COUNT(*), COALESCE(SUM(s.duration), 0), COALESCE(SUM(s.file_size), 0)
+         FROM songs s GROUP BY k ORDER BY COUNT(*) DESC",
+        key_expr
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(StatBucket {
+                key: row.get(0)?,
+                track_count: row.get(1)?,
+                total_duration: row.get::<_, i64>(2)? as u64,
+                total_size: row.get::<_, i64>(3)? as u64,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
+}
+
+// from: 名字表 x 与关联表 link (含 link.song_id) 的联接
+fn ranked(conn: &Connection, from: &str, order_by_play_time: bool) -> rusqlite::Result<Vec<RankedName>> {
+    let sql = format!(
+        "WITH song_play AS (
+            SELECT e.song_id, {play} AS ms
+            FROM play_events e JOIN songs s ON s.id = e.song_id
+            GROUP BY e.song_id
+        )
+        SELECT x.id, x.name, COUNT(*), COALESCE(SUM(sp.ms), 0) AS play_time
+        FROM {from}
+        LEFT JOIN song_play sp ON sp.song_id = link.song_id
+        GROUP BY x.id
+        ORDER BY {order} DESC, x.sort_name
+        LIMIT ?1",
+        play = PLAY_TIME_MS,
+        from = from,
+        order = if order_by_play_time { "play_time" } else { "COUNT(*)" },
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt
+        .query_map([TOP_LIMIT], |row| {
+            Ok(RankedName {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                track_count: row.get(2)?,
+                play_time: row.get::<_, i64>(3)? as u64,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .filter(|r| !order_by_play_time || r.play_time > 0)
+        .collect();
+    Ok(rows)
+}
+
+pub fn collect_stats(conn: &Connection) -> rusqlite::Result<LibraryStats> {
+    let (track_count, total_duration, total_size): (u32, i64, i64) = conn.query_row(
+        "SELECT COUNT(*), COALESCE(SUM(duration), 0), COALESCE(SUM(file_size), 0) FROM songs",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )?;
+    let lossless_count: u32 = conn.query_row(
+        &format!("SELECT COUNT(*) FROM songs s WHERE {}", LOSSLESS_CONDITION),
+        [],
+        |row| row.get(0),
+    )?;
+
+    let artists = "artists x JOIN song_artists link ON link.artist_id = x.id AND link.role = 'artist'";
+    let genres = "genres x JOIN song_genres link ON link.genre_id = x.id";
+
+    // 按扫描时记录的封面键判断，cover_path 只是可被缓存清理删掉的缩略图
+    let albums_without_cover: u32 = conn.query_row(
+        "SELECT COUNT(*) FROM albums a
+         WHERE EXISTS (SELECT 1 FROM songs s WHERE s.album_id = a.id)
+           AND NOT EXISTS (
+               SELECT 1 FROM songs s JOIN song_covers c ON c.song_id = s.id
+               WHERE s.album_id = a.id AND c.cover_key IS NOT NULL
+           )",
+        [],
+        |row| row.get(0),
+    )?;
+    let tracks_without_tags: u32 = conn.query_row(
+        "SELECT COUNT(*) FROM songs WHERE COALESCE(title, '') = '' OR COALESCE(artist, '') IN ('', ?1)",
+        [UNKNOWN_ARTIST],
+        |row| row.get(0),
+    )?;
+
+    Ok(LibraryStats {
+        track_count,
+        total_duration: total_duration as u64,
+        total_size: total_size as u64,
+        formats: buckets(conn, "LOWER(s.format)")?,
+        sample_rates: buckets(conn, "NULLIF(s.sample_rate, 0)")?,
+        bit_depths: buckets(conn, "s.bit_depth")?,
+        lossless_count,
+        lossy_count: track_count - lossless_count,
+        lossless_share: if track_count > 0 { lossless_count as f64 / track_count as f64 } else { 0.0 },
+        top_artists_by_tracks: ranked(conn, artists, false)?,
+        top_artists_by_play_time: ranked(conn, artists, true)?,
+        top_genres_by_tracks: ranked(conn, genres, false)?,
+        top_genres_by_play_time: ranked(conn, genres, true)?,
+        albums_without_cover,
+        tracks_without_tags,
+    })
+}
+
+// --- Commands ---
+
+#[tauri::command]
+pub async fn get_library_stats(db_state: State<'_, DbState>) -> Result<LibraryStats, String> {
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
+    collect_stats(&conn).map_err(|e| e.to_string())
+}
@@ -5,6 +5,10 @@ use lofty::probe::Probe;
 use lofty::prelude::*;
 use regex::Regex;
 use walkdir::WalkDir;
+use tauri::State;
+
+use crate::database::DbState;
+use crate::music::relocate_song;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RenameConfig {
@@ -181,8 +185,9 @@ pub fn preview_rename(root_path: String, config: RenameConfig) -> Result<Vec<Ren
 }
 
 #[tauri::command]
-pub fn apply_rename(operations: Vec<RenameOperation>) -> Result<u32, String> {
+pub fn apply_rename(operations: Vec<RenameOperation>, db_state: State<'_, DbState>) -> Result<u32, String> {
     let mut success_count = 0;
+    let conn = db_state.conn.lock().map_err(|e| e.to_string())?;
     
     for op in operations {
         let src = PathBuf::from(&op.original_path);
@@ -190,6 +195,10 @@ pub fn apply_rename(operations: Vec<RenameOperation>) -> Result<u32, String> {
             let dest = parent.join(&op.new_name);
             if fs::rename(&src, &dest).is_ok() {
                 success_count += 1;
+                // 同步数据库路径，保持歌曲 id (及歌单引用) 不变
+                if let Err(e) = relocate_song(&conn, &op.original_path, &dest.to_string_lossy()) {
+                    eprintln!("Failed to update song path {:?}: {}", dest, e);
+                }
             } else {
                 eprintln!("Failed to rename {:?} to {:?}", src, dest);
             }
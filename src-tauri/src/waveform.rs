@@ -0,0 +1,94 @@
+use crate::cache::{self, CacheKind};
+use rodio::{Decoder, Source};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+// --- 波形：进度条背景用的峰值序列 ---
+// 先按 WINDOWS_PER_SECOND 取每个窗口的峰值，解码完成后再合并成 WAVEFORM_POINTS 个点，
+// 结果为 0..=255 (按最响的点归一化)，按源文件缓存在 waveforms 缓存目录
+
+const WAVEFORM_POINTS: usize = 256;
+const WINDOWS_PER_SECOND: u32 = 20;
+
+fn window_peaks(path: &Path) -> Result<Vec<f32>, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let source = Decoder::new(BufReader::new(file)).map_err(|e| e.to_string())?;
+    let window = (source.sample_rate() / WINDOWS_PER_SECOND).max(1) as usize * source.channels().max(1) as usize;
+
+    let mut peaks = Vec::new();
+    let mut peak = 0.0f32;
+    for (i, sample) in source.enumerate() {
+        peak = peak.max((sample as f32).abs());
+        if (i + 1) % window == 0 {
+            peaks.push(peak);
+            peak = 0.0;
+        }
+    }
+    if peak > 0.0 {
+        peaks.push(peak);
+    }
+    Ok(peaks)
+}
+
+// 合并成固定点数并归一化；比 points 短的音频按实际窗口数返回
+pub fn downsample(peaks: &[f32], points: usize) -> Vec<u8> {
+    if peaks.is_empty() || points == 0 {
+        return Vec::new();
+    }
+    let merged: Vec<f32> = if peaks.len() <= points {
+        peaks.to_vec()
+    } else {
+        (0..points)
+            .map(|i| {
+                let (start, end) = (i * peaks.len() / points, (i + 1) * peaks.len() / points);
+                peaks[start..end].iter().copied().fold(0.0, f32::max)
+            })
+            .collect()
+    };
+    let max = merged.iter().copied().fold(0.0, f32::max);
+    if max <= 0.0 {
+        return vec![0; merged.len()];
+    }
+    merged.iter().map(|p| (p / max * 255.0).round() as u8).collect()
+}
+
+pub fn waveform(path: &Path) -> Result<Vec<u8>, String> {
+    let cached = cache::manager().zip(cache::source_key(path));
+    if let Some((cache, key)) = &cached {
+        if let Some(bytes) = cache.read(CacheKind::Waveforms, key) {
+            return Ok(bytes);
+        }
+    }
+    let points = downsample(&window_peaks(path)?, WAVEFORM_POINTS);
+    if let Some((cache, key)) = cached {
+        if let Err(e) = cache.write(CacheKind::Waveforms, &key, &points) {
+            eprintln!("波形缓存写入失败: {}", e);
+        }
+    }
+    Ok(points)
+}
+
+// --- Commands ---
+
+#[tauri::command]
+pub async fn get_waveform(path: String) -> Result<Vec<u8>, String> {
+    tauri::async_runtime::spawn_blocking(move || waveform(Path::new(&path)))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn downsample_takes_bucket_peaks_and_normalizes() {
+        let peaks = [0.1, 0.5, 0.2, 0.25, 0.0, 0.05];
+        assert_eq!(downsample(&peaks, 3), [255, 128, 26]);
+        // 点数不足时不插值
+        assert_eq!(downsample(&[0.5, 1.0], 4), [128, 255]);
+        assert_eq!(downsample(&[0.0, 0.0], 4), [0, 0]);
+        assert!(downsample(&[], 4).is_empty());
+    }
+}
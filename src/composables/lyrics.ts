@@ -2,11 +2,50 @@ import { ref, computed, reactive } from 'vue';
 import { invoke } from '@tauri-apps/api/core';
 import { currentSong, currentTime, AUDIO_DELAY } from './playerState';
 
+// 逐字时间 (秒)，用于卡拉 OK 高亮
+export interface LyricWord {
+  start: number;
+  end: number | null;
+  text: string;
+}
+
+// 和声 / 背景人声 (TTML)
+export interface BackgroundVocals {
+  text: string;
+  words: LyricWord[];
+}
+
 export interface LyricLine {
   time: number;       
   text: string;       
   translation: string;
   romaji: string;     
+  words: LyricWord[];
+  background: BackgroundVocals | null;
+}
+
+type RawWord = { start: number; end: number | null; text: string };
+
+// 后端 get_parsed_lyrics 的返回值，时间单位为毫秒
+interface ParsedLyrics {
+  synced: boolean;
+  offset: number;
+  tags: Record<string, string>;
+  lines: {
+    time: number;
+    text: string;
+    translation: string | null;
+    romaji: string | null;
+    words: RawWord[];
+    background: { text: string; words: RawWord[] } | null;
+  }[];
+  // sylt / uslt / vorbis / mp4 / tag / comment / file
+  source: string;
+  language: string | null;
+  // lrc / ttml / srt / vtt / txt
+  format: string;
+  // 歌词原文
+  content: string;
 }
 
 export const lyricsSettings = reactive({
@@ -20,75 +59,66 @@ export const lyricsSettings = reactive({
 export const showDesktopLyrics = ref(false); 
 const rawLyrics = ref<string>('');
 const parsedLyrics = ref<LyricLine[]>([]);
-
-function parseLrc(lrc: string): LyricLine[] {
-  const lines = lrc.split('\n');
-  const rawEntries: { time: number, text: string }[] = [];
-  const timeExp = /\[(\d{2}):(\d{2})\.(\d{2,3})\]/;
-
-  for (const line of lines) {
-    const match = timeExp.exec(line);
-    if (match) {
-      const min = parseInt(match[1]);
-      const sec = parseInt(match[2]);
-      const ms = match[3].length === 2 ? parseInt(match[3]) * 10 : parseInt(match[3]);
-      const time = min * 60 + sec + ms / 1000;
-      const text = line.replace(timeExp, '').trim();
-      if (text) rawEntries.push({ time, text });
-    }
-  }
-
-  rawEntries.sort((a, b) => a.time - b.time);
-
-  const result: LyricLine[] = [];
-  if (rawEntries.length === 0) return result;
-
-  let currentGroup = { time: rawEntries[0].time, texts: [rawEntries[0].text] };
-
-  for (let i = 1; i < rawEntries.length; i++) {
-    const entry = rawEntries[i];
-    if (Math.abs(entry.time - currentGroup.time) < 0.05) {
-      currentGroup.texts.push(entry.text);
-    } else {
-      result.push(mapGroupToLine(currentGroup));
-      currentGroup = { time: entry.time, texts: [entry.text] };
-    }
-  }
-  result.push(mapGroupToLine(currentGroup));
-  return result;
+// false: 没有时间轴的歌词，只静态展示，不滚动也不高亮
+const lyricsSynced = ref(false);
+
+function toLyricWords(words: RawWord[]): LyricWord[] {
+  return words.map(w => ({
+    start: w.start / 1000,
+    end: w.end === null ? null : w.end / 1000,
+    text: w.text
+  }));
 }
 
-function mapGroupToLine(group: { time: number, texts: string[] }): LyricLine {
-  return {
-    time: group.time,
-    text: group.texts[0] || '',
-    translation: group.texts[1] || '', 
-    romaji: group.texts[2] || ''       
-  };
+function toLyricLines(parsed: ParsedLyrics): LyricLine[] {
+  return parsed.lines.map(line => ({
+    time: line.time / 1000,
+    text: line.text,
+    translation: line.translation || '',
+    romaji: line.romaji || '',
+    words: toLyricWords(line.words),
+    background: line.background
+      ? { text: line.background.text, words: toLyricWords(line.background.words) }
+      : null
+  }));
 }
 
 async function loadLyrics() {
   if (!currentSong.value) {
     rawLyrics.value = '';
     parsedLyrics.value = [];
+    lyricsSynced.value = false;
     return;
   }
   try {
-    const lrc = await invoke<string>('get_song_lyrics', { path: currentSong.value.path });
-    rawLyrics.value = lrc;
-    parsedLyrics.value = parseLrc(lrc);
+    const parsed = await invoke<ParsedLyrics | null>('get_parsed_lyrics', { path: currentSong.value.path });
+    rawLyrics.value = parsed ? parsed.content : '';
+    lyricsSynced.value = !!parsed && parsed.synced;
+    parsedLyrics.value = parsed ? toLyricLines(parsed) : [];
   } catch (e) {
     console.error("歌词加载失败:", e);
+    rawLyrics.value = '';
     parsedLyrics.value = [];
+    lyricsSynced.value = false;
   }
 }
 
+// 加上延迟补偿后的歌词时间，确保“声音出来后”才高亮
+const lyricTime = computed(() => currentTime.value - AUDIO_DELAY.value);
+
+// 逐字高亮进度 (0~1)；最后一行的字没有 end，用 fallbackEnd 兜底
+function wordProgress(word: LyricWord, fallbackEnd: number): number {
+  const end = word.end ?? fallbackEnd;
+  if (lyricTime.value >= end) return 1;
+  if (lyricTime.value <= word.start || end <= word.start) return 0;
+  return (lyricTime.value - word.start) / (end - word.start);
+}
+
 // 🟢 严格匹配逻辑：找到最后一个“时间小于等于当前时间”的歌词
 const currentLyricIndex = computed(() => {
-  if (parsedLyrics.value.length === 0) return -1;
+  if (!lyricsSynced.value || parsedLyrics.value.length === 0) return -1;
   
-  // 加上延迟补偿，确保“声音出来后”才高亮
-  const targetTime = currentTime.value - AUDIO_DELAY.value;
+  const targetTime = lyricTime.value;
   
   // 使用倒序查找（效率更高，也更符合逻辑）
   // 从后往前找，找到第一个 time <= targetTime 的就是当前句
@@ -101,7 +131,7 @@ const currentLyricIndex = computed(() => {
 });
 
 const currentLyricLine = computed(() => {
-  if (parsedLyrics.value.length === 0) {
+  if (!lyricsSynced.value || parsedLyrics.value.length === 0) {
     const fallback = rawLyrics.value.trim() ? '暂无滚动歌词' : '纯音乐 / 暂无歌词';
     return { text: fallback, lines: [fallback] };
   }
@@ -129,6 +159,8 @@ export function useLyrics() {
     currentLyricLine,
     currentLyricIndex, 
     parsedLyrics, 
+    lyricsSynced,
+    wordProgress,
     loadLyrics
   };
 }
\ No newline at end of file
@@ -8,7 +8,6 @@ export * from './playerState';
 import { useLyrics } from './lyrics';
 import { useToast } from './toast';
 import { extractDominantColors } from './colorExtraction';
-import { convertFileSrc } from '@tauri-apps/api/core';
 
 // 动画帧 ID
 
@@ -407,7 +406,7 @@ export function usePlayer() {
 
     librarySongs.value.forEach(s => { 
 
-      const k = (s.year && s.year.length>=4) ? s.year.substring(0,4) : 'Unknown'; 
+      const k = s.year ? String(s.year) : 'Unknown'; 
 
       map.set(k, (map.get(k)||0)+1); 
 
@@ -485,7 +484,7 @@ export function usePlayer() {
 
     }
 
-    return State.songList.value.filter(s => (s.artist||'Unknown') === State.filterCondition.value || (s.album||'Unknown') === State.filterCondition.value || (s.genre||'Unknown') === State.filterCondition.value || ((s.year?.substring(0,4))||'Unknown') === State.filterCondition.value);
+    return State.songList.value.filter(s => (s.artist||'Unknown') === State.filterCondition.value || (s.album||'Unknown') === State.filterCondition.value || (s.genre||'Unknown') === State.filterCondition.value || (s.year ? String(s.year) : 'Unknown') === State.filterCondition.value);
 
   });
 
@@ -752,7 +751,7 @@ export function usePlayer() {
     } catch(e){console.error(e);} 
 
   }
-  function generateOrganizedPath(song: State.Song): string { const root = State.settings.value.organizeRoot || 'D:\\Music'; const sep = root.includes('/') ? '/' : '\\'; if (!State.settings.value.enableAutoOrganize) return ""; const clean = (s: string) => s.replace(/[<>:"/\\|?*]/g, '_').trim(); const artist = clean(song.artist && song.artist !== 'Unknown' ? song.artist : 'Unknown Artist'); const album = clean(song.album && song.album !== 'Unknown' ? song.album : 'Unknown Album'); const title = clean(song.title || song.name); const year = clean(song.year ? song.year.substring(0,4) : '0000'); let relativePath = State.settings.value.organizeRule.replace('{Artist}', artist).replace('{Album}', album).replace('{Title}', title).replace('{Year}', year); relativePath = relativePath.replace(/\/\//g, '/').replace(/\\\\/g, '\\'); return `${root}${sep}${relativePath}`; }
+  function generateOrganizedPath(song: State.Song): string { const root = State.settings.value.organizeRoot || 'D:\\Music'; const sep = root.includes('/') ? '/' : '\\'; if (!State.settings.value.enableAutoOrganize) return ""; const clean = (s: string) => s.replace(/[<>:"/\\|?*]/g, '_').trim(); const artist = clean(song.artist && song.artist !== 'Unknown' ? song.artist : 'Unknown Artist'); const album = clean(song.album && song.album !== 'Unknown' ? song.album : 'Unknown Album'); const title = clean(song.title || song.name); const year = clean(song.year ? String(song.year).padStart(4, '0') : '0000'); let relativePath = State.settings.value.organizeRule.replace('{Artist}', artist).replace('{Album}', album).replace('{Title}', title).replace('{Year}', year); relativePath = relativePath.replace(/\/\//g, '/').replace(/\\\\/g, '\\'); return `${root}${sep}${relativePath}`; }
   async function moveFile(song: State.Song, newPath: string) { try { await invoke('move_music_file', { oldPath: song.path, newPath }); const oldPath = song.path; const target = State.songList.value.find(s => s.path === oldPath); if (target) target.path = newPath; if (State.currentSong.value && State.currentSong.value.path === oldPath) State.currentSong.value.path = newPath; State.playlists.value.forEach(pl => { const i = pl.songPaths.indexOf(oldPath); if(i!==-1) pl.songPaths[i]=newPath; }); const fi = State.favoritePaths.value.indexOf(oldPath); if(fi!==-1) State.favoritePaths.value[fi]=newPath; return true; } catch (e) { useToast().showToast(`整理失败: ${e}`, "error"); return false; } }
   function handleAutoNext() { if (State.playMode.value===1 && State.currentSong.value) { playSong(State.currentSong.value); } else { nextSong(); } }
   async function handleVolume(e:Event) { const v=parseInt((e.target as HTMLInputElement).value); State.volume.value=v; await invoke('set_volume',{volume:v/100.0}); }
@@ -961,12 +960,9 @@ export function usePlayer() {
     }, { deep: true });
 
     watch(State.currentCover, async (newCover) => {
-      if (newCover) {
-        let url = newCover;
-        if (!newCover.startsWith('http') && !newCover.startsWith('data:')) {
-          url = convertFileSrc(newCover);
-        }
-        const colors = await extractDominantColors(url, 4);
+      const songPath = State.currentSong.value?.path;
+      if (newCover && songPath) {
+        const colors = await extractDominantColors(songPath, 4);
         State.dominantColors.value = colors;
       }
     });
@@ -1,4 +1,6 @@
 export interface Song {
+  id?: number;
+  album_id?: number;
   name: string;
   title?: string; 
   path: string;
@@ -6,13 +8,24 @@ export interface Song {
   album: string;
   duration: number;
   genre?: string;
-  year?: string;
+  year?: number;
   cover?: string;
   // Audio quality fields (v1.1.1)
   bitrate?: number;
   sample_rate?: number;
   bit_depth?: number;
   format?: string;
+  // Full tag metadata (v1.2.0)
+  album_artist?: string;
+  track_number?: number;
+  track_total?: number;
+  disc_number?: number;
+  disc_total?: number;
+  composer?: string;
+  play_count?: number;
+  skip_count?: number;
+  last_played?: number;
+  rating?: number;
 }
 
 export interface HistoryItem { 